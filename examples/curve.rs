@@ -3,7 +3,6 @@ mod common;
 use shiny::{
     color::{Color, Space as ColorSpace},
     image::{Image, PixelFormat},
-    math::vector2::Vec2,
     pixel_buffer::PixelBuffer,
     shapes::{
         bezier::{Bezier, CubicSlice},
@@ -4,10 +4,7 @@ use shiny::{
     color::{Color, Space as ColorSpace},
     image::{Image, PixelFormat},
     pixel_buffer::PixelBuffer,
-    shapes::{
-        bezier::{Bezier, CubicSlice},
-        point::Point,
-    },
+    shapes::bezier::{Bezier, CubicSlice},
 };
 
 use common::write_png;
@@ -2,10 +2,10 @@ use crate::shapes::path::Path;
 
 #[derive(Debug)]
 struct Replace {
-    position: u16,
-    segment_id: u16,
-    first_point: u16,
-    one_past_last_point: u16,
+    position: u32,
+    segment_id: u32,
+    first_point: u32,
+    one_past_last_point: u32,
 }
 
 #[derive(Default)]
@@ -13,6 +13,10 @@ pub struct ChangeList {
     x: Vec<f32>,
     y: Vec<f32>,
     changes: Vec<Replace>,
+    // Scratch space for rebuilding the path, recycled from the points it had
+    // before the last changes were applied.
+    merged_x: Vec<f32>,
+    merged_y: Vec<f32>,
 }
 
 impl ChangeList {
@@ -22,7 +26,7 @@ impl ChangeList {
         self.changes.clear();
     }
 
-    pub fn replace<F>(&mut self, segment: u16, position: u16, mut f: F)
+    pub fn replace<F>(&mut self, segment: u32, position: u32, mut f: F)
     where
         F: FnMut(&mut Vec<f32>, &mut Vec<f32>),
     {
@@ -37,10 +41,12 @@ impl ChangeList {
         });
     }
 
+    /// Splices every replacement into `path`, in a single pass over its
+    /// points. The changes are kept, sorted by position, until the list is
+    /// cleared, so that anything referring to the points of the path can be
+    /// updated to match.
     pub fn apply(&mut self, path: &mut Path) {
-        println!("Applying chages {:?}", &self.changes);
-
-        self.changes.sort_by(|a, b| a.position.cmp(&b.position));
+        self.changes.sort_by_key(|change| change.position);
 
         #[cfg(debug_assertions)]
         for i in 1..self.changes.len() {
@@ -52,13 +58,20 @@ impl ChangeList {
             assert!(prev.segment_id <= this.segment_id);
         }
 
-        let mut offset: usize = 0;
+        // The path is rebuilt in a single pass, copying the points between
+        // changes and the replacement for each changed curve in turn.
+        self.merged_x.clear();
+        self.merged_y.clear();
+        self.merged_x.reserve(path.x.len() + self.x.len());
+        self.merged_y.reserve(path.y.len() + self.y.len());
 
+        let mut next: usize = 0;
         for change in &self.changes {
             // grab the new points
-            let position = change.position as usize + offset;
-            let new_x = &self.x[change.first_point.into()..change.one_past_last_point.into()];
-            let new_y = &self.y[change.first_point.into()..change.one_past_last_point.into()];
+            let position = change.position as usize;
+            let new_points = change.first_point as usize..change.one_past_last_point as usize;
+            let new_x = &self.x[new_points.clone()];
+            let new_y = &self.y[new_points];
 
             // make sure that the first and last point are the same as on the old curve
             debug_assert_eq!(path.x[position], new_x[0]);
@@ -66,20 +79,34 @@ impl ChangeList {
             debug_assert_eq!(path.x[position + 3], new_x[new_x.len() - 1]);
             debug_assert_eq!(path.y[position + 3], new_y[new_y.len() - 1]);
 
-            // splice the new points in, replacing the old curve
-            path.x
-                .splice(position..=position + 3, new_x.iter().cloned());
-            path.y
-                .splice(position..=position + 3, new_y.iter().cloned());
+            // copy the points up to the old curve, then the new points in its
+            // place. The last point is shared with the next curve, which may
+            // also be replaced, so it is copied along with whatever follows.
+            self.merged_x.extend_from_slice(&path.x[next..position]);
+            self.merged_y.extend_from_slice(&path.y[next..position]);
+            self.merged_x.extend_from_slice(&new_x[..new_x.len() - 1]);
+            self.merged_y.extend_from_slice(&new_y[..new_y.len() - 1]);
+            next = position + 3;
 
             path.segments[change.segment_id as usize].length +=
-                TryInto::<u16>::try_into(new_x.len() - 4).unwrap();
-            offset += new_x.len() - 4;
+                TryInto::<u32>::try_into(new_x.len() - 4).unwrap();
         }
+        self.merged_x.extend_from_slice(&path.x[next..]);
+        self.merged_y.extend_from_slice(&path.y[next..]);
 
-        self.changes.clear();
-        self.x.clear();
-        self.y.clear();
+        std::mem::swap(&mut path.x, &mut self.merged_x);
+        std::mem::swap(&mut path.y, &mut self.merged_y);
+    }
+
+    /// The position of every change in the original path, and the number of
+    /// points that replaced the curve there.
+    pub fn changes(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.changes.iter().map(|change| {
+            (
+                change.position,
+                change.one_past_last_point - change.first_point,
+            )
+        })
     }
 }
 
@@ -92,7 +119,10 @@ mod tests {
     #[test]
     fn replace() {
         let mut path = Path {
-            segments: vec![Segment { length: 6 }],
+            segments: vec![Segment {
+                length: 6,
+                closed: false,
+            }],
             x: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0],
             y: vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0],
         };
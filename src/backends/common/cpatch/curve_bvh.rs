@@ -1,368 +1,295 @@
-use std::cell::Cell;
-
-use crate::shapes::{
-    bezier::{Bezier, CubicSlice},
-    path::Path,
-    point::Point,
-    rect::Rect,
+use crate::{
+    shapes::{
+        bezier::{Bezier, CubicSlice},
+        path::Path,
+        rect::{BoundingBox, Rect},
+    },
+    utils::bounding_volume::Bvh,
 };
 
+use super::change_list::ChangeList;
+
 /// Used for graph flattening (intersection) and fill scoring (ray cast).
-pub struct CurveBvh<'a> {
-    pub nodes: &'a mut [Node],
-    pub curves: &'a [Curve],
-}
+pub type CurveBvh = Bvh<Curve>;
 
-impl<'a> CurveBvh<'a> {
-    pub fn storage() -> Builder {
-        Builder::default()
-    }
+/// Nodes with this many curves or fewer are not subdivided any further.
+const MAX_CURVES_PER_LEAF: u32 = 4;
 
-    pub fn curves_in<'p>(
-        &self,
-        leaf: Leaf,
-        path: &'p Path,
-    ) -> impl Iterator<Item = (CubicSlice<'p>, u16, u16)> + 'a
-    where
-        'p: 'a,
-    {
-        self.curves[leaf.first_curve as usize..(leaf.first_curve + leaf.num_curves) as usize]
-            .iter()
-            .map(|curve| {
-                (
-                    CubicSlice {
-                        x: path.x[curve.first_point as usize..curve.first_point as usize + 4]
-                            .try_into()
-                            .unwrap(),
-                        y: path.y[curve.first_point as usize..curve.first_point as usize + 4]
-                            .try_into()
-                            .unwrap(),
-                    },
-                    curve.segment_id,
-                    curve.first_point,
-                )
-            })
-    }
+/// A reference to a curve in the path that a [`CurveBvh`] was built from.
+#[derive(Clone, Copy, Debug)]
+pub struct Curve {
+    pub bounds: Rect,
+    pub segment_id: u32,
+    pub first_point: u32,
+}
 
-    pub fn find_intersecting_with(&self, node_idx: u32, buffer: &mut Vec<(u32, Leaf)>) {
-        buffer.clear();
-        self.intersect(node_idx, self.nodes[node_idx as usize].bbox, buffer, 0);
+impl Curve {
+    /// The control points of the curve in `path`.
+    pub fn slice<'p>(&self, path: &'p Path) -> CubicSlice<'p> {
+        get_slice(path, self.first_point)
     }
+}
 
-    pub fn intersect(
-        &self,
-        origin_idx: u32,
-        bounds: Rect,
-        buffer: &mut Vec<(u32, Leaf)>,
-        current_node_idx: u32,
-    ) {
-        if origin_idx != current_node_idx {
-            let node = &self.nodes[current_node_idx as usize];
-            match node.data {
-                Data::Empty => unreachable!(),
-                Data::Leaf(leaf) => {
-                    buffer.push((current_node_idx, leaf));
-                }
-                Data::Branch(branch) => {
-                    self.intersect(origin_idx, bounds, buffer, branch.left_then_right);
-                    self.intersect(origin_idx, bounds, buffer, branch.left_then_right + 1);
-                }
-            }
-        }
+impl BoundingBox for Curve {
+    fn bounding_box(&self) -> Rect {
+        self.bounds
     }
 }
 
-#[derive(Default)]
 pub struct Builder {
-    nodes: Vec<Node>,
-    curves: Vec<Curve>,
+    bvh: CurveBvh,
+    /// The curves that replaced the curves split by the last refit.
+    created: Vec<Curve>,
+    // Scratch space for refitting the bvh.
+    splits: Vec<(u32, u32, u32)>,
 }
 
-impl Builder {
-    pub fn build(&mut self, path: &Path) -> CurveBvh {
-        let num_curves = {
-            let mut count = 0;
-            for segment in &path.segments {
-                debug_assert_eq!((segment.length - 1) % 3, 0);
-                count += (segment.length - 1) / 3;
-            }
-            count
-        } as usize;
-
-        self.nodes.clear();
-        self.nodes.reserve(2 * num_curves);
-
-        self.curves.clear();
-        self.curves.reserve(num_curves);
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            bvh: Bvh::with_leaf_size(MAX_CURVES_PER_LEAF),
+            created: Vec::new(),
+            splits: Vec::new(),
+        }
+    }
+}
 
+impl Builder {
+    pub fn build(&mut self, path: &Path) -> &CurveBvh {
         let mut offset = 0;
-        for (segment, segment_id) in path.iter().zip(0..) {
-            let it = segment.zip((0..).step_by(3)).map(|(c, i)| Curve {
-                segment_id,
-                centroid: c.coarse_bounds().centroid(),
-                first_point: offset + i,
-            });
-
-            self.curves.extend(it);
+        let curves = path.iter().zip(0..).flat_map(|(segment, segment_id)| {
+            let first_point = offset;
             offset += path.segments[segment_id as usize].length;
-        }
 
-        debug_assert_eq!(self.curves.capacity(), self.curves.len());
-
-        let root = Node {
-            bbox: Self::compute_bounds(&self.curves, path),
-            last_touched: Cell::new(0),
-            data: Data::Leaf(Leaf {
-                first_curve: 0,
-                num_curves: num_curves as u16,
-            }),
-        };
-
-        self.nodes.push(root);
-        self.nodes.push(Node {
-            bbox: Rect::default(),
-            last_touched: Cell::new(0),
-            data: Data::Empty,
+            segment
+                .zip((first_point..).step_by(3))
+                .map(move |(c, first_point)| Curve {
+                    bounds: c.coarse_bounds(),
+                    segment_id,
+                    first_point,
+                })
         });
 
-        self.subdivide(0, path);
-
-        CurveBvh {
-            nodes: &mut self.nodes,
-            curves: &self.curves,
-        }
+        self.bvh.rebuild(curves);
+        &self.bvh
     }
 
-    fn get_slice(curve: Curve, path: &Path) -> CubicSlice {
-        let first_point: usize = curve.first_point.into();
-        CubicSlice::new(
-            path.x[first_point..first_point + 4].try_into().unwrap(),
-            path.y[first_point..first_point + 4].try_into().unwrap(),
-        )
+    /// The bvh as it was last built or refit.
+    pub fn bvh(&self) -> &CurveBvh {
+        &self.bvh
     }
 
-    fn compute_bounds(curves: &[Curve], path: &Path) -> Rect {
-        if curves.is_empty() {
-            Rect::default()
-        } else {
-            curves[1..].iter().fold(
-                Self::get_slice(curves[0], path).coarse_bounds(),
-                |bounds, curve| bounds | Self::get_slice(*curve, path).coarse_bounds(),
-            )
-        }
+    /// The pieces of every curve that was split by the last refit, in the
+    /// order they appear in the bvh.
+    pub fn created(&self) -> &[Curve] {
+        &self.created
     }
 
-    fn subdivide(&mut self, node_id: u32, path: &Path) {
-        // Need to get left_idx here because we borrow self.nodes mutably and
-        // keep it until we split the node.
-        let left_idx = self.nodes.len() as u32;
-
-        let (split_axis, split_pos, split_cost) = self.find_split_axis(node_id, path);
-        let node = &mut self.nodes[node_id as usize];
-        let leaf = node.leaf().unwrap();
-
-        if leaf.num_curves <= 1 {
-            return;
+    /// Updates the bvh after `changes` have been applied to `path`. The pieces
+    /// of every curve that was split take its place in the same leaf, and the
+    /// rest of the tree is left as it was.
+    pub fn refit(&mut self, path: &Path, changes: &ChangeList) {
+        // The position of each change, the number of curves replacing the
+        // curve there, and the number of points added by it and every change
+        // before it.
+        self.splits.clear();
+        let mut added = 0;
+        for (position, num_points) in changes.changes() {
+            added += num_points - 4;
+            self.splits.push((position, (num_points - 1) / 3, added));
         }
 
-        let mut i = leaf.first_curve as isize;
-        let mut j = i + leaf.num_curves as isize - 1;
-        match split_axis {
-            SplitAxis::X => {
-                while i <= j {
-                    if self.curves[i as usize].centroid.x < split_pos {
-                        i += 1;
-                    } else {
-                        self.curves.swap(i as usize, j as usize);
-                        j -= 1;
+        self.created.clear();
+        let created = &mut self.created;
+        let splits = &self.splits;
+        self.bvh.refit(|curve, out| {
+            let i = splits.partition_point(|(position, _, _)| *position < curve.first_point);
+            let shift = if i == 0 { 0 } else { splits[i - 1].2 };
+            let first_point = curve.first_point + shift;
+
+            match splits.get(i) {
+                Some((position, num_curves, _)) if *position == curve.first_point => {
+                    for j in 0..*num_curves {
+                        let first_point = first_point + 3 * j;
+                        let piece = Curve {
+                            bounds: get_slice(path, first_point).coarse_bounds(),
+                            segment_id: curve.segment_id,
+                            first_point,
+                        };
+                        out.push(piece);
+                        created.push(piece);
                     }
                 }
+                _ => out.push(Curve {
+                    first_point,
+                    ..*curve
+                }),
             }
-            SplitAxis::Y => {
-                while i <= j {
-                    if self.curves[i as usize].centroid.y < split_pos {
-                        i += 1;
-                    } else {
-                        self.curves.swap(i as usize, j as usize);
-                        j -= 1;
-                    }
-                }
-            }
-        }
-
-        let left_count = u16::try_from(i).unwrap() - leaf.first_curve;
-        let node_cost = leaf.num_curves as f32 * node.bbox.area();
-
-        println!(
-            "num curves: {}, bbox area: {}",
-            leaf.num_curves,
-            node.bbox.area()
-        );
-        println!("node cost: {}, split cost: {}", node_cost, split_cost);
-
-        if split_cost >= node_cost {
-            return;
-        }
-
-        node.data = Data::Branch(Branch {
-            left_then_right: left_idx,
         });
+    }
+}
 
-        self.nodes.push(Node {
-            bbox: Self::compute_bounds(&self.curves[leaf.first_curve as usize..i as usize], path),
-            last_touched: Cell::new(0),
-            data: Data::Leaf(Leaf {
-                first_curve: leaf.first_curve,
-                num_curves: left_count,
-            }),
-        });
+fn get_slice(path: &Path, first_point: u32) -> CubicSlice<'_> {
+    let first_point = first_point as usize;
+    CubicSlice::new(
+        path.x[first_point..first_point + 4].try_into().unwrap(),
+        path.y[first_point..first_point + 4].try_into().unwrap(),
+    )
+}
 
-        self.nodes.push(Node {
-            bbox: Self::compute_bounds(
-                &self.curves[i as usize..(leaf.first_curve + leaf.num_curves) as usize],
-                path,
-            ),
-            last_touched: Cell::new(0),
-            data: Data::Leaf(Leaf {
-                first_curve: i as u16,
-                num_curves: leaf.num_curves - left_count as u16,
-            }),
-        });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{path::Builder as PathBuilder, point::Point};
+    use std::cell::Cell;
+
+    /// Reads the paths in tiger.svg.
+    fn tiger_paths() -> Vec<Path> {
+        let data = include_str!("../../../../test_files/tiger.svg");
+        let document = roxmltree::Document::parse(data).unwrap();
+
+        document
+            .descendants()
+            .filter(|node| node.has_tag_name("path"))
+            .map(|node| Path::from_svg_path_data(node.attribute("d").unwrap()).unwrap())
+            .collect()
     }
 
-    fn find_split_axis(&self, node_id: u32, path: &Path) -> (SplitAxis, f32, f32) {
-        let node = &self.nodes[node_id as usize];
-
-        let mut best_axis = SplitAxis::X;
-        let mut best_pos = 0.0;
-        let mut best_cost = f32::MAX;
-
-        for i in 0..node.leaf().unwrap().num_curves {
-            let curve = &self.curves[(node.leaf().unwrap().first_curve + i) as usize];
-            let candidate_pos = curve.centroid.x;
-            let cost = self.evaluate_sah(node, SplitAxis::X, candidate_pos, path);
-            if cost < best_cost {
-                best_axis = SplitAxis::X;
-                best_pos = candidate_pos;
-                best_cost = cost;
+    #[test]
+    fn intersect_finds_overlaps() {
+        let mut builder = Builder::default();
+
+        for path in tiger_paths() {
+            let bvh = builder.build(&path);
+            let mut candidates = Vec::new();
+
+            for curve in path.iter().flatten() {
+                let bounds = curve.coarse_bounds();
+                candidates.clear();
+                bvh.query_rect_intersection(bounds, &mut candidates);
+
+                let expected = bvh
+                    .items()
+                    .iter()
+                    .filter(|other| other.slice(&path).coarse_bounds().intersects_with(&bounds))
+                    .count();
+                assert_eq!(candidates.len(), expected);
             }
         }
+    }
 
-        for i in 0..node.leaf().unwrap().num_curves {
-            let curve = &self.curves[(node.leaf().unwrap().first_curve + i) as usize];
-            let candidate_pos = curve.centroid.y;
-            let cost = self.evaluate_sah(node, SplitAxis::Y, candidate_pos, path);
-            if cost < best_cost {
-                best_axis = SplitAxis::Y;
-                best_pos = candidate_pos;
-                best_cost = cost;
+    #[test]
+    fn intersect_prunes_by_bounds() {
+        let mut builder = Builder::default();
+
+        // The number of bounds tested when querying the bounds of every curve,
+        // counting both nodes and curves, and the number of pairs there would
+        // be without pruning.
+        let tests = Cell::new(0);
+        let mut unpruned = 0;
+
+        for path in tiger_paths() {
+            let bvh = builder.build(&path);
+
+            for curve in path.iter().flatten() {
+                let bounds = curve.coarse_bounds();
+                bvh.query(
+                    |bbox: &Rect| {
+                        tests.set(tests.get() + 1);
+                        bbox.intersects_with(&bounds)
+                    },
+                    |_| {},
+                );
+                unpruned += bvh.items().len();
             }
         }
 
-        (best_axis, best_pos, best_cost)
+        assert!(tests.get() * 2 < unpruned);
     }
 
-    fn evaluate_sah(&self, node: &Node, axis: SplitAxis, position: f32, path: &Path) -> f32 {
-        let mut left = None;
-        let mut num_left = 0;
-
-        let mut right = None;
-        let mut num_right = 0;
-
-        for i in 0..node.leaf().unwrap().num_curves {
-            let curve = &self.curves[(node.leaf().unwrap().first_curve + i) as usize];
-            let slice = Self::get_slice(*curve, path);
-
-            let curve_pos = match axis {
-                SplitAxis::X => curve.centroid.x,
-                SplitAxis::Y => curve.centroid.y,
-            };
-
-            if curve_pos < position {
-                num_left += 1;
-                left = left.map(|b: Rect| b | slice.coarse_bounds());
-            } else {
-                num_right += 1;
-                right = right.map(|b: Rect| b | slice.coarse_bounds());
+    /// A grid of squares, which is large enough to need a deep tree.
+    fn grid() -> Path {
+        let mut path = PathBuilder::default();
+        for i in 0..40 {
+            for j in 0..40 {
+                let (x, y) = (i as f32 * 3.0, j as f32 * 3.0);
+                path.move_to(Point::new(x, y));
+                path.line_to(Point::new(x + 2.0, y)).unwrap();
+                path.line_to(Point::new(x + 2.0, y + 2.0)).unwrap();
+                path.line_to(Point::new(x, y + 2.0)).unwrap();
+                path.close().unwrap();
             }
         }
-
-        // Return without testing for <= 0 as described in article...
-        left.map_or(0.0, |lb| num_left as f32 * lb.area())
-            + right.map_or(0.0, |rb| num_right as f32 * rb.area())
+        path.build().unwrap()
     }
-}
-
-#[derive(Clone, Copy)]
-pub struct Curve {
-    pub centroid: Point,
-    pub segment_id: u16,
-    pub first_point: u16,
-}
-
-#[derive(Clone, Copy, Debug)]
-pub struct Leaf {
-    /// The index of the first curve in this leaf.
-    pub first_curve: u16,
-    /// The number of curves in this leaf.
-    pub num_curves: u16,
-}
-
-#[derive(Clone, Copy, Default, Debug)]
-pub struct Branch {
-    /// The index of the left child. The right child is at index + 1. This is a
-    /// u32 instead of a u16 because we have 2N nodes for N curves.
-    pub left_then_right: u32,
-}
 
-pub enum SplitAxis {
-    X,
-    Y,
-}
-
-#[derive(Debug)]
-pub struct Node {
-    pub bbox: Rect,
-    pub last_touched: Cell<u32>,
-    pub data: Data,
-}
-
-#[derive(Debug)]
-pub enum Data {
-    Empty,
-    Leaf(Leaf),
-    Branch(Branch),
-}
+    /// Checks that every curve in the path is in the bvh exactly once, with
+    /// the right bounds, and can be found by querying them.
+    fn check(bvh: &CurveBvh, path: &Path) {
+        let mut seen = Vec::new();
+        let mut found = Vec::new();
+        for curve in bvh.items() {
+            let bounds = curve.slice(path).coarse_bounds();
+            assert_eq!(curve.bounds, bounds);
+            seen.push(curve.first_point);
+
+            found.clear();
+            bvh.query_rect_intersection(bounds, &mut found);
+            assert!(found.iter().any(|c| c.first_point == curve.first_point));
+        }
+        seen.sort_unstable();
 
-impl Node {
-    pub fn leaf(&self) -> Option<Leaf> {
-        match self.data {
-            Data::Leaf(leaf) => Some(leaf),
-            _ => None,
+        let mut expected = Vec::new();
+        let mut offset = 0;
+        for segment in &path.segments {
+            expected.extend((offset..offset + segment.length - 1).step_by(3));
+            offset += segment.length;
         }
+        assert_eq!(seen, expected);
     }
 
-    pub fn branch(&self) -> Option<Branch> {
-        match self.data {
-            Data::Branch(branch) => Some(branch),
-            _ => None,
-        }
+    #[test]
+    fn build() {
+        let path = grid();
+        let mut builder = Builder::default();
+        check(builder.build(&path), &path);
     }
 
-    pub fn is_leaf(&self) -> bool {
-        match self.data {
-            Data::Leaf { .. } => true,
-            Data::Empty => false,
-            Data::Branch { .. } => false,
+    #[test]
+    fn refit() {
+        let mut path = grid();
+        let mut builder = Builder::default();
+        builder.build(&path);
+
+        // Split the first side of every square in two places, and the third
+        // side in one.
+        let mut changes = ChangeList::default();
+        let mut offset = 0;
+        for (segment, segment_id) in path.iter().zip(0..) {
+            for (curve, i) in segment.zip(0..) {
+                let splits: &[f32] = match i {
+                    0 => &[0.3, 0.6],
+                    2 => &[0.5],
+                    _ => &[],
+                };
+
+                if !splits.is_empty() {
+                    changes.replace(segment_id, offset + 3 * i, |x, y| {
+                        curve.splitn(splits.iter(), x, y);
+                    });
+                }
+            }
+            offset += path.segments[segment_id as usize].length;
         }
-    }
 
-    pub fn is_branch(&self) -> bool {
-        !self.is_leaf()
-    }
-}
+        let old_num_curves = path.iter().flatten().count();
+        changes.apply(&mut path);
+        assert!(path.iter().flatten().count() > old_num_curves);
 
-#[test]
-fn f() {
-    println!("{}", std::mem::size_of::<Node>());
+        builder.refit(&path, &changes);
+        check(builder.bvh(), &path);
+
+        // Three pieces of the first side and two of the third, per square.
+        assert_eq!(builder.created().len(), 40 * 40 * 5);
+    }
 }
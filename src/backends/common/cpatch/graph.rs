@@ -0,0 +1,238 @@
+//! The planar graph formed by a flattened path, in which curves only meet at
+//! their end points.
+//!
+//! Every curve is represented by a pair of half-edges running in opposite
+//! directions, each of which borders the face to its left. Walking from each
+//! half-edge to the next one around the same face traces out the boundary of
+//! every face in the graph.
+
+use std::collections::HashMap;
+
+use crate::{
+    math::vector2::Vec2,
+    shapes::{
+        bezier::{Bezier, Cubic},
+        point::Point,
+    },
+};
+
+use super::FillScore;
+
+/// The parameter along a curve used to measure the direction in which it
+/// leaves a vertex. Using a point slightly along the curve rather than its
+/// tangent separates curves that leave a vertex in the same direction.
+const DIRECTION_PROBE: f32 = 1.0 / 64.0;
+
+pub struct HalfEdge {
+    /// The curve, oriented in the direction of the half-edge.
+    pub curve: Cubic,
+    /// The winding number of the face to the left of the half-edge.
+    pub winding: i32,
+    destination: usize,
+}
+
+pub struct Graph {
+    vertices: Vec<Point>,
+    /// The outgoing half-edges of each vertex, in counter-clockwise order.
+    outgoing: Vec<Vec<usize>>,
+    /// Half-edges come in pairs: the twin of half-edge `i` is `i ^ 1`.
+    edges: Vec<HalfEdge>,
+}
+
+impl Graph {
+    /// Builds a graph from curves and their fill scores. End points closer
+    /// than `tolerance` to each other are merged into a single vertex, and the
+    /// curves are adjusted to meet there exactly. Curves that begin and end at
+    /// the same vertex without enclosing any area are discarded.
+    pub fn new(curves: impl Iterator<Item = (Cubic, FillScore)>, tolerance: f32) -> Self {
+        let mut graph = Self {
+            vertices: Vec::new(),
+            outgoing: Vec::new(),
+            edges: Vec::new(),
+        };
+
+        let mut cells = HashMap::new();
+        for (mut curve, score) in curves {
+            let origin = graph.vertex(&mut cells, curve.p0(), tolerance);
+            let destination = graph.vertex(&mut cells, curve.p3(), tolerance);
+
+            let bounds = curve.coarse_bounds();
+            if origin == destination && bounds.width().max(bounds.height()) <= tolerance {
+                continue;
+            }
+
+            (curve.x[0], curve.y[0]) = (graph.vertices[origin].x, graph.vertices[origin].y);
+            (curve.x[3], curve.y[3]) =
+                (graph.vertices[destination].x, graph.vertices[destination].y);
+
+            graph.outgoing[origin].push(graph.edges.len());
+            graph.edges.push(HalfEdge {
+                curve,
+                winding: score.left,
+                destination,
+            });
+
+            graph.outgoing[destination].push(graph.edges.len());
+            graph.edges.push(HalfEdge {
+                curve: Cubic::new(curve.p3(), curve.p2(), curve.p1(), curve.p0()),
+                winding: score.right,
+                destination: origin,
+            });
+        }
+
+        let edges = &graph.edges;
+        for outgoing in &mut graph.outgoing {
+            outgoing.sort_by(|a, b| direction(&edges[*a]).total_cmp(&direction(&edges[*b])));
+        }
+
+        graph
+    }
+
+    pub fn edges(&self) -> &[HalfEdge] {
+        &self.edges
+    }
+
+    /// Finds the boundary of every face in the graph, calling `f` with the
+    /// half-edges of each in order. Every half-edge is part of exactly one
+    /// boundary.
+    pub fn for_each_face<F>(&self, mut f: F)
+    where
+        F: FnMut(&[usize]),
+    {
+        let mut visited = vec![false; self.edges.len()];
+        let mut face = Vec::new();
+
+        for start in 0..self.edges.len() {
+            face.clear();
+
+            let mut edge = start;
+            while !visited[edge] {
+                visited[edge] = true;
+                face.push(edge);
+                edge = self.next(edge);
+            }
+
+            if !face.is_empty() {
+                f(&face);
+            }
+        }
+    }
+
+    /// The half-edge that follows `edge` around the face to its left. This is
+    /// the first outgoing half-edge clockwise from the twin of `edge`, which
+    /// makes the tightest possible left turn.
+    fn next(&self, edge: usize) -> usize {
+        let twin = edge ^ 1;
+        let outgoing = &self.outgoing[self.edges[edge].destination];
+        let position = outgoing.iter().position(|e| *e == twin).unwrap();
+        outgoing[(position + outgoing.len() - 1) % outgoing.len()]
+    }
+
+    /// Finds the first vertex within `tolerance` of `point`, or adds one.
+    ///
+    /// Vertices are sorted into `cells`, a grid of squares `tolerance` wide, so
+    /// that only the vertices in the point's cell and the cells around it need
+    /// to be compared with it.
+    fn vertex(
+        &mut self,
+        cells: &mut HashMap<(i64, i64), Vec<usize>>,
+        point: Point,
+        tolerance: f32,
+    ) -> usize {
+        let cell = |v: f32| (v / tolerance).floor() as i64;
+        let (x, y) = (cell(point.x), cell(point.y));
+
+        let existing = (x.saturating_sub(1)..=x.saturating_add(1))
+            .flat_map(|x| (y.saturating_sub(1)..=y.saturating_add(1)).map(move |y| (x, y)))
+            .filter_map(|cell| cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(|v| (self.vertices[*v] - point).length() <= tolerance)
+            .min();
+
+        existing.unwrap_or_else(|| {
+            self.vertices.push(point);
+            self.outgoing.push(Vec::new());
+            cells
+                .entry((x, y))
+                .or_default()
+                .push(self.vertices.len() - 1);
+            self.vertices.len() - 1
+        })
+    }
+}
+
+/// The angle at which a half-edge leaves its origin.
+fn direction(edge: &HalfEdge) -> f32 {
+    let d: Vec2 = edge.curve.at(DIRECTION_PROBE) - edge.curve.p0();
+    d.y().atan2(d.x())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(x0: f32, y0: f32, x1: f32, y1: f32) -> Cubic {
+        let (p0, p1) = (Point::new(x0, y0), Point::new(x1, y1));
+        Cubic::new(p0, p0 + (p1 - p0) * 0.25, p0 + (p1 - p0) * 0.75, p1)
+    }
+
+    #[test]
+    fn faces() {
+        // A square divided in two by a vertical line, with the left half
+        // filled. The end points don't quite match up.
+        let score = |left, right| FillScore { left, right };
+        let curves = [
+            (line(0.0, 0.0, 1.0, 0.0), score(1, 0)),
+            (line(1.0, 0.0, 2.0, 0.0), score(0, 0)),
+            (line(2.0, 0.0, 2.0, 2.0), score(0, 0)),
+            (line(2.0, 2.0, 1.0, 2.0), score(0, 0)),
+            (line(1.0, 2.0, 0.0, 2.0), score(1, 0)),
+            (line(0.0, 2.0, 0.0, 0.000_01), score(1, 0)),
+            (line(1.0, 0.0, 1.0, 2.0), score(1, 0)),
+        ];
+
+        let graph = Graph::new(curves.iter().copied(), 1e-3);
+        assert_eq!(graph.vertices.len(), 6);
+        assert_eq!(graph.edges().len(), 14);
+
+        let mut faces = Vec::new();
+        graph.for_each_face(|face| {
+            let windings = face
+                .iter()
+                .map(|e| graph.edges()[*e].winding)
+                .collect::<Vec<_>>();
+            faces.push((face.len(), windings));
+        });
+
+        // Both halves of the square, and the space around it.
+        faces.sort();
+        assert_eq!(
+            faces,
+            [
+                (4, vec![0, 0, 0, 0]),
+                (4, vec![1, 1, 1, 1]),
+                (6, vec![0, 0, 0, 0, 0, 0])
+            ]
+        );
+    }
+
+    #[test]
+    fn vertex_merging() {
+        let mut graph = Graph::new(std::iter::empty(), 1.0);
+        let mut cells = HashMap::new();
+        let mut vertex = |x, y| graph.vertex(&mut cells, Point::new(x, y), 1.0);
+
+        // Points in neighbouring cells are merged if they are close enough.
+        assert_eq!(vertex(0.95, 0.0), 0);
+        assert_eq!(vertex(1.05, 0.0), 0);
+        assert_eq!(vertex(1.05, -0.5), 0);
+        assert_eq!(vertex(2.1, 0.0), 1);
+        assert_eq!(vertex(2.1, 0.1), 1);
+
+        // The first vertex within tolerance wins, not the closest one.
+        assert_eq!(vertex(1.6, 0.0), 0);
+        assert_eq!(vertex(-1.0, -1.0), 2);
+        assert_eq!(graph.vertices.len(), 3);
+    }
+}
@@ -7,68 +7,71 @@
 //! 1. Graph flattening: Every curve within the path is tested for intersection
 //!    with every other curve. Intersecting curves are replaced with subdivided
 //!    counterparts, where the curves meet at the intersection.
-//!     1a. Normalization: The curves are normalized to the unit square.
+//!    1a. Normalization: The curves are normalized to the unit square.
 //! 2. Fill scoring: A ray is shot out from each curve at its midpoint towards
-//!    the edge of the path. The fill score is then computed according to the
-//!    desired fill rule "Non-Zero" or "Even-Odd". This is accelerated by the
-//!    use of a bounding volume hierarchy.
-//! 3. Cycle extraction: TBD
-//! 4. Patch cuttiing: TBD
-//! 5. Self-intersection cutting: TBD
-//! 6. Extension correction: TBD
+//!    the edge of the path. The crossings along the ray give the winding
+//!    numbers of the regions to either side of the curve, which are then
+//!    interpreted according to the desired [`FillRule`], "Non-Zero" or
+//!    "Even-Odd", by way of [`FillRule::is_filled`]. This is accelerated by
+//!    the use of a bounding volume hierarchy.
+//! 3. Cycle extraction: The flattened path forms a planar graph, whose faces
+//!    are found by walking around each one, always taking the tightest left
+//!    turn at every vertex. Each face becomes a closed, non-self-intersecting
+//!    cycle annotated with the winding number that its curves' fill scores
+//!    give it.
+//! 4. Patch cutting: The curves bounding the filled cycles are split into
+//!    pieces that are monotonic in x and y, and the regions they bound are cut
+//!    along vertical lines through the ends of every piece. This leaves
+//!    [`CPatch`]es: regions bounded above and below by a single curve each.
+//! 5. Self-intersection cutting: Curves that loop across themselves are split
+//!    where they do so. Because the graph in step 3 relies on curves only
+//!    meeting at their end points, this happens at the start of step 1.
+//! 6. Extension correction: The curves of each patch are trimmed to its
+//!    horizontal range, extending them where needed so that neighbouring
+//!    patches meet exactly.
+//!
+//! [`Builder`] runs every step in turn.
+
+use std::collections::HashSet;
 
 use crate::{
-    math::cmp::{max, min},
-    shapes::{bezier::Bezier, path::Path, rect::Rect},
+    math::{cmp::max, vector2::Vec2},
+    paint::FillRule,
+    shapes::{
+        bezier::{Bezier, CubicSlice},
+        path::{Path, Segment},
+        point::Point,
+    },
+    utils::arrayvec::ArrayVec,
 };
 
 mod change_list;
 mod curve_bvh;
+mod graph;
+mod patch;
+mod polynomial;
+mod winding;
 
-pub use change_list::ChangeList;
-pub use curve_bvh::CurveBvh;
-
-pub fn normalize(path: &mut Path) -> Rect {
-    let rect = {
-        let mut min_x = path.x[0];
-        let mut min_y = path.y[0];
-        let mut max_x = path.x[0];
-        let mut max_y = path.y[0];
-
-        for x in &path.x[1..] {
-            min_x = min!(min_x, *x);
-            max_x = max!(max_x, *x);
-        }
-
-        for y in &path.y[1..] {
-            min_y = min!(min_y, *y);
-            max_y = max!(max_y, *y);
-        }
-
-        Rect::new(min_x, max_x, min_y, max_y)
-    };
+/// Intersections closer than this to the end of a curve are considered to be
+/// at the end of the curve.
+const SPLIT_EPSILON: f32 = 1e-4;
 
-    let offset_x = rect.left;
-    let offset_y = rect.top;
-    let div_x = rect.width();
-    let div_y = rect.height();
+/// End points closer together than this, relative to the size of the path,
+/// are considered to be the same vertex when extracting cycles.
+const VERTEX_EPSILON: f32 = 1e-4;
 
-    for x in &mut path.x {
-        *x = (*x - offset_x) / div_x;
-    }
-
-    for y in &mut path.y {
-        *y = (*y - offset_y) / div_y;
-    }
-
-    rect
-}
+pub use change_list::ChangeList;
+pub use curve_bvh::CurveBvh;
+pub use patch::{CPatch, CPatchList};
 
+/// Splits every curve in the path wherever it intersects itself or another
+/// curve, so that curves only ever meet at their end points. Returns a bvh of
+/// the flattened path.
 pub fn flatten<'a>(
     path: &mut Path,
     change_buffer: &mut change_list::ChangeList,
     bvh_builder: &'a mut curve_bvh::Builder,
-) -> curve_bvh::CurveBvh<'a> {
+) -> &'a CurveBvh {
     // CONCERN (straivers): Normalization may accidentally produce denormal numbers for
     // very large paths, which have an outsized impact on performance. A
     // possible solution would be to use f64 instead, though it would involve
@@ -78,100 +81,776 @@ pub fn flatten<'a>(
     // normalize?
 
     change_buffer.clear();
-    let mut intersection_buffer = Vec::new();
 
-    let mut candidates = 0;
-    let mut intersections = 0;
+    let mut offset = 0;
+    for ((segment, info), segment_id) in path.iter().zip(&path.segments).zip(0..) {
+        for (curve, first_point) in segment.zip((offset..).step_by(3)) {
+            if let Some((t0, t1)) = self_intersection(curve) {
+                change_buffer.replace(segment_id, first_point, |x, y| {
+                    curve.splitn([t0, t1].iter(), x, y);
+                });
+            }
+        }
+        offset += info.length;
+    }
+    change_buffer.apply(path);
+    change_buffer.clear();
 
-    let mut iteration = 0;
-    loop {
-        let mut found_intersection = false;
-        let bvh = bvh_builder.build(path);
+    let bounds = path.bounds();
+    let tolerance = max!(bounds.width(), bounds.height()) * VERTEX_EPSILON;
+
+    bvh_builder.build(path);
+
+    // The curves to test against every other curve in the current pass. At
+    // first, that is all of them. After that, only the pieces of the curves
+    // that were split can intersect anything, since every other pair of
+    // curves has already been tested, or involved a curve that was split.
+    // They are tested in the order they appear in the bvh, which keeps
+    // neighbouring curves together, and their first points are kept sorted
+    // to look them up.
+    let mut dirty = bvh_builder.bvh().items().to_vec();
+    let mut dirty_points = Vec::new();
+
+    // The first points of the curves that have been split in the current
+    // pass. A curve can only be replaced once per pass, so any further
+    // intersections are left for the next.
+    let mut touched = HashSet::new();
+
+    while !dirty.is_empty() {
+        let bvh = bvh_builder.bvh();
+        let mut candidates = Vec::new();
+
+        dirty_points.clear();
+        dirty_points.extend(dirty.iter().map(|curve| curve.first_point));
+        dirty_points.sort_unstable();
+        let is_dirty = |first_point: u32| dirty_points.binary_search(&first_point).is_ok();
+        touched.clear();
+
+        for curve_ref in &dirty {
+            let curve = curve_ref.slice(path);
+            let first_point = curve_ref.first_point;
+
+            candidates.clear();
+            bvh.query_rect_intersection(curve_ref.bounds, &mut candidates);
+
+            for candidate in &candidates {
+                // Pairs of dirty curves are found twice, once from either
+                // side. Only look at them the first time.
+                let c_first_point = candidate.first_point;
+                if c_first_point == first_point
+                    || (c_first_point < first_point && is_dirty(c_first_point))
+                    || touched.contains(&first_point)
+                    || touched.contains(&c_first_point)
+                {
+                    continue;
+                }
+
+                let c_curve = candidate.slice(path);
+                let (node_splits, candidate_splits) =
+                    interior_intersections(curve, c_curve, tolerance);
+
+                if !node_splits.is_empty() {
+                    change_buffer.replace(curve_ref.segment_id, first_point, |x, y| {
+                        curve.splitn(node_splits.iter(), x, y);
+                    });
+                    touched.insert(first_point);
+                }
 
-        for (node, node_idx) in bvh.nodes.iter().zip(0..).filter(|node| node.0.is_leaf()) {
-            if iteration > 0 && node.last_touched.get() == iteration {
-                continue;
+                if !candidate_splits.is_empty() {
+                    change_buffer.replace(candidate.segment_id, c_first_point, |x, y| {
+                        c_curve.splitn(candidate_splits.iter(), x, y);
+                    });
+                    touched.insert(c_first_point);
+                }
             }
+        }
 
-            let leaf = node.leaf().unwrap();
-            bvh.find_intersecting_with(node_idx, &mut intersection_buffer);
+        if touched.is_empty() {
+            break;
+        }
 
-            candidates += intersection_buffer.len();
+        change_buffer.apply(path);
+        bvh_builder.refit(path, change_buffer);
+        change_buffer.clear();
 
-            // println!("node");
+        dirty.clear();
+        dirty.extend_from_slice(bvh_builder.created());
+    }
 
-            for (curve, segment_id, first_point) in bvh.curves_in(leaf, path) {
-                // println!("curve");
+    bvh_builder.bvh()
+}
 
-                for (c_node_id, (c_curve, c_segment_id, c_first_point)) in intersection_buffer
-                    .iter()
-                    .cloned()
-                    .filter(|(c_node_id, _)| {
-                        bvh.nodes[*c_node_id as usize].last_touched.get() != iteration
-                    })
-                    .flat_map(|(i, leaf)| std::iter::repeat(i).zip(bvh.curves_in(leaf, path)))
-                {
-                    println!("\t curve {}, candidate {}", node_idx, c_node_id);
-                    let (node_splits, candidate_splits) = curve.find_intersections(&c_curve);
-
-                    if !node_splits.is_empty() {
-                        intersections += node_splits.len();
-
-                        change_buffer.replace(segment_id, first_point, |x, y| {
-                            curve.splitn(node_splits.iter(), x, y);
-                        });
-
-                        change_buffer.replace(c_segment_id, c_first_point, |x, y| {
-                            c_curve.splitn(candidate_splits.iter(), x, y);
-                        });
-
-                        node.last_touched.replace(iteration);
-                        bvh.nodes[c_node_id as usize]
-                            .last_touched
-                            .replace(iteration);
-
-                        // Skip the rest of the intersection buffer since we've
-                        // already made changes to the bvh that won't be reflected
-                        // until it's been rebuilt.
-                        found_intersection = true;
-                        break;
-                    }
-                }
+/// Finds the pair of parameters at which a curve that loops across itself
+/// passes through the same point, if it does so within the curve.
+fn self_intersection(curve: CubicSlice) -> Option<(f32, f32)> {
+    let p = |i: usize| Vec2::new(curve.x[i], curve.y[i]);
+    let cross = |u: Vec2, v: Vec2| u.x() * v.y() - u.y() * v.x();
+
+    // The curve in power form: a t^3 + b t^2 + c t + d.
+    let a = (p(3) - p(0)) + (p(1) - p(2)) * 3.0;
+    let b = (p(0) + p(2)) * 3.0 - p(1) * 6.0;
+    let c = (p(1) - p(0)) * 3.0;
+
+    // For s != t, B(s) = B(t) reduces to a (s^2 + st + t^2) + b (s + t) + c = 0.
+    // Taking the cross product with `a` gives the sum of the parameters, and
+    // the remaining component along `a` gives their product.
+    let ab = cross(a, b);
+    if ab.abs() <= f32::EPSILON * a.length2().max(b.length2()) {
+        return None;
+    }
+
+    let sum = -cross(a, c) / ab;
+    let product = sum * sum + a.dot(b * sum + c) / a.length2();
+    let discriminant = sum * sum - 4.0 * product;
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    let root = discriminant.sqrt();
+    let (t0, t1) = ((sum - root) * 0.5, (sum + root) * 0.5);
+    (t0 > SPLIT_EPSILON && t1 < 1.0 - SPLIT_EPSILON && t1 - t0 > SPLIT_EPSILON).then_some((t0, t1))
+}
+
+/// Finds the parameters at which `a` and `b` must be split so that they only
+/// meet at their end points. Intersections at the ends of a curve don't
+/// require it to be split, and are discarded. End points within `tolerance`
+/// of each other are considered to be shared.
+fn interior_intersections(
+    a: CubicSlice,
+    b: CubicSlice,
+    tolerance: f32,
+) -> (ArrayVec<f32, 9>, ArrayVec<f32, 9>) {
+    fn interior(splits: &[f32]) -> ArrayVec<f32, 9> {
+        let mut interior = splits
+            .iter()
+            .copied()
+            .filter(|t| *t > SPLIT_EPSILON && *t < 1.0 - SPLIT_EPSILON)
+            .collect::<ArrayVec<f32, 9>>();
+
+        interior.sort_by(f32::total_cmp);
+        let mut unique = ArrayVec::new();
+        for t in interior.iter() {
+            if unique
+                .last()
+                .is_none_or(|last: &f32| t - last > SPLIT_EPSILON)
+            {
+                unique.push(*t);
             }
         }
+        unique
+    }
+
+    // Curves that follow one another in a segment share an end point, and
+    // usually meet there at a tangent, which the intersection search converges
+    // on very poorly. The same goes for the pieces of curves that were split
+    // where they cross, whose end points only agree to within rounding error.
+    // Either way, they are assumed not to cross anywhere else.
+    let adjacent = |p: Point, q: Point| (p - q).length() <= tolerance;
+    if adjacent(a.p3(), b.p0())
+        || adjacent(a.p0(), b.p3())
+        || adjacent(a.p0(), b.p0())
+        || adjacent(a.p3(), b.p3())
+        || !a.coarse_bounds().intersects_with(&b.coarse_bounds())
+    {
+        return (ArrayVec::new(), ArrayVec::new());
+    }
 
-        iteration += 1;
+    let (a_splits, b_splits) = a.find_intersections(&b);
+    (interior(&a_splits), interior(&b_splits))
+}
 
-        if found_intersection {
-            change_buffer.apply(path);
-        } else {
-            break;
+/// The winding numbers of the regions immediately to either side of a curve.
+/// Left and right are relative to the direction of the curve at its midpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FillScore {
+    pub left: i32,
+    pub right: i32,
+}
+
+/// Computes the [`FillScore`] of every curve in the path, in the order that
+/// they appear in it. `bvh` must have been built from `path`.
+///
+/// Winding numbers are independent of the fill rule, so the scores can be used
+/// with either.
+pub fn compute_fill_scores(path: &Path, bvh: &CurveBvh, scores: &mut Vec<FillScore>) {
+    scores.clear();
+
+    let mut candidates = Vec::new();
+    let mut offset = 0;
+    for (segment, info) in path.iter().zip(&path.segments) {
+        for (curve, first_point) in segment.zip((offset..).step_by(3)) {
+            scores.push(fill_score(path, bvh, curve, first_point, &mut candidates));
         }
+        offset += info.length;
     }
+}
+
+fn fill_score<'a>(
+    path: &Path,
+    bvh: &'a CurveBvh,
+    curve: CubicSlice,
+    first_point: u32,
+    candidates: &mut Vec<&'a curve_bvh::Curve>,
+) -> FillScore {
+    let origin = curve.at(0.5);
+    let tangent = curve.derivative(0.5);
+
+    // Cast the ray along whichever axis is closest to perpendicular to the
+    // curve, so that it crosses the curve cleanly.
+    let (axis, direction) = if tangent.y().abs() >= tangent.x().abs() {
+        (winding::Axis::X, Vec2::new(1.0, 0.0))
+    } else {
+        (winding::Axis::Y, Vec2::new(0.0, 1.0))
+    };
 
-    println!("{} candidates, {} intersections", candidates, intersections);
+    candidates.clear();
+    bvh.query_ray_intersection(origin, direction, candidates);
 
-    // TODO (straivers): This is a hack to get around fixed-lifetime issues.
-    // Ideally, we would return the bvh if !found_intersection, but that's not
-    // possible because it would require the bvh to have a lifetime of 'a,
-    // preventing us from recreating the bvh every iteration (multiple mutable
-    // borrows). A possible option would be to somehow 'rebuild' the bvh without
-    // actually doing any of the work, but I am not sure how to do that safely.
-    //
-    // A token produced by `CurveBvh` that can be used to recover it would be
-    // ideal, 'cept that it is entirely unsafe. Now, if we only use it here, it
-    // should be fine, but I remain uncomfortable with the idea if it can be
-    // avoided.
-    bvh_builder.build(path)
+    let mut ahead = 0;
+    for other in candidates.iter() {
+        let skip = (other.first_point == first_point).then_some(0.5);
+        ahead += winding::ray_crossings(other.slice(path), origin, axis, skip);
+    }
+
+    // A point just behind the origin has to cross the curve itself to escape
+    // along the ray.
+    let own_crossing = direction.x() * tangent.y() - direction.y() * tangent.x();
+    let behind = if own_crossing > 0.0 {
+        ahead + 1
+    } else if own_crossing < 0.0 {
+        ahead - 1
+    } else {
+        ahead
+    };
+
+    let left_normal = Vec2::new(-tangent.y(), tangent.x());
+    if left_normal.dot(direction) > 0.0 {
+        FillScore {
+            left: ahead,
+            right: behind,
+        }
+    } else {
+        FillScore {
+            left: behind,
+            right: ahead,
+        }
+    }
+}
+
+/// Computes the winding number of `point` with respect to `path`, from which
+/// [`FillRule::is_filled`] determines if the path covers it. Points on the
+/// path itself may go either way.
+#[must_use]
+pub fn winding_number(path: &Path, point: Point) -> i32 {
+    path.iter()
+        .flatten()
+        .map(|curve| winding::ray_crossings(curve, point, winding::Axis::X, None))
+        .sum()
 }
 
-pub fn compute_fill_scores(path: &Path, bvh: curve_bvh::CurveBvh, score_buffer: &mut Vec<u16>) {
-    todo!()
+/// A closed, non-self-intersecting loop of curves bounding a single region of
+/// a path.
+pub struct Cycle {
+    /// A single closed segment. The region lies to the left of every curve in
+    /// it, so a cycle that runs clockwise is a hole in the region around it.
+    pub path: Path,
+    /// The winding number of the region.
+    pub winding: i32,
+}
+
+impl Cycle {
+    /// Determines if the region bounded by the cycle is filled under
+    /// `fill_rule`.
+    #[must_use]
+    pub fn is_filled(&self, fill_rule: FillRule) -> bool {
+        fill_rule.is_filled(self.winding)
+    }
+}
+
+/// Breaks a flattened path up into the cycles bounding each of its regions.
+/// `scores` must hold the [`FillScore`] of every curve in the path, as
+/// computed by [`compute_fill_scores`].
+///
+/// Regions with a winding number of zero are never filled, and are skipped.
+#[must_use]
+pub fn extract_cycles(path: &Path, scores: &[FillScore]) -> Vec<Cycle> {
+    let bounds = path.bounds();
+    let tolerance = max!(bounds.width(), bounds.height()) * VERTEX_EPSILON;
+
+    let curves = path.iter().flatten().map(|curve| curve.as_owned());
+    let graph = graph::Graph::new(curves.zip(scores.iter().copied()), tolerance);
+    let edges = graph.edges();
+
+    let mut cycles = Vec::new();
+    graph.for_each_face(|face| {
+        // Every curve around a face borders it on the same side, so they
+        // should all agree on its winding number.
+        let winding = edges[face[0]].winding;
+        if winding == 0 {
+            return;
+        }
+
+        let mut x = Vec::with_capacity(1 + 3 * face.len());
+        let mut y = Vec::with_capacity(1 + 3 * face.len());
+        x.push(edges[face[0]].curve.x[0]);
+        y.push(edges[face[0]].curve.y[0]);
+        for edge in face {
+            x.extend_from_slice(&edges[*edge].curve.x[1..]);
+            y.extend_from_slice(&edges[*edge].curve.y[1..]);
+        }
+
+        cycles.push(Cycle {
+            path: Path {
+                segments: vec![Segment {
+                    length: x.len() as u32,
+                    closed: true,
+                }],
+                x,
+                y,
+            },
+            winding,
+        });
+    });
+
+    cycles
 }
 
-pub fn extract_cycles(path: Path) -> Vec<Path> {
-    todo!()
+/// Cuts the regions bounded by the cycles that are filled under `fill_rule`
+/// into [`CPatch`]es, appending them to `patches`. This combines patch cutting
+/// and extension correction; self-intersections have already been cut by
+/// [`flatten`].
+pub fn finalize(cycles: &[Cycle], fill_rule: FillRule, patches: &mut CPatchList) {
+    patch::cut(cycles, fill_rule, patches);
 }
 
-/// Combines patch cutting, self-intersection cutting, and extension correction.
-pub fn finalize(path: &mut Path) {}
+/// Reusable storage for converting paths into [`CPatch`]es.
+#[derive(Default)]
+pub struct Builder {
+    changes: ChangeList,
+    bvh: curve_bvh::Builder,
+    scores: Vec<FillScore>,
+}
+
+impl Builder {
+    /// Converts the area filled by `path` under `fill_rule` into patches,
+    /// appending them to `patches`.
+    pub fn build(&mut self, path: &Path, fill_rule: FillRule, patches: &mut CPatchList) {
+        if path.x.is_empty() {
+            return;
+        }
+
+        let mut path = path.clone();
+        let bvh = flatten(&mut path, &mut self.changes, &mut self.bvh);
+        compute_fill_scores(&path, bvh, &mut self.scores);
+
+        let cycles = extract_cycles(&path, &self.scores);
+        finalize(&cycles, fill_rule, patches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::cmp::ApproxEq,
+        shapes::{bezier::Cubic, path::Builder as PathBuilder},
+    };
+
+    fn add_polygon(builder: &mut PathBuilder, points: &[(f32, f32)]) {
+        builder.move_to(Point::new(points[0].0, points[0].1));
+        for (x, y) in &points[1..] {
+            builder.line_to(Point::new(*x, *y)).unwrap();
+        }
+        builder.close().unwrap();
+    }
+
+    /// Adds a circle approximated by four cubics, running counter-clockwise
+    /// in a y-down coordinate system unless `reversed` is set.
+    fn add_circle(builder: &mut PathBuilder, (cx, cy): (f32, f32), r: f32, reversed: bool) {
+        let k = 0.552_284_8 * r;
+        let quadrants = [
+            [(cx + r, cy - k), (cx + k, cy - r), (cx, cy - r)],
+            [(cx - k, cy - r), (cx - r, cy - k), (cx - r, cy)],
+            [(cx - r, cy + k), (cx - k, cy + r), (cx, cy + r)],
+            [(cx + k, cy + r), (cx + r, cy + k), (cx + r, cy)],
+        ];
+
+        // Mirroring the circle vertically reverses it.
+        let point = |(x, y): (f32, f32)| {
+            if reversed {
+                Point::new(x, 2.0 * cy - y)
+            } else {
+                Point::new(x, y)
+            }
+        };
+
+        builder.move_to(Point::new(cx + r, cy));
+        for [p1, p2, p3] in quadrants {
+            builder.add_cubic(point(p1), point(p2), point(p3)).unwrap();
+        }
+        builder.close().unwrap();
+    }
+
+    fn fill_scores(path: &Path) -> Vec<FillScore> {
+        let mut storage = curve_bvh::Builder::default();
+        let bvh = storage.build(path);
+        let mut scores = Vec::new();
+        compute_fill_scores(path, bvh, &mut scores);
+        scores
+    }
+
+    fn extract(mut path: Path) -> Vec<Cycle> {
+        let mut changes = ChangeList::default();
+        let mut storage = curve_bvh::Builder::default();
+        let bvh = flatten(&mut path, &mut changes, &mut storage);
+
+        let mut scores = Vec::new();
+        compute_fill_scores(&path, bvh, &mut scores);
+        extract_cycles(&path, &scores)
+    }
+
+    /// The signed area enclosed by the path, found by sampling its curves.
+    fn signed_area(path: &Path) -> f32 {
+        const SAMPLES: usize = 64;
+
+        let mut area = 0.0;
+        for curve in path.iter().flatten() {
+            let mut previous = curve.p0();
+            for i in 1..=SAMPLES {
+                let point = curve.at(i as f32 / SAMPLES as f32);
+                area += previous.x * point.y - point.x * previous.y;
+                previous = point;
+            }
+        }
+        area * 0.5
+    }
+
+    /// Reads the path in p1.svg.
+    fn p1() -> Path {
+        let data = include_str!("../../../../test_files/p1.svg");
+        let document = roxmltree::Document::parse(data).unwrap();
+        let node = document
+            .descendants()
+            .find(|node| node.has_tag_name("path"))
+            .unwrap();
+
+        Path::from_svg_path_data(node.attribute("d").unwrap()).unwrap()
+    }
+
+    fn is_closed(path: &Path) -> bool {
+        let last = path.x.len() - 1;
+        path.segments.len() == 1
+            && Point::new(path.x[0], path.y[0]).approx_eq(&Point::new(path.x[last], path.y[last]))
+    }
+
+    #[test]
+    fn nested_squares() {
+        let outer = [(0.0, 0.0), (30.0, 0.0), (30.0, 30.0), (0.0, 30.0)];
+        let inner = [(10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (10.0, 20.0)];
+        let reversed = [(10.0, 10.0), (10.0, 20.0), (20.0, 20.0), (20.0, 10.0)];
+
+        let mut builder = PathBuilder::default();
+        add_polygon(&mut builder, &outer);
+        add_polygon(&mut builder, &inner);
+        let path = builder.build().unwrap();
+
+        // The interiors of both squares lie to the left of their curves.
+        let scores = fill_scores(&path);
+        assert_eq!(scores.len(), 8);
+        let winding = scores[0].left;
+        assert_eq!(winding.abs(), 1);
+        for score in &scores[..4] {
+            assert_eq!(
+                *score,
+                FillScore {
+                    left: winding,
+                    right: 0
+                }
+            );
+        }
+        for score in &scores[4..] {
+            assert_eq!(
+                *score,
+                FillScore {
+                    left: 2 * winding,
+                    right: winding
+                }
+            );
+        }
+
+        let mut builder = PathBuilder::default();
+        add_polygon(&mut builder, &outer);
+        add_polygon(&mut builder, &reversed);
+        let path = builder.build().unwrap();
+
+        // Reversing the inner square cuts a hole in the outer one.
+        let scores = fill_scores(&path);
+        for score in &scores[4..] {
+            assert_eq!(
+                *score,
+                FillScore {
+                    left: winding,
+                    right: 0
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn curved_outline() {
+        // A circle of radius 20 approximated by four cubics, around a square.
+        // The rays cast from the midpoints of the square's edges pass exactly
+        // through the points where the circle's curves meet.
+        const K: f32 = 0.552_284_8 * 20.0;
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(70.0, 50.0));
+        for [p1, p2, p3] in [
+            [(70.0, 50.0 + K), (50.0 + K, 70.0), (50.0, 70.0)],
+            [(50.0 - K, 70.0), (30.0, 50.0 + K), (30.0, 50.0)],
+            [(30.0, 50.0 - K), (50.0 - K, 30.0), (50.0, 30.0)],
+            [(50.0 + K, 30.0), (70.0, 50.0 - K), (70.0, 50.0)],
+        ] {
+            let [p1, p2, p3] = [p1, p2, p3].map(|(x, y)| Point::new(x, y));
+            builder.add_cubic(p1, p2, p3).unwrap();
+        }
+        builder.close().unwrap();
+        add_polygon(
+            &mut builder,
+            &[(45.0, 45.0), (55.0, 45.0), (55.0, 55.0), (45.0, 55.0)],
+        );
+        let path = builder.build().unwrap();
+
+        let scores = fill_scores(&path);
+        assert_eq!(scores.len(), 8);
+
+        let winding = scores[0].left;
+        assert_eq!(winding.abs(), 1);
+        for score in &scores[..4] {
+            assert_eq!(
+                *score,
+                FillScore {
+                    left: winding,
+                    right: 0
+                }
+            );
+        }
+        for score in &scores[4..] {
+            assert_eq!(
+                *score,
+                FillScore {
+                    left: 2 * winding,
+                    right: winding
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_circles() {
+        let mut builder = PathBuilder::default();
+        add_circle(&mut builder, (50.0, 50.0), 20.0, false);
+        add_circle(&mut builder, (70.0, 50.0), 20.0, false);
+        let path = builder.build().unwrap();
+
+        // Two lunes, and the lens where they overlap.
+        let mut cycles = extract(path);
+        assert_eq!(cycles.len(), 3);
+        assert!(cycles.iter().all(|cycle| is_closed(&cycle.path)));
+        assert!(cycles
+            .iter()
+            .all(|cycle| cycle.is_filled(FillRule::NonZero)));
+
+        cycles.sort_by_key(|cycle| cycle.winding.abs());
+        let winding = cycles[0].winding;
+        assert_eq!(winding.abs(), 1);
+        assert_eq!(cycles[1].winding, winding);
+        assert_eq!(cycles[2].winding, 2 * winding);
+        assert!(!cycles[2].is_filled(FillRule::EvenOdd));
+
+        // Every region lies to the left of its cycle, so the cycles all run in
+        // the same direction.
+        let orientation = signed_area(&cycles[0].path).signum();
+        let expected = [765.3, 765.3, 491.35];
+        for (cycle, expected) in cycles.iter().zip(expected) {
+            let area = signed_area(&cycle.path) * orientation;
+            assert!((area - expected).abs() < expected * 0.01, "{area}");
+        }
+
+        // With one circle reversed, the lens has a winding number of zero and
+        // is discarded.
+        let mut builder = PathBuilder::default();
+        add_circle(&mut builder, (50.0, 50.0), 20.0, false);
+        add_circle(&mut builder, (70.0, 50.0), 20.0, true);
+        let path = builder.build().unwrap();
+
+        let cycles = extract(path);
+        assert_eq!(cycles.len(), 2);
+        assert_eq!(cycles[0].winding, -cycles[1].winding);
+        for cycle in &cycles {
+            let area = signed_area(&cycle.path) * orientation;
+            assert!((area - 765.3).abs() < 765.3 * 0.01, "{area}");
+        }
+    }
+
+    #[test]
+    fn large_path() {
+        // A polygon with more points than fit in a u16, crossed by a square
+        // near the end of the path.
+        const SIDES: usize = 30_000;
+        let points = (0..SIDES)
+            .map(|i| {
+                let angle = std::f32::consts::TAU * (i as f32 + 0.5) / SIDES as f32;
+                (100.0 * angle.cos(), 100.0 * angle.sin())
+            })
+            .collect::<Vec<_>>();
+
+        let mut builder = PathBuilder::default();
+        add_polygon(&mut builder, &points);
+        add_polygon(
+            &mut builder,
+            &[(90.0, -10.0), (110.0, -10.0), (110.0, 10.0), (90.0, 10.0)],
+        );
+        let mut path = builder.build().unwrap();
+        assert!(path.x.len() > u16::MAX as usize);
+
+        let mut changes = ChangeList::default();
+        let mut storage = curve_bvh::Builder::default();
+        let bvh = flatten(&mut path, &mut changes, &mut storage);
+
+        // Each crossing splits a side of the polygon and a side of the square.
+        assert_eq!(bvh.items().len(), SIDES + 4 + 4);
+        assert_eq!(path.iter().flatten().count(), SIDES + 4 + 4);
+
+        let mut scores = Vec::new();
+        compute_fill_scores(&path, bvh, &mut scores);
+        // Every curve separates regions whose winding numbers differ by one.
+        assert!(scores
+            .iter()
+            .all(|score| (score.left - score.right).abs() == 1));
+    }
+
+    #[test]
+    fn self_intersecting_path() {
+        // p1.svg holds a single path that crosses over itself.
+        let path = p1();
+        let expected = signed_area(&path);
+
+        let cycles = extract(path);
+        assert!(cycles.len() > 1);
+        assert!(cycles.iter().all(|cycle| is_closed(&cycle.path)));
+
+        // Each region is counted by the original path as many times as its
+        // winding number.
+        let area = cycles
+            .iter()
+            .map(|cycle| signed_area(&cycle.path) * cycle.winding as f32)
+            .sum::<f32>();
+        assert!(
+            (area - expected).abs() < expected.abs() * 0.005,
+            "{area} {expected}"
+        );
+    }
+
+    /// Checks that the patches built from `path` cover exactly the points that
+    /// it fills under `fill_rule`, by sampling both on a grid.
+    fn assert_covers(path: &Path, fill_rule: FillRule) {
+        const GRID: usize = 128;
+
+        let mut patches = CPatchList::default();
+        super::Builder::default().build(path, fill_rule, &mut patches);
+
+        let bounds = path.bounds();
+        let mut mismatches = 0;
+        let mut filled = 0;
+        for i in 0..GRID {
+            for j in 0..GRID {
+                // Offset the samples slightly so that they don't line up with
+                // the axis-aligned parts of the paths.
+                let point = Point::new(
+                    bounds.left + bounds.width() * (i as f32 + 0.513) / GRID as f32,
+                    bounds.top + bounds.height() * (j as f32 + 0.487) / GRID as f32,
+                );
+
+                let winding = winding_number(path, point);
+
+                let coverage = patches
+                    .iter()
+                    .filter(|patch| {
+                        let bounds = patch.bounds();
+                        let (top, bottom) = patch.span_at(point.x);
+                        bounds.left <= point.x
+                            && point.x < bounds.right
+                            && top <= point.y
+                            && point.y < bottom
+                    })
+                    .count();
+
+                filled += usize::from(fill_rule.is_filled(winding));
+                if coverage != usize::from(fill_rule.is_filled(winding)) {
+                    mismatches += 1;
+                }
+            }
+        }
+
+        // Samples right on the boundary may go either way.
+        assert!(filled > 0);
+        assert!(mismatches * 500 < filled, "{mismatches} of {filled}");
+    }
+
+    #[test]
+    fn self_intersections() {
+        let looped = Cubic {
+            x: [0.0, 15.0, -5.0, 10.0],
+            y: [0.0, 10.0, 10.0, 0.0],
+        };
+        let (t0, t1) = self_intersection(looped.as_slice()).unwrap();
+        assert!(t0 < t1);
+        assert!(looped.at(t0).approx_eq_within(&looped.at(t1), 1e-3));
+
+        let arc = Cubic {
+            x: [20.0, 20.0, 11.045_696, 0.0],
+            y: [0.0, 11.045_696, 20.0, 20.0],
+        };
+        assert!(self_intersection(arc.as_slice()).is_none());
+
+        let line = Cubic {
+            x: [0.0, 2.5, 7.5, 10.0],
+            y: [0.0, 2.5, 7.5, 10.0],
+        };
+        assert!(self_intersection(line.as_slice()).is_none());
+
+        // Closing the loop leaves its inside and outside wound in opposite
+        // directions.
+        let mut builder = PathBuilder::default();
+        builder.move_to(looped.p0());
+        builder
+            .add_cubic(looped.p1(), looped.p2(), looped.p3())
+            .unwrap();
+        builder.close().unwrap();
+        let path = builder.build().unwrap();
+
+        let mut cycles = extract(path.clone());
+        assert_eq!(cycles.len(), 2);
+        cycles.sort_by_key(|cycle| cycle.winding);
+        assert_eq!(cycles[0].winding, -cycles[1].winding);
+
+        assert_covers(&path, FillRule::NonZero);
+    }
+
+    #[test]
+    fn patches() {
+        let mut builder = PathBuilder::default();
+        add_circle(&mut builder, (50.0, 50.0), 20.0, false);
+        add_circle(&mut builder, (70.0, 50.0), 20.0, false);
+        add_polygon(
+            &mut builder,
+            &[(55.0, 45.0), (65.0, 45.0), (65.0, 55.0), (55.0, 55.0)],
+        );
+        let circles = builder.build().unwrap();
+
+        assert_covers(&circles, FillRule::NonZero);
+        assert_covers(&circles, FillRule::EvenOdd);
+        assert_covers(&p1(), FillRule::NonZero);
+        assert_covers(&p1(), FillRule::EvenOdd);
+    }
+}
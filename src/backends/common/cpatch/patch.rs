@@ -0,0 +1,425 @@
+//! Cutting the filled regions of a path into CPatches.
+//!
+//! Every curve bounding a filled region is split into pieces that are
+//! monotonic in both x and y. The regions are then cut along vertical lines
+//! through the end points of every piece, so that between each pair of lines
+//! the pieces are stacked on top of one another without crossing, alternately
+//! entering and leaving the filled area. Each consecutive pair of pieces bounds
+//! a patch, and patches that continue across a line with the same pair of
+//! curves are merged back together.
+
+use std::slice;
+
+use crate::{
+    math::cmp::{max, min},
+    paint::FillRule,
+    shapes::{
+        bezier::{Bezier, Cubic},
+        rect::Rect,
+    },
+};
+
+use super::{
+    polynomial::{evaluate, extrema, solve_monotonic},
+    Cycle, VERTEX_EPSILON,
+};
+
+/// A region bounded above and below by a pair of curves that span the same
+/// horizontal range, and by vertical lines at either end of it. The curves may
+/// meet at either end, but never cross.
+///
+/// Both curves run from left to right, and are monotonic in x and y. This
+/// means that the extent of a curve over any horizontal range can be found
+/// from its values at either end of the range.
+#[derive(Clone, Copy, Debug)]
+pub struct CPatch {
+    /// The curve bounding the patch from above, towards negative y.
+    pub top: Cubic,
+    /// The curve bounding the patch from below, towards positive y.
+    pub bottom: Cubic,
+}
+
+impl CPatch {
+    #[must_use]
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            self.top.x[0],
+            self.top.x[3],
+            min!(self.top.y[0], self.top.y[3]),
+            max!(self.bottom.y[0], self.bottom.y[3]),
+        )
+    }
+
+    /// The top and bottom of the patch at `x`. Values of `x` outside of the
+    /// patch are clamped to its ends.
+    #[must_use]
+    pub fn span_at(&self, x: f32) -> (f32, f32) {
+        (y_at(&self.top, x), y_at(&self.bottom, x))
+    }
+}
+
+/// The patches making up the filled area of a path, in no particular order.
+/// Patches never overlap, though they may share edges.
+#[derive(Default)]
+pub struct CPatchList {
+    patches: Vec<CPatch>,
+}
+
+impl CPatchList {
+    pub fn clear(&mut self) {
+        self.patches.clear();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.patches.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.patches.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, CPatch> {
+        self.patches.iter()
+    }
+
+    /// The smallest rectangle containing every patch.
+    #[must_use]
+    pub fn bounds(&self) -> Rect {
+        let mut patches = self.patches.iter();
+        patches.next().map_or_else(Rect::default, |first| {
+            patches.fold(first.bounds(), |bounds, patch| bounds | patch.bounds())
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a CPatchList {
+    type Item = &'a CPatch;
+    type IntoIter = slice::Iter<'a, CPatch>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A run of consecutive slabs over which the same pair of pieces bounds a
+/// patch.
+struct Run {
+    top: usize,
+    bottom: usize,
+    first_slab: usize,
+    last_slab: usize,
+}
+
+/// Cuts the regions bounded by the cycles that are filled under `fill_rule`
+/// into patches, appending them to `patches`.
+pub fn cut(cycles: &[Cycle], fill_rule: FillRule, patches: &mut CPatchList) {
+    let pieces = monotonic_pieces(cycles, fill_rule);
+    if pieces.is_empty() {
+        return;
+    }
+
+    let bounds = pieces
+        .iter()
+        .fold(pieces[0].coarse_bounds(), |b, p| b | p.coarse_bounds());
+    let tolerance = max!(bounds.width(), bounds.height()) * VERTEX_EPSILON;
+
+    // The positions of the vertical lines. End points closer together than
+    // the tolerance share a line.
+    let mut lines = pieces
+        .iter()
+        .flat_map(|piece| [piece.x[0], piece.x[3]])
+        .collect::<Vec<_>>();
+    lines.sort_by(f32::total_cmp);
+    lines.dedup_by(|x, previous| *x - *previous <= tolerance);
+
+    let line_at = |x: f32| {
+        let i = lines.partition_point(|line| *line < x);
+        if i > 0 && (i == lines.len() || x - lines[i - 1] < lines[i] - x) {
+            i - 1
+        } else {
+            i
+        }
+    };
+
+    // The range of slabs spanned by each piece, sorted by the first of them.
+    // Pieces that don't span any are too narrow to matter.
+    let mut spans = pieces
+        .iter()
+        .enumerate()
+        .map(|(i, piece)| (line_at(piece.x[0]), line_at(piece.x[3]), i))
+        .filter(|(first, end, _)| first < end)
+        .collect::<Vec<_>>();
+    spans.sort_unstable();
+
+    let mut runs = Vec::<Run>::new();
+    let mut previous_runs = Vec::new();
+    let mut current_runs = Vec::new();
+    let mut active = Vec::new();
+    let mut next_span = 0;
+
+    for slab in 0..lines.len() - 1 {
+        active.retain(|(end, _)| *end > slab);
+        while next_span < spans.len() && spans[next_span].0 == slab {
+            let (_, end, piece) = spans[next_span];
+            active.push((end, piece));
+            next_span += 1;
+        }
+
+        // Pieces never cross, so their order in the middle of the slab holds
+        // across all of it.
+        let middle = (lines[slab] + lines[slab + 1]) * 0.5;
+        active.sort_by(|(_, a), (_, b)| {
+            y_at(&pieces[*a], middle).total_cmp(&y_at(&pieces[*b], middle))
+        });
+
+        for pair in active.chunks_exact(2) {
+            let (top, bottom) = (pair[0].1, pair[1].1);
+
+            let continued = previous_runs
+                .iter()
+                .copied()
+                .find(|i: &usize| runs[*i].top == top && runs[*i].bottom == bottom);
+
+            if let Some(i) = continued {
+                runs[i].last_slab = slab;
+                current_runs.push(i);
+            } else {
+                current_runs.push(runs.len());
+                runs.push(Run {
+                    top,
+                    bottom,
+                    first_slab: slab,
+                    last_slab: slab,
+                });
+            }
+        }
+
+        std::mem::swap(&mut previous_runs, &mut current_runs);
+        current_runs.clear();
+    }
+
+    for run in &runs {
+        let (left, right) = (lines[run.first_slab], lines[run.last_slab + 1]);
+        patches.patches.push(CPatch {
+            top: trim(&pieces[run.top], left, right),
+            bottom: trim(&pieces[run.bottom], left, right),
+        });
+    }
+}
+
+/// Collects the curves bounding the filled regions, split into pieces that are
+/// monotonic in x and y and oriented from left to right. Curves separating two
+/// filled regions appear in the cycles of both, and are discarded.
+fn monotonic_pieces(cycles: &[Cycle], fill_rule: FillRule) -> Vec<Cubic> {
+    let mut curves = cycles
+        .iter()
+        .filter(|cycle| cycle.is_filled(fill_rule))
+        .flat_map(|cycle| cycle.path.iter().flatten())
+        .map(|curve| {
+            let curve = curve.as_owned();
+            if (curve.x[3], curve.y[3]) < (curve.x[0], curve.y[0]) {
+                reverse(&curve)
+            } else {
+                curve
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Shared curves are exact copies of each other, since they were produced
+    // from the same edge of the graph.
+    let key = |curve: &Cubic| (curve.x.map(f32::to_bits), curve.y.map(f32::to_bits));
+    curves.sort_by_key(key);
+
+    let mut unique = Vec::with_capacity(curves.len());
+    let mut i = 0;
+    while i < curves.len() {
+        let count = curves[i..]
+            .iter()
+            .take_while(|curve| key(curve) == key(&curves[i]))
+            .count();
+        if count % 2 == 1 {
+            unique.push(curves[i]);
+        }
+        i += count;
+    }
+
+    let mut pieces = Vec::with_capacity(unique.len());
+    let (mut x, mut y) = (Vec::new(), Vec::new());
+    for curve in &unique {
+        let mut splits = extrema(&curve.x)
+            .iter()
+            .chain(extrema(&curve.y).iter())
+            .copied()
+            .collect::<Vec<_>>();
+        splits.sort_by(f32::total_cmp);
+        splits.dedup_by(|t, previous| *t - *previous <= f32::EPSILON);
+
+        if splits.is_empty() {
+            pieces.push(*curve);
+            continue;
+        }
+
+        x.clear();
+        y.clear();
+        curve.splitn(splits.iter(), &mut x, &mut y);
+        for i in (0..x.len() - 1).step_by(3) {
+            let piece = Cubic {
+                x: x[i..i + 4].try_into().unwrap(),
+                y: y[i..i + 4].try_into().unwrap(),
+            };
+            pieces.push(if piece.x[3] < piece.x[0] {
+                reverse(&piece)
+            } else {
+                piece
+            });
+        }
+    }
+
+    pieces
+}
+
+/// Cuts the part of `piece` between `left` and `right` out of it.
+///
+/// This is also where extension correction happens. The paper uses this step
+/// to stop the implicit form of a curve from affecting pixels beyond its end
+/// points. Patches here are tested by evaluating their curves directly, which
+/// doesn't have that problem, but the cut points still have to be corrected:
+/// they are only found approximately, and lines may sit slightly past the end
+/// of a piece that was snapped to them. The ends of the cut are moved onto the
+/// lines exactly, extending the piece if needed, so that neighbouring patches
+/// meet without gaps or overlaps.
+fn trim(piece: &Cubic, left: f32, right: f32) -> Cubic {
+    let t0 = solve_monotonic(&piece.x, left, 0.0, 1.0);
+    let t1 = solve_monotonic(&piece.x, right, 0.0, 1.0);
+
+    let mut trimmed = if t0 <= 0.0 && t1 >= 1.0 {
+        *piece
+    } else {
+        piece.split2(t0, t1).1
+    };
+
+    trimmed.x[0] = left;
+    trimmed.x[3] = right;
+    trimmed.x[1] = trimmed.x[1].clamp(left, right);
+    trimmed.x[2] = trimmed.x[2].clamp(left, right);
+    trimmed
+}
+
+/// Evaluates a curve that is monotonic in x at `x`.
+fn y_at(curve: &Cubic, x: f32) -> f32 {
+    let t = if x <= curve.x[0] {
+        0.0
+    } else if x >= curve.x[3] {
+        1.0
+    } else {
+        solve_monotonic(&curve.x, x, 0.0, 1.0)
+    };
+    evaluate(&curve.y, t)
+}
+
+fn reverse(curve: &Cubic) -> Cubic {
+    Cubic::new(curve.p3(), curve.p2(), curve.p1(), curve.p0())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::path::{Path, Segment};
+
+    fn cycle(points: &[(f32, f32)], winding: i32) -> Cycle {
+        let mut x = vec![points[0].0];
+        let mut y = vec![points[0].1];
+        for (p0, p1) in points.iter().zip(points.iter().cycle().skip(1)) {
+            for t in [0.25, 0.75, 1.0] {
+                x.push(p0.0 + (p1.0 - p0.0) * t);
+                y.push(p0.1 + (p1.1 - p0.1) * t);
+            }
+        }
+
+        Cycle {
+            path: Path {
+                segments: vec![Segment {
+                    length: x.len() as u32,
+                    closed: true,
+                }],
+                x,
+                y,
+            },
+            winding,
+        }
+    }
+
+    /// Integrates the height of the patches over their widths.
+    fn area(patches: &CPatchList) -> f32 {
+        const SAMPLES: usize = 256;
+
+        let mut area = 0.0;
+        for patch in patches {
+            let bounds = patch.bounds();
+            let dx = bounds.width() / SAMPLES as f32;
+            for i in 0..SAMPLES {
+                let (top, bottom) = patch.span_at(bounds.left + (i as f32 + 0.5) * dx);
+                assert!(top <= bottom);
+                area += (bottom - top) * dx;
+            }
+        }
+        area
+    }
+
+    #[test]
+    fn polygons() {
+        // A square is a single patch.
+        let square = cycle(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], 1);
+        let mut patches = CPatchList::default();
+        cut(&[square], FillRule::NonZero, &mut patches);
+        assert_eq!(patches.len(), 1);
+        assert!((area(&patches) - 100.0).abs() < 1e-3);
+
+        let patch = patches.iter().next().unwrap();
+        assert_eq!(patch.span_at(5.0), (0.0, 10.0));
+
+        // A triangle is cut in two at its apex, and so is a square with a notch
+        // cut into its bottom.
+        patches.clear();
+        let triangle = cycle(&[(0.0, 10.0), (10.0, 10.0), (5.0, 0.0)], 1);
+        cut(&[triangle], FillRule::NonZero, &mut patches);
+        assert_eq!(patches.len(), 2);
+        assert!((area(&patches) - 50.0).abs() < 1e-2);
+
+        patches.clear();
+        let notched = cycle(
+            &[
+                (0.0, 0.0),
+                (10.0, 0.0),
+                (10.0, 10.0),
+                (5.0, 5.0),
+                (0.0, 10.0),
+            ],
+            1,
+        );
+        cut(&[notched], FillRule::NonZero, &mut patches);
+        assert_eq!(patches.len(), 2);
+        assert!((area(&patches) - 75.0).abs() < 1e-2);
+        assert_eq!(patches.bounds(), Rect::new(0.0, 10.0, 0.0, 10.0));
+    }
+
+    #[test]
+    fn shared_edges() {
+        // Two squares stacked on top of each other merge into a single patch
+        // when both are filled.
+        let upper = || cycle(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)], 1);
+        let lower = || cycle(&[(0.0, 10.0), (10.0, 10.0), (10.0, 20.0), (0.0, 20.0)], 2);
+
+        let mut patches = CPatchList::default();
+        cut(&[upper(), lower()], FillRule::NonZero, &mut patches);
+        assert_eq!(patches.len(), 1);
+        assert!((area(&patches) - 200.0).abs() < 1e-2);
+
+        patches.clear();
+        cut(&[upper(), lower()], FillRule::EvenOdd, &mut patches);
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches.bounds(), Rect::new(0.0, 10.0, 0.0, 10.0));
+    }
+}
@@ -0,0 +1,69 @@
+//! Helpers for treating one coordinate of a cubic bezier curve as a
+//! polynomial in `t`.
+
+use crate::utils::arrayvec::ArrayVec;
+
+/// The maximum number of bisection steps taken to solve a monotonic curve.
+/// This is enough to exhaust the precision of an f32 on the unit interval.
+const MAX_BISECTION_STEPS: u32 = 32;
+
+pub fn evaluate(p: &[f32; 4], t: f32) -> f32 {
+    let mt = 1.0 - t;
+    mt * mt * mt * p[0] + 3.0 * mt * mt * t * p[1] + 3.0 * mt * t * t * p[2] + t * t * t * p[3]
+}
+
+/// Finds the parameters in (0, 1) where the derivative of the curve is zero,
+/// in ascending order.
+pub fn extrema(p: &[f32; 4]) -> ArrayVec<f32, 2> {
+    let d0 = p[1] - p[0];
+    let d1 = p[2] - p[1];
+    let d2 = p[3] - p[2];
+
+    // The derivative divided by 3, as a quadratic in t.
+    let a = d0 - 2.0 * d1 + d2;
+    let b = 2.0 * (d1 - d0);
+    let c = d0;
+
+    let mut roots = ArrayVec::<f32, 2>::new();
+    if a.abs() <= f32::EPSILON {
+        if b.abs() > f32::EPSILON {
+            roots.push(-c / b);
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant >= 0.0 {
+            let sqrt = discriminant.sqrt();
+            let (r0, r1) = ((-b - sqrt) / (2.0 * a), (-b + sqrt) / (2.0 * a));
+            roots.push(r0.min(r1));
+            if r0 != r1 {
+                roots.push(r0.max(r1));
+            }
+        }
+    }
+
+    roots
+        .iter()
+        .filter(|t| **t > 0.0 && **t < 1.0)
+        .copied()
+        .collect()
+}
+
+/// Finds `t` in `[t0, t1]` such that `evaluate(p, t) == value`, where the
+/// curve is known to be monotonic over the interval and to pass through
+/// `value`.
+pub fn solve_monotonic(p: &[f32; 4], value: f32, mut t0: f32, mut t1: f32) -> f32 {
+    let increasing = evaluate(p, t0) <= evaluate(p, t1);
+    for _ in 0..MAX_BISECTION_STEPS {
+        let mid = (t0 + t1) * 0.5;
+        if mid <= t0 || mid >= t1 {
+            break;
+        }
+
+        if (evaluate(p, mid) < value) == increasing {
+            t0 = mid;
+        } else {
+            t1 = mid;
+        }
+    }
+    (t0 + t1) * 0.5
+}
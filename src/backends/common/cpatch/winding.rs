@@ -0,0 +1,139 @@
+//! Counting the crossings between curves and axis-aligned rays, from which
+//! winding numbers are computed.
+
+use crate::{
+    shapes::{bezier::CubicSlice, point::Point},
+    utils::arrayvec::ArrayVec,
+};
+
+use super::polynomial::{evaluate, extrema, solve_monotonic};
+
+/// One of the two coordinate axes, along which rays are cast.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// Computes the contribution of `curve` to the winding number of `origin`, by
+/// counting the times it crosses the ray cast from `origin` in the positive
+/// direction of `axis`. A crossing counts as +1 if the curve passes the ray
+/// from its right to its left (relative to the direction of the ray), and -1
+/// otherwise.
+///
+/// If `skip` is set, crossings in the part of the curve around that parameter
+/// are not counted. This is used to cast rays from points on the curve itself.
+pub fn ray_crossings(curve: CubicSlice, origin: Point, axis: Axis, skip: Option<f32>) -> i32 {
+    // The curve is treated as a pair of scalar functions: one along the ray,
+    // and one across it. Crossing the ray means that the function across it
+    // passes through the origin.
+    let (along, across, origin_along, origin_across, orientation) = match axis {
+        Axis::X => (curve.x, curve.y, origin.x, origin.y, 1),
+        // Swapping the axes mirrors the curve, flipping the sense of every
+        // crossing.
+        Axis::Y => (curve.y, curve.x, origin.y, origin.x, -1),
+    };
+
+    // Split the curve where it turns back on itself across the ray so that
+    // every piece crosses the ray at most once.
+    let mut splits = ArrayVec::<f32, 4>::new();
+    splits.push(0.0);
+    for t in &extrema(across) {
+        splits.push(*t);
+    }
+    splits.push(1.0);
+
+    let mut winding = 0;
+    for piece in splits.windows(2) {
+        let (t0, t1) = (piece[0], piece[1]);
+        if skip.is_some_and(|t| t0 <= t && t <= t1) {
+            continue;
+        }
+
+        // Each piece is half-open, so that a crossing exactly at the point
+        // where two pieces meet is counted exactly once.
+        let (a0, a1) = (evaluate(across, t0), evaluate(across, t1));
+        let direction = if a0 <= origin_across && origin_across < a1 {
+            1
+        } else if a1 <= origin_across && origin_across < a0 {
+            -1
+        } else {
+            continue;
+        };
+
+        let t = solve_monotonic(across, origin_across, t0, t1);
+        if evaluate(along, t) > origin_along {
+            winding += direction * orientation;
+        }
+    }
+
+    winding
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::bezier::Cubic;
+
+    #[test]
+    fn crossings() {
+        // An S-shaped curve running from bottom to top, that crosses the line
+        // y = 5 three times.
+        let s = Cubic {
+            x: [0.0, 30.0, -20.0, 10.0],
+            y: [0.0, 15.0, -5.0, 10.0],
+        };
+        let s = s.as_slice();
+
+        assert_eq!(ray_crossings(s, Point::new(-100.0, 5.0), Axis::X, None), 1);
+        assert_eq!(ray_crossings(s, Point::new(100.0, 5.0), Axis::X, None), 0);
+        assert_eq!(ray_crossings(s, Point::new(-100.0, 20.0), Axis::X, None), 0);
+
+        // Only the first two crossings lie ahead of this point, and they go in
+        // opposite directions.
+        assert_eq!(ray_crossings(s, Point::new(3.0, 5.0), Axis::X, None), 0);
+
+        // Casting along y reverses the sense of the crossing.
+        let line = Cubic {
+            x: [0.0, 3.0, 7.0, 10.0],
+            y: [0.0, 0.0, 0.0, 0.0],
+        };
+        let line = line.as_slice();
+        assert_eq!(
+            ray_crossings(line, Point::new(5.0, -1.0), Axis::Y, None),
+            -1
+        );
+        assert_eq!(ray_crossings(line, Point::new(5.0, 1.0), Axis::Y, None), 0);
+        assert_eq!(
+            ray_crossings(line, Point::new(5.0, -1.0), Axis::Y, Some(0.5)),
+            0
+        );
+    }
+
+    #[test]
+    fn shared_end_points() {
+        // Two curves meeting exactly on the ray are counted once in total.
+        let up = Cubic {
+            x: [0.0, 0.0, 0.0, 0.0],
+            y: [0.0, 2.0, 3.0, 5.0],
+        };
+        let up_again = Cubic {
+            x: [0.0, 0.0, 0.0, 0.0],
+            y: [5.0, 6.0, 8.0, 10.0],
+        };
+        let down = Cubic {
+            x: [0.0, 0.0, 0.0, 0.0],
+            y: [5.0, 4.0, 2.0, 0.0],
+        };
+
+        let origin = Point::new(-1.0, 5.0);
+        let through = ray_crossings(up.as_slice(), origin, Axis::X, None)
+            + ray_crossings(up_again.as_slice(), origin, Axis::X, None);
+        assert_eq!(through, 1);
+
+        // Touching the ray and turning back doesn't cross it.
+        let touch = ray_crossings(up.as_slice(), origin, Axis::X, None)
+            + ray_crossings(down.as_slice(), origin, Axis::X, None);
+        assert_eq!(touch, 0);
+    }
+}
@@ -1,3 +1,4 @@
+#[cfg(feature = "backend-software")]
 mod common;
 
 #[cfg(feature = "backend-software")]
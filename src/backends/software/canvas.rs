@@ -1,28 +1,122 @@
 use rand::Rng;
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use crate::{
-    backends::common::cpatch::{flatten, ChangeList, CurveBvh},
-    canvas::{Canvas, CanvasOps, CanvasOptions},
+    backends::common::cpatch::{self, CPatchList},
+    canvas::{Canvas, CanvasOps, CanvasOptions, FillMethod},
     color::{Color, Space as ColorSpace},
     hash::hash_of,
     image::{Error as ImageError, Image, PixelFormat},
-    math::vector2::Vec2,
-    paint::{Paint, PaintConfig},
+    math::affine::Affine,
+    paint::{BlendMode, CompositeOp, FillRule, Gradient, Paint, PaintConfig},
     pixel_buffer::PixelBuffer,
     shapes::{
-        bezier::Bezier,
         path::{Builder as PathBuilder, Path},
-        rect::Rect,
+        point::Point,
+        rect::{BoundingBox, Rect},
+        stroke::stroke,
     },
+    utils::bounding_volume::Bvh,
+};
+
+use super::{
+    clip::ClipMask, gradient::GradientRamp, patch_rasterizer::PatchRasterizer,
+    rasterizer::Rasterizer, BackendState,
 };
 
-use super::BackendState;
+/// The maximum distance, in pixels, between a stroke outline and the true
+/// offset of the path being stroked.
+const STROKE_TOLERANCE: f32 = 0.1;
 
 pub struct SoftwareCanvas {
     shared_state: Rc<RefCell<BackendState>>,
     pixels: PixelBuffer,
     config: CanvasOptions,
+    rasterizer: Rasterizer,
+    patch_builder: cpatch::Builder,
+    patches: CPatchList,
+    patch_rasterizer: PatchRasterizer,
+    /// Every shape drawn since the canvas was last cleared, for hit testing.
+    /// Always empty unless `config.record_shapes` is set.
+    shapes: Vec<Shape>,
+    /// The number of shapes drawn since the canvas was last cleared, including
+    /// those that were culled and never recorded.
+    draw_count: usize,
+    shape_bvh: RefCell<Bvh<ShapeBounds>>,
+    /// Set when shapes have been drawn since `shape_bvh` was last built.
+    shape_bvh_stale: Cell<bool>,
+    state: DrawState,
+    saved_states: Vec<DrawState>,
+    /// The layers that have been pushed, innermost last. While there are any,
+    /// `pixels` holds the innermost layer's contents, which only cover the
+    /// layer's area.
+    layers: Vec<Layer>,
+}
+
+/// The drawing state that is saved and restored by `save()` and `restore()`.
+#[derive(Clone, Default)]
+struct DrawState {
+    transform: Affine,
+    /// The area that can be drawn to, or the whole canvas if `None`. Masks are
+    /// immutable once built, so they are shared with saved states.
+    clip: Option<Rc<ClipMask>>,
+}
+
+/// A layer that is being drawn to in place of the target beneath it.
+struct Layer {
+    /// The pixels of the layer or canvas beneath this one.
+    backdrop: PixelBuffer,
+    /// The area of the canvas covered by the layer's pixels, in whole pixels.
+    /// This is the clip's bounds when the layer was pushed, since nothing
+    /// outside of it can be drawn.
+    area: Rect,
+    opacity: f32,
+    blend_mode: BlendMode,
+    /// The number of saved states when the layer was pushed, including the
+    /// one saved by pushing it.
+    saved_states: usize,
+    /// The number of recorded shapes when the layer was pushed, which clearing
+    /// the layer leaves alone.
+    shapes: usize,
+    /// The area that has been drawn to within the layer, if any.
+    dirty: Option<Rect>,
+}
+
+/// The colors that a shape is filled with.
+enum Source {
+    Solid(Color),
+    /// A gradient, along with the transform from pixels to the gradient's
+    /// coordinates.
+    Gradient(Rc<GradientRamp>, Affine),
+}
+
+/// A shape as it was drawn to the canvas.
+struct Shape {
+    /// The shape's position in drawing order.
+    index: usize,
+    path: Path,
+    /// The part of the path's bounds that was drawn to.
+    bounds: Rect,
+    fill_rule: FillRule,
+    clip: Option<Rc<ClipMask>>,
+}
+
+/// The part of a shape's bounds that lies within the canvas.
+#[derive(Clone, Copy)]
+struct ShapeBounds {
+    bounds: Rect,
+    /// The shape's position in `shapes`.
+    index: usize,
+}
+
+impl BoundingBox for ShapeBounds {
+    fn bounding_box(&self) -> Rect {
+        self.bounds
+    }
 }
 
 impl SoftwareCanvas {
@@ -38,41 +132,261 @@ impl SoftwareCanvas {
             shared_state,
             config,
             pixels: PixelBuffer::new(width, height, format, color_space)?,
+            rasterizer: Rasterizer::default(),
+            patch_builder: cpatch::Builder::default(),
+            patches: CPatchList::default(),
+            patch_rasterizer: PatchRasterizer::default(),
+            shapes: Vec::new(),
+            draw_count: 0,
+            shape_bvh: RefCell::new(Bvh::default()),
+            shape_bvh_stale: Cell::new(false),
+            state: DrawState::default(),
+            saved_states: Vec::new(),
+            layers: Vec::new(),
         })
     }
+
+    /// The pixels of the canvas itself, beneath any layers.
+    fn canvas_pixels(&self) -> &PixelBuffer {
+        match self.layers.first() {
+            Some(layer) => &layer.backdrop,
+            None => &self.pixels,
+        }
+    }
+
+    /// The area of the canvas that `pixels` covers.
+    fn area(&self) -> Rect {
+        match self.layers.last() {
+            Some(layer) => layer.area,
+            None => Rect::new(0.0, self.width() as f32, 0.0, self.height() as f32),
+        }
+    }
+
+    /// Records that `area` of the innermost layer has been drawn to, if there
+    /// is one.
+    fn mark_dirty(&mut self, area: Rect) {
+        if let Some(layer) = self.layers.last_mut() {
+            layer.dirty = Some(match layer.dirty {
+                Some(dirty) => dirty | area,
+                None => area,
+            });
+        }
+    }
+
+    /// Finds the colors to fill a shape with, given a paint's color and its
+    /// gradient, if it has one.
+    fn source(&self, color: Color, gradient: Option<&Gradient>) -> Source {
+        let gradient = match gradient {
+            Some(gradient) => gradient,
+            None => return Source::Solid(color),
+        };
+
+        let hash = hash_of(gradient);
+        let ramp = self.shared_state.borrow().gradients.get(&hash).cloned();
+        let ramp = match ramp {
+            Some(ramp) => ramp,
+            None => {
+                let ramp = Rc::new(GradientRamp::new(gradient));
+                self.shared_state
+                    .borrow_mut()
+                    .gradients
+                    .insert(hash, ramp.clone());
+                ramp
+            }
+        };
+
+        // Gradients that collapse onto a line or a point have no color.
+        match (self.state.transform * gradient.transform).invert() {
+            Some(inverse) => Source::Gradient(ramp, inverse),
+            None => Source::Solid(Color {
+                a: 0.0,
+                ..Color::BLACK
+            }),
+        }
+    }
+
+    /// Maps `path` from path coordinates to pixels with the current transform,
+    /// copying it only if the transform would change it.
+    fn transform_path<'a>(&self, path: Cow<'a, Path>) -> Cow<'a, Path> {
+        if self.state.transform == Affine::IDENTITY {
+            return path;
+        }
+        let mut path = path.into_owned();
+        path.transform(&self.state.transform);
+        Cow::Owned(path)
+    }
+
+    fn fill(
+        &mut self,
+        path: Cow<Path>,
+        source: Source,
+        fill_rule: FillRule,
+        blend_mode: BlendMode,
+        op: CompositeOp,
+    ) {
+        let source = if self.config.debug_randomize_color {
+            Source::Solid(rand::thread_rng().gen())
+        } else {
+            source
+        };
+
+        let index = self.draw_count;
+        self.draw_count += 1;
+
+        // Open segments are filled as though they were closed.
+        let path = if path.is_closed() {
+            path
+        } else {
+            Cow::Owned(path.to_closed())
+        };
+
+        let area = self.area();
+        let clip = self.state.clip.clone();
+        let bounds = match &clip {
+            Some(clip) => path.bounds() & area & clip.bounds(),
+            None => path.bounds() & area,
+        };
+        if bounds.area() <= 0.0 {
+            return;
+        }
+
+        self.mark_dirty(bounds);
+
+        let pixels = &mut self.pixels;
+        let (left, top) = (area.left as u32, area.top as u32);
+        let composite = |x, y, coverage: f32| {
+            let coverage = match &clip {
+                Some(clip) => coverage * clip.coverage(x, y),
+                None => coverage,
+            };
+            // Gradients are sampled at the center of each pixel.
+            let color = match &source {
+                Source::Solid(color) => *color,
+                Source::Gradient(ramp, inverse) => {
+                    ramp.sample(*inverse * Point::new(x as f32 + 0.5, y as f32 + 0.5))
+                }
+            };
+            pixels.composite(x - left, y - top, color, coverage, blend_mode, op);
+        };
+
+        match self.config.fill_method {
+            FillMethod::Scanline => {
+                self.rasterizer.begin(bounds);
+                self.rasterizer.add_path(&path);
+                self.rasterizer.finish(fill_rule, composite);
+            }
+            FillMethod::CPatch => {
+                self.patches.clear();
+                self.patch_builder
+                    .build(&path, fill_rule, &mut self.patches);
+                self.patch_rasterizer.fill(&self.patches, bounds, composite);
+            }
+        }
+
+        if self.config.record_shapes {
+            self.shapes.push(Shape {
+                index,
+                path: path.into_owned(),
+                bounds,
+                fill_rule,
+                clip,
+            });
+            self.shape_bvh_stale.set(true);
+        }
+    }
 }
 
 impl Canvas for SoftwareCanvas {
     fn get_pixels(&self) -> PixelBuffer {
-        self.pixels.clone()
+        self.canvas_pixels().clone()
     }
 }
 
 impl CanvasOps for SoftwareCanvas {
     fn width(&self) -> u32 {
-        self.pixels.width()
+        self.canvas_pixels().width()
     }
 
     fn height(&self) -> u32 {
-        self.pixels.height()
+        self.canvas_pixels().height()
     }
 
     fn clear(&mut self, color: Color) {
-        self.pixels.clear(color);
+        let layer_shapes = match self.layers.last() {
+            Some(layer) => layer.shapes,
+            None => {
+                self.pixels.clear(color);
+                self.shapes.clear();
+                self.draw_count = 0;
+                self.shape_bvh_stale.set(true);
+                return;
+            }
+        };
+
+        self.shapes.truncate(layer_shapes);
+        self.shape_bvh_stale.set(true);
+
+        let area = self.area();
+        let clip = match &self.state.clip {
+            Some(clip) => clip.clone(),
+            None => {
+                self.pixels.clear(color);
+                self.mark_dirty(area);
+                return;
+            }
+        };
+
+        let bounds = area & clip.bounds();
+        if bounds.area() <= 0.0 {
+            return;
+        }
+        self.mark_dirty(bounds);
+
+        let (left, top) = (area.left as u32, area.top as u32);
+        for y in bounds.top.floor() as u32..bounds.bottom.ceil() as u32 {
+            for x in bounds.left.floor() as u32..bounds.right.ceil() as u32 {
+                let coverage = clip.coverage(x, y);
+                if coverage > 0.0 {
+                    self.pixels.composite(
+                        x - left,
+                        y - top,
+                        color,
+                        coverage,
+                        BlendMode::Normal,
+                        CompositeOp::Source,
+                    );
+                }
+            }
+        }
     }
 
     fn create_paint(&mut self, config: PaintConfig) -> Paint {
         let hash = hash_of(&config);
-        self.shared_state.borrow_mut().paints.insert(hash, config);
+        let mut shared_state = self.shared_state.borrow_mut();
+        for gradient in [&config.fill_gradient, &config.stroke_gradient]
+            .into_iter()
+            .flatten()
+        {
+            shared_state
+                .gradients
+                .entry(hash_of(gradient))
+                .or_insert_with(|| Rc::new(GradientRamp::new(gradient)));
+        }
+        shared_state.paints.insert(hash, config);
         Paint::new(hash)
     }
 
-    fn destroy_paint(&mut self, paint: Paint) {
+    fn destroy_paint(&mut self, _paint: Paint) {
         todo!()
     }
 
     fn paint_config(&self, paint: Paint) -> PaintConfig {
-        todo!()
+        self.shared_state
+            .borrow()
+            .paints
+            .get(&paint.handle)
+            .cloned()
+            .unwrap_or_default()
     }
 
     fn begin_path(&mut self) -> PathBuilder {
@@ -80,107 +394,675 @@ impl CanvasOps for SoftwareCanvas {
     }
 
     fn fill_path(&mut self, path: &Path, paint: Paint) {
-        let mut path = path.clone();
-
-        let prect = Rect::new(0.0, self.width() as f32, 0.0, self.height() as f32);
-
-        if true {
-            let mut change_buffer = ChangeList::default();
-            let mut bvh_builder = CurveBvh::storage();
-
-            // let mul = normalize(&mut path);
-            println!("Num points before flattening: {}", path.x.len());
-            let bvh = flatten(&mut path, &mut change_buffer, &mut bvh_builder);
-            println!("BVH computed with {} nodes", bvh.nodes.len());
-            println!("Num points after flattening: {}", path.x.len());
-
-            // println!("\t nodes: {:?}", &bvh.nodes);
-
-            for node in bvh.nodes.iter() {
-                // let bbox = Rect::new(
-                //     mul.left + (node.bbox.left * mul.width()),
-                //     mul.left + (node.bbox.right * mul.width()),
-                //     mul.top + (node.bbox.top * mul.height()),
-                //     mul.top + (node.bbox.bottom * mul.height()),
-                // );
-
-                let bounds = (node.bbox + Vec2::new(400.0, 100.0)) & prect;
-                if bounds.width() > 0.0 {
-                    for x in bounds.left.round() as u32..bounds.right.round() as u32 {
-                        self.pixels.set(x, bounds.top.round() as u32, Color::GREEN);
-                        self.pixels
-                            .set(x, bounds.bottom.round() as u32, Color::GREEN);
-                    }
-                    for y in bounds.top.round() as u32..bounds.bottom.round() as u32 {
-                        self.pixels.set(bounds.left.round() as u32, y, Color::GREEN);
-                        self.pixels
-                            .set(bounds.right.round() as u32, y, Color::GREEN);
-                    }
-                }
+        let config = self.paint_config(paint);
+        let path = self.transform_path(Cow::Borrowed(path));
+        let source = self.source(config.fill_color, config.fill_gradient.as_ref());
+        self.fill(
+            path,
+            source,
+            config.fill_rule,
+            config.blend_mode,
+            config.composite_op,
+        );
+    }
+
+    fn stroke_path(&mut self, path: &Path, paint: Paint) {
+        let config = self.paint_config(paint);
+        // Stroking happens before the transform so that the stroke is scaled
+        // and skewed along with the path, which means that the tolerance has
+        // to be scaled to match.
+        let scale = self.state.transform.scale_factor();
+        let tolerance = if scale > 0.0 {
+            STROKE_TOLERANCE / scale
+        } else {
+            STROKE_TOLERANCE
+        };
+        let outline = stroke(path, &config.stroke_style, tolerance);
+        let outline = self.transform_path(Cow::Owned(outline));
+        let source = self.source(config.stroke_color, config.stroke_gradient.as_ref());
+        self.fill(
+            outline,
+            source,
+            FillRule::NonZero,
+            config.blend_mode,
+            config.composite_op,
+        );
+    }
+
+    fn hit_test(&self, point: Point) -> Option<usize> {
+        if self.shape_bvh_stale.replace(false) {
+            let bounds = self
+                .shapes
+                .iter()
+                .enumerate()
+                .map(|(index, shape)| ShapeBounds {
+                    bounds: shape.bounds,
+                    index,
+                });
+            self.shape_bvh.borrow_mut().rebuild(bounds);
+        }
+
+        // Shapes that cover the point are ranked by how many shapes were drawn
+        // over them, scaled to lie within [0, 1), and all other shapes are
+        // further away than that. The point is inside of the bounds of every
+        // shape that covers it, so the topmost one rules out everything else.
+        let count = self.shapes.len() as f32;
+        let covers = |shape: &Shape| {
+            let visible = match &shape.clip {
+                Some(clip) => clip.coverage(point.x as u32, point.y as u32) > 0.0,
+                None => true,
+            };
+            visible
+                && shape
+                    .fill_rule
+                    .is_filled(cpatch::winding_number(&shape.path, point))
+        };
+
+        let bvh = self.shape_bvh.borrow();
+        let (candidate, distance) = bvh.query_nearest(point, |candidate| {
+            if covers(&self.shapes[candidate.index]) {
+                (count - 1.0 - candidate.index as f32) / count
+            } else {
+                1.0 + candidate.bounds.distance_to(point)
+            }
+        })?;
+        (distance < 1.0).then_some(self.shapes[candidate.index].index)
+    }
+
+    fn save(&mut self) {
+        self.saved_states.push(self.state.clone());
+    }
+
+    fn restore(&mut self) {
+        // States saved before the innermost layer was pushed can only be
+        // restored by popping it.
+        let floor = self.layers.last().map_or(0, |layer| layer.saved_states);
+        if self.saved_states.len() > floor {
+            if let Some(state) = self.saved_states.pop() {
+                self.state = state;
             }
         }
+    }
 
-        let mut rng = rand::thread_rng();
+    fn transform(&self) -> Affine {
+        self.state.transform
+    }
 
-        let mut color = if self.config.debug_randomize_color {
-            rng.gen()
-        } else {
-            self.shared_state
-                .borrow()
-                .paints
-                .get(&paint.handle)
-                .map_or(Color::DEFAULT, |p| p.fill_color)
+    fn set_transform(&mut self, transform: Affine) {
+        self.state.transform = transform;
+    }
+
+    fn clip_path(&mut self, path: &Path, fill_rule: FillRule) {
+        let path = self.transform_path(Cow::Owned(path.to_closed()));
+        let canvas_rect = Rect::new(0.0, self.width() as f32, 0.0, self.height() as f32);
+        let mask = ClipMask::new(
+            &mut self.rasterizer,
+            &path,
+            fill_rule,
+            canvas_rect,
+            self.state.clip.as_deref(),
+        );
+        self.state.clip = Some(Rc::new(mask));
+    }
+
+    fn push_layer(&mut self, opacity: f32, blend_mode: BlendMode, clip: Option<(&Path, FillRule)>) {
+        self.save();
+        let saved_states = self.saved_states.len();
+        if let Some((path, fill_rule)) = clip {
+            self.clip_path(path, fill_rule);
+        }
+
+        // Nothing outside of the clip can be drawn, so the layer only needs to
+        // cover its bounds. The buffer is never empty, even if the area is.
+        let area = match &self.state.clip {
+            Some(clip) => self.area() & clip.bounds(),
+            None => self.area(),
         };
+        let left = area.left.floor().max(0.0);
+        let top = area.top.floor().max(0.0);
+        let area = Rect::new(
+            left,
+            area.right.ceil().max(left),
+            top,
+            area.bottom.ceil().max(top),
+        );
+        let pixels = self
+            .pixels
+            .new_layer((area.width() as u32).max(1), (area.height() as u32).max(1));
 
-        for segment in path.iter() {
-            for curve in segment {
-                let mut t = 0.0;
-                let delta = 0.001;
-                loop {
-                    if t >= 1.0 {
-                        break;
-                    }
-
-                    let p = curve.at(t) + Vec2::new(400.0, 100.0);
-
-                    if p.x > 0.0 && p.y > 0.0 {
-                        self.pixels
-                            .set(p.x.round() as u32, p.y.round() as u32, color);
-                    }
-                    t += delta;
-                }
+        self.layers.push(Layer {
+            backdrop: std::mem::replace(&mut self.pixels, pixels),
+            area,
+            opacity: opacity.clamp(0.0, 1.0),
+            blend_mode,
+            saved_states,
+            shapes: self.shapes.len(),
+            dirty: None,
+        });
+    }
 
-                if false {
-                    // draw bounding boxes
-                    let bounds = (curve.coarse_bounds() + Vec2::new(400.0, 100.0)) & prect;
-                    if bounds.width() > 0.0 {
-                        for x in bounds.left.round() as u32..bounds.right.round() as u32 {
-                            self.pixels
-                                .set(x, bounds.top.round() as u32, Color::BRIGHT_PINK);
-                            self.pixels
-                                .set(x, bounds.bottom.round() as u32, Color::BRIGHT_PINK);
-                        }
-                        for y in bounds.top.round() as u32..bounds.bottom.round() as u32 {
-                            self.pixels
-                                .set(bounds.left.round() as u32, y, Color::BRIGHT_PINK);
-                            self.pixels
-                                .set(bounds.right.round() as u32, y, Color::BRIGHT_PINK);
-                        }
-                    }
-                }
+    fn pop_layer(&mut self) {
+        let layer = match self.layers.pop() {
+            Some(layer) => layer,
+            None => return,
+        };
+
+        let source = std::mem::replace(&mut self.pixels, layer.backdrop);
+        self.saved_states.truncate(layer.saved_states);
+        self.restore();
+
+        let dirty = match layer.dirty {
+            Some(dirty) if layer.opacity > 0.0 => dirty,
+            _ => return,
+        };
+        self.mark_dirty(dirty);
 
-                if self.config.debug_randomize_color {
-                    let mut new_color = rng.gen();
-                    while new_color == color {
-                        new_color = rng.gen();
-                    }
-                    color = new_color;
+        let (left, top) = (layer.area.left as u32, layer.area.top as u32);
+        let backdrop = self.area();
+        let (backdrop_left, backdrop_top) = (backdrop.left as u32, backdrop.top as u32);
+        for y in dirty.top.floor() as u32..dirty.bottom.ceil() as u32 {
+            for x in dirty.left.floor() as u32..dirty.right.ceil() as u32 {
+                let color = source.get(x - left, y - top);
+                if color.a > 0.0 {
+                    self.pixels.composite(
+                        x - backdrop_left,
+                        y - backdrop_top,
+                        color,
+                        layer.opacity,
+                        layer.blend_mode,
+                        CompositeOp::SourceOver,
+                    );
                 }
             }
         }
     }
+}
 
-    fn stroke_path(&mut self, path: &Path, paint: Paint) {
-        todo!()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backends::software::Software, math::vector2::Vec2, paint::ColorStop};
+
+    fn square(x: f32, y: f32, size: f32) -> Path {
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(x, y));
+        builder.line_to(Point::new(x + size, y)).unwrap();
+        builder.line_to(Point::new(x + size, y + size)).unwrap();
+        builder.line_to(Point::new(x, y + size)).unwrap();
+        builder.close().unwrap();
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn hit_test() {
+        let backend = Software::new();
+        let mut canvas = backend
+            .new_canvas(
+                64,
+                64,
+                PixelFormat::Rgba8,
+                ColorSpace::LinearSrgb,
+                CanvasOptions {
+                    record_shapes: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let paint = canvas.create_paint(PaintConfig {
+            fill_color: Color::RED,
+            ..Default::default()
+        });
+
+        // A ring, and a square overlapping part of it and its hole.
+        let mut ring = PathBuilder::default();
+        ring.move_to(Point::new(4.0, 4.0));
+        for (x, y) in [(36.0, 4.0), (36.0, 36.0), (4.0, 36.0)] {
+            ring.line_to(Point::new(x, y)).unwrap();
+        }
+        ring.close().unwrap();
+        ring.move_to(Point::new(12.0, 12.0));
+        for (x, y) in [(28.0, 12.0), (28.0, 28.0), (12.0, 28.0)] {
+            ring.line_to(Point::new(x, y)).unwrap();
+        }
+        ring.close().unwrap();
+        let ring = ring.build().unwrap();
+
+        let even_odd = canvas.create_paint(PaintConfig {
+            fill_color: Color::BLUE,
+            fill_rule: FillRule::EvenOdd,
+            ..Default::default()
+        });
+
+        canvas.fill_path(&ring, even_odd);
+        canvas.fill_path(&square(24.0, 24.0, 20.0), paint);
+        // Entirely off of the canvas, so it isn't drawn.
+        canvas.fill_path(&square(100.0, 100.0, 10.0), paint);
+
+        assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), Some(0));
+        assert_eq!(canvas.hit_test(Point::new(20.0, 20.0)), None);
+        assert_eq!(canvas.hit_test(Point::new(26.0, 26.0)), Some(1));
+        assert_eq!(canvas.hit_test(Point::new(32.0, 32.0)), Some(1));
+        assert_eq!(canvas.hit_test(Point::new(40.0, 40.0)), Some(1));
+        assert_eq!(canvas.hit_test(Point::new(50.0, 50.0)), None);
+        assert_eq!(canvas.hit_test(Point::new(105.0, 105.0)), None);
+
+        // The square that wasn't drawn still counts towards the index.
+        canvas.fill_path(&square(0.0, 0.0, 10.0), paint);
+        assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), Some(3));
+
+        canvas.clear(Color::BLACK);
+        assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), None);
+
+        // Without recording, nothing can be hit.
+        let mut canvas = backend
+            .new_canvas(
+                64,
+                64,
+                PixelFormat::Rgba8,
+                ColorSpace::LinearSrgb,
+                CanvasOptions::default(),
+            )
+            .unwrap();
+        canvas.fill_path(&square(0.0, 0.0, 10.0), paint);
+        assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), None);
+    }
+
+    #[test]
+    fn fill_open_path() {
+        let backend = Software::new();
+        let mut canvas = backend
+            .new_canvas(
+                32,
+                32,
+                PixelFormat::Rgba8,
+                ColorSpace::LinearSrgb,
+                CanvasOptions {
+                    record_shapes: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let paint = canvas.create_paint(PaintConfig {
+            fill_color: Color::WHITE,
+            ..Default::default()
+        });
+
+        let triangle = |close: bool| {
+            let mut builder = PathBuilder::default();
+            builder.move_to(Point::new(4.0, 4.0));
+            builder.line_to(Point::new(28.0, 4.0)).unwrap();
+            builder.line_to(Point::new(4.0, 28.0)).unwrap();
+            if close {
+                builder.close().unwrap();
+            }
+            builder.build().unwrap()
+        };
+
+        canvas.clear(Color::BLACK);
+        canvas.fill_path(&triangle(true), paint);
+        let closed = canvas.get_pixels();
+
+        canvas.clear(Color::BLACK);
+        canvas.fill_path(&triangle(false), paint);
+        let open = canvas.get_pixels();
+
+        for y in 0..32 {
+            for x in 0..32 {
+                assert_eq!(closed.get(x, y).r, open.get(x, y).r);
+            }
+        }
+        assert_eq!(open.get(8, 8).r, 1.0);
+        assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), Some(0));
+    }
+
+    #[test]
+    fn transform_stack() {
+        let backend = Software::new();
+        let mut canvas = backend
+            .new_canvas(
+                32,
+                32,
+                PixelFormat::Rgba8,
+                ColorSpace::LinearSrgb,
+                CanvasOptions {
+                    record_shapes: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let paint = canvas.create_paint(PaintConfig {
+            fill_color: Color::WHITE,
+            ..Default::default()
+        });
+
+        canvas.clear(Color::BLACK);
+        canvas.save();
+        canvas.translate(Vec2::new(16.0, 0.0));
+        canvas.scale(2.0, 2.0);
+        {
+            let mut scope = canvas.scoped();
+            scope.translate(Vec2::new(0.0, 8.0));
+            scope.fill_path(&square(0.0, 0.0, 4.0), paint);
+        }
+        canvas.fill_path(&square(0.0, 0.0, 4.0), paint);
+        canvas.restore();
+        canvas.fill_path(&square(0.0, 0.0, 4.0), paint);
+        // Restoring with nothing saved leaves the transform alone.
+        canvas.restore();
+        assert_eq!(canvas.transform(), Affine::IDENTITY);
+
+        let pixels = canvas.get_pixels();
+        let is_filled = |x, y| pixels.get(x, y).r == 1.0;
+        assert!(is_filled(2, 2));
+        assert!(!is_filled(6, 6));
+        assert!(is_filled(22, 6));
+        assert!(!is_filled(26, 10));
+        assert!(is_filled(22, 22));
+        assert!(!is_filled(14, 22));
+
+        // Hit testing happens in pixels, regardless of the transform.
+        assert_eq!(canvas.hit_test(Point::new(22.0, 22.0)), Some(0));
+    }
+
+    #[test]
+    fn clip() {
+        for fill_method in [FillMethod::Scanline, FillMethod::CPatch] {
+            let backend = Software::new();
+            let mut canvas = backend
+                .new_canvas(
+                    32,
+                    32,
+                    PixelFormat::Rgba8,
+                    ColorSpace::LinearSrgb,
+                    CanvasOptions {
+                        fill_method,
+                        record_shapes: true,
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+
+            let paint = canvas.create_paint(PaintConfig {
+                fill_color: Color::WHITE,
+                ..Default::default()
+            });
+
+            canvas.clear(Color::BLACK);
+            {
+                let mut scope = canvas.scoped();
+                scope.clip_rect(Rect::new(4.0, 20.5, 4.0, 28.0));
+                scope.translate(Vec2::new(12.0, 0.0));
+                scope.clip_rect(Rect::new(0.0, 16.0, 0.0, 16.0));
+                scope.fill_path(&square(-12.0, 0.0, 32.0), paint);
+            }
+            canvas.fill_path(&square(0.0, 28.0, 4.0), paint);
+
+            let pixels = canvas.get_pixels();
+            let value = |x, y| pixels.get(x, y).r;
+            assert_eq!(value(12, 4), 1.0);
+            assert_eq!(value(19, 15), 1.0);
+            assert!((value(20, 10) - 0.5).abs() < 0.01);
+            assert_eq!(value(8, 8), 0.0);
+            assert_eq!(value(16, 20), 0.0);
+            assert_eq!(value(24, 8), 0.0);
+            // The clip is removed with the scope.
+            assert_eq!(value(2, 30), 1.0);
+
+            assert_eq!(canvas.hit_test(Point::new(16.0, 8.0)), Some(0));
+            assert_eq!(canvas.hit_test(Point::new(8.0, 8.0)), None);
+            assert_eq!(canvas.hit_test(Point::new(2.0, 30.0)), Some(1));
+        }
+    }
+
+    #[test]
+    fn layers() {
+        let backend = Software::new();
+        let mut canvas = backend
+            .new_canvas(
+                32,
+                32,
+                PixelFormat::Rgba8,
+                ColorSpace::LinearSrgb,
+                CanvasOptions::default(),
+            )
+            .unwrap();
+
+        let paint = canvas.create_paint(PaintConfig {
+            fill_color: Color::WHITE,
+            ..Default::default()
+        });
+
+        canvas.clear(Color::BLACK);
+        canvas.push_layer(0.5, BlendMode::Normal, None);
+        canvas.translate(Vec2::new(2.0, 2.0));
+        canvas.fill_path(&square(0.0, 0.0, 12.0), paint);
+        canvas.fill_path(&square(4.0, 4.0, 12.0), paint);
+
+        // The nested layer is clipped, and its state is restored when popped
+        // even if it wasn't balanced.
+        canvas.push_layer(
+            0.5,
+            BlendMode::Normal,
+            Some((&square(16.0, 0.0, 8.0), FillRule::NonZero)),
+        );
+        canvas.save();
+        canvas.translate(Vec2::new(16.0, 0.0));
+        canvas.fill_path(&square(0.0, 0.0, 12.0), paint);
+        canvas.pop_layer();
+        assert_eq!(canvas.transform(), Affine::translate(Vec2::new(2.0, 2.0)));
+
+        // Open layers aren't visible yet.
+        assert_eq!(canvas.get_pixels().get(8, 8).r, 0.0);
+        canvas.pop_layer();
+        canvas.pop_layer();
+        assert_eq!(canvas.transform(), Affine::IDENTITY);
+
+        let pixels = canvas.get_pixels();
+        let value = |x, y| pixels.get(x, y).r;
+        // The overlapping squares are composited together, so the overlap is
+        // no brighter than either square.
+        assert!((value(4, 4) - 0.5).abs() < 0.01);
+        assert!((value(12, 12) - 0.5).abs() < 0.01);
+        assert!((value(16, 16) - 0.5).abs() < 0.01);
+        assert!((value(20, 4) - 0.25).abs() < 0.01);
+        assert_eq!(value(28, 4), 0.0);
+        assert_eq!(value(24, 24), 0.0);
+    }
+
+    #[test]
+    fn bounded_composite_ops() {
+        let backend = Software::new();
+        let mut canvas = SoftwareCanvas::new(
+            16,
+            16,
+            PixelFormat::Rgba8,
+            ColorSpace::LinearSrgb,
+            CanvasOptions::default(),
+            backend.shared.clone(),
+        )
+        .unwrap();
+
+        for op in [
+            CompositeOp::Clear,
+            CompositeOp::Source,
+            CompositeOp::SourceIn,
+            CompositeOp::DestinationIn,
+            CompositeOp::DestinationAtop,
+        ] {
+            let paint = canvas.create_paint(PaintConfig {
+                fill_color: Color {
+                    a: 0.5,
+                    ..Color::BLUE
+                },
+                composite_op: op,
+                ..Default::default()
+            });
+            canvas.clear(Color::RED);
+            canvas.fill_path(&square(4.0, 4.0, 8.0), paint);
+
+            // Only the pixels covered by the square are affected.
+            let pixels = canvas.get_pixels();
+            assert_ne!(pixels.get(8, 8), Color::RED, "{op:?}");
+            assert_eq!(pixels.get(2, 2), Color::RED, "{op:?}");
+            assert_eq!(pixels.get(14, 8), Color::RED, "{op:?}");
+        }
+    }
+
+    #[test]
+    fn layer_restore_and_clear() {
+        let backend = Software::new();
+        let mut canvas = SoftwareCanvas::new(
+            32,
+            32,
+            PixelFormat::Rgba8,
+            ColorSpace::LinearSrgb,
+            CanvasOptions {
+                record_shapes: true,
+                ..Default::default()
+            },
+            backend.shared.clone(),
+        )
+        .unwrap();
+
+        let paint = canvas.create_paint(PaintConfig {
+            fill_color: Color::WHITE,
+            ..Default::default()
+        });
+
+        canvas.clear(Color::BLACK);
+        canvas.fill_path(&square(0.0, 0.0, 8.0), paint);
+
+        // Unbalanced restores inside of a layer can't reach the states saved
+        // outside of it.
+        canvas.save();
+        canvas.translate(Vec2::new(1.0, 1.0));
+        let mut triangle = PathBuilder::default();
+        triangle.move_to(Point::new(16.0, 16.0));
+        triangle.line_to(Point::new(32.0, 16.0)).unwrap();
+        triangle.line_to(Point::new(16.0, 32.0)).unwrap();
+        triangle.close().unwrap();
+        let triangle = triangle.build().unwrap();
+        canvas.push_layer(1.0, BlendMode::Normal, Some((&triangle, FillRule::NonZero)));
+        canvas.translate(Vec2::new(1.0, 1.0));
+        canvas.restore();
+        canvas.restore();
+        assert_eq!(canvas.transform(), Affine::translate(Vec2::new(2.0, 2.0)));
+
+        // Clearing the layer only clears within its clip, and only forgets the
+        // shapes drawn inside of it.
+        canvas.fill_path(&square(16.0, 16.0, 4.0), paint);
+        assert_eq!(canvas.hit_test(Point::new(20.0, 20.0)), Some(1));
+        canvas.clear(Color::RED);
+        assert_eq!(canvas.hit_test(Point::new(20.0, 20.0)), None);
+        assert_eq!(canvas.hit_test(Point::new(4.0, 4.0)), Some(0));
+
+        canvas.pop_layer();
+        assert_eq!(canvas.transform(), Affine::translate(Vec2::new(1.0, 1.0)));
+        canvas.restore();
+        assert_eq!(canvas.transform(), Affine::IDENTITY);
+
+        let pixels = canvas.get_pixels();
+        assert_eq!(pixels.get(20, 20).r, 1.0);
+        assert_eq!(pixels.get(20, 20).g, 0.0);
+        assert_eq!(pixels.get(30, 30).r, 0.0);
+        assert_eq!(pixels.get(4, 4).g, 1.0);
+    }
+
+    #[test]
+    fn layer_storage() {
+        let backend = Software::new();
+        let new_canvas = || {
+            SoftwareCanvas::new(
+                32,
+                32,
+                PixelFormat::Rgb10a2,
+                ColorSpace::LinearSrgb,
+                CanvasOptions::default(),
+                backend.shared.clone(),
+            )
+            .unwrap()
+        };
+        let mut direct = new_canvas();
+        let mut layered = new_canvas();
+
+        // Rounds differently with 8 and 10 bits per channel.
+        let paint = direct.create_paint(PaintConfig {
+            fill_color: Color {
+                r: 0.5004,
+                g: 0.5004,
+                b: 0.5004,
+                a: 1.0,
+                space: ColorSpace::LinearSrgb,
+            },
+            ..Default::default()
+        });
+        direct.clear(Color::BLACK);
+        direct.fill_path(&square(4.0, 4.0, 8.0), paint);
+
+        // The layer only covers the clip's bounds, rounded out to whole pixels.
+        layered.clear(Color::BLACK);
+        layered.push_layer(
+            1.0,
+            BlendMode::Normal,
+            Some((&square(2.5, 2.5, 12.0), FillRule::NonZero)),
+        );
+        assert_eq!(layered.pixels.width(), 13);
+        assert_eq!(layered.pixels.height(), 13);
+        assert_eq!(layered.pixels.pixel_format(), PixelFormat::Rgba16);
+        assert_eq!(layered.width(), 32);
+        layered.fill_path(&square(4.0, 4.0, 8.0), paint);
+        layered.pop_layer();
+
+        assert!(direct.get_pixels().bytes() == layered.get_pixels().bytes());
+    }
+
+    #[test]
+    fn gradients() {
+        let backend = Software::new();
+        let mut canvas = SoftwareCanvas::new(
+            32,
+            32,
+            PixelFormat::Rgba8,
+            ColorSpace::LinearSrgb,
+            CanvasOptions::default(),
+            backend.shared.clone(),
+        )
+        .unwrap();
+
+        let mut gradient = Gradient::linear(
+            Point::new(0.0, 0.0),
+            Point::new(8.0, 0.0),
+            vec![
+                ColorStop::new(0.0, Color::BLACK),
+                ColorStop::new(1.0, Color::WHITE),
+            ],
+        );
+        gradient.interpolation = ColorSpace::LinearSrgb;
+        let paint = canvas.create_paint(PaintConfig {
+            fill_gradient: Some(gradient.clone()),
+            ..Default::default()
+        });
+        let stroke = canvas.create_paint(PaintConfig {
+            stroke_gradient: Some(gradient.clone()),
+            ..Default::default()
+        });
+        // Paints share the lookup tables of identical gradients.
+        assert_ne!(paint.handle, stroke.handle);
+        assert_eq!(backend.shared.borrow().gradients.len(), 1);
+
+        // The gradient follows the canvas' transform.
+        canvas.clear(Color::BLACK);
+        canvas.scale(4.0, 4.0);
+        canvas.fill_path(&square(0.0, 0.0, 8.0), paint);
+
+        let pixels = canvas.get_pixels();
+        let value = |x, y| pixels.get(x, y).r;
+        assert!((value(0, 0) - 0.016).abs() < 0.01);
+        assert!((value(15, 8) - 0.484).abs() < 0.01);
+        assert!((value(24, 24) - 0.766).abs() < 0.01);
+        assert!((value(31, 31) - 0.984).abs() < 0.01);
     }
 }
@@ -0,0 +1,152 @@
+//! Anti-aliased clip masks.
+//!
+//! A clip mask stores the coverage of every pixel within its bounds, and is
+//! built by rasterizing the clip path exactly as though it were being filled.
+//! Intersecting clips multiplies their coverage together, so the edges of
+//! nested clips stay smooth.
+
+use crate::{
+    paint::FillRule,
+    shapes::{path::Path, rect::Rect},
+};
+
+use super::rasterizer::Rasterizer;
+
+pub struct ClipMask {
+    /// The coverage of each pixel within the mask's bounds.
+    coverage: Vec<f32>,
+    /// The left edge of the mask in canvas coordinates.
+    left: u32,
+    /// The top edge of the mask in canvas coordinates.
+    top: u32,
+    /// The width of the mask in pixels.
+    width: u32,
+    /// The height of the mask in pixels.
+    height: u32,
+}
+
+impl ClipMask {
+    /// Builds the mask for the area inside of `path`, limited to `area` and
+    /// intersected with `parent`, if any.
+    ///
+    /// The area must not extend into negative coordinates.
+    pub fn new(
+        rasterizer: &mut Rasterizer,
+        path: &Path,
+        fill_rule: FillRule,
+        area: Rect,
+        parent: Option<&ClipMask>,
+    ) -> Self {
+        let area = match parent {
+            Some(parent) => path.bounds() & area & parent.bounds(),
+            None => path.bounds() & area,
+        };
+
+        let mut mask = Self {
+            coverage: Vec::new(),
+            left: area.left.floor().max(0.0) as u32,
+            top: area.top.floor().max(0.0) as u32,
+            width: 0,
+            height: 0,
+        };
+
+        if area.area() <= 0.0 {
+            return mask;
+        }
+
+        mask.width = area.right.ceil() as u32 - mask.left;
+        mask.height = area.bottom.ceil() as u32 - mask.top;
+        mask.coverage
+            .resize(mask.width as usize * mask.height as usize, 0.0);
+
+        rasterizer.begin(area);
+        rasterizer.add_path(path);
+        rasterizer.finish(fill_rule, |x, y, coverage| {
+            let parent_coverage = parent.map_or(1.0, |parent| parent.coverage(x, y));
+            let index = (y - mask.top) * mask.width + (x - mask.left);
+            mask.coverage[index as usize] = coverage.min(1.0) * parent_coverage;
+        });
+
+        mask
+    }
+
+    /// The smallest pixel-aligned rectangle enclosing every pixel that the
+    /// mask lets through.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            self.left as f32,
+            (self.left + self.width) as f32,
+            self.top as f32,
+            (self.top + self.height) as f32,
+        )
+    }
+
+    /// The fraction of the pixel at `(x, y)` that the mask lets through.
+    /// Pixels outside of the mask's bounds are not let through at all.
+    pub fn coverage(&self, x: u32, y: u32) -> f32 {
+        let (x, y) = (x.wrapping_sub(self.left), y.wrapping_sub(self.top));
+        if x < self.width && y < self.height {
+            self.coverage[(y * self.width + x) as usize]
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::cmp::ApproxEq,
+        shapes::{path::Builder as PathBuilder, point::Point},
+    };
+
+    fn rectangle(left: f32, right: f32, top: f32, bottom: f32) -> Path {
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(left, top));
+        builder.line_to(Point::new(right, top)).unwrap();
+        builder.line_to(Point::new(right, bottom)).unwrap();
+        builder.line_to(Point::new(left, bottom)).unwrap();
+        builder.close().unwrap();
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn intersect() {
+        let mut rasterizer = Rasterizer::default();
+        let area = Rect::new(0.0, 32.0, 0.0, 32.0);
+
+        let outer = rectangle(4.0, 20.5, 4.0, 20.0);
+        let outer = ClipMask::new(&mut rasterizer, &outer, FillRule::NonZero, area, None);
+        assert_eq!(outer.bounds(), Rect::new(4.0, 21.0, 4.0, 20.0));
+        assert!(outer.coverage(4, 4).approx_eq(&1.0));
+        assert!(outer.coverage(20, 10).approx_eq(&0.5));
+        assert!(outer.coverage(3, 10).approx_eq(&0.0));
+        assert!(outer.coverage(25, 25).approx_eq(&0.0));
+
+        let inner = rectangle(12.0, 28.0, 12.0, 28.0);
+        let inner = ClipMask::new(
+            &mut rasterizer,
+            &inner,
+            FillRule::NonZero,
+            area,
+            Some(&outer),
+        );
+        assert_eq!(inner.bounds(), Rect::new(12.0, 21.0, 12.0, 20.0));
+        assert!(inner.coverage(12, 12).approx_eq(&1.0));
+        assert!(inner.coverage(20, 15).approx_eq(&0.5));
+        assert!(inner.coverage(8, 8).approx_eq(&0.0));
+        assert!(inner.coverage(24, 24).approx_eq(&0.0));
+
+        let disjoint = rectangle(24.0, 28.0, 24.0, 28.0);
+        let disjoint = ClipMask::new(
+            &mut rasterizer,
+            &disjoint,
+            FillRule::NonZero,
+            area,
+            Some(&outer),
+        );
+        assert_eq!(disjoint.bounds().area(), 0.0);
+        assert!(disjoint.coverage(26, 26).approx_eq(&0.0));
+    }
+}
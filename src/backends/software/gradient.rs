@@ -0,0 +1,260 @@
+//! Gradient sampling.
+//!
+//! The colors of a gradient are interpolated into a lookup table once, when
+//! the paint is created, so that drawing only has to find each pixel's offset
+//! along the gradient.
+
+use crate::{
+    color::Color,
+    paint::{Gradient, GradientKind, Spread},
+    shapes::point::Point,
+};
+
+/// The number of colors in a gradient's lookup table. This is enough that
+/// neighboring entries differ by less than the precision of an 8-bit channel.
+const RAMP_SIZE: usize = 256;
+
+pub struct GradientRamp {
+    kind: GradientKind,
+    spread: Spread,
+    /// The colors of the gradient at evenly spaced offsets from 0 to 1, in the
+    /// gradient's interpolation color space.
+    colors: Box<[Color]>,
+}
+
+impl GradientRamp {
+    pub fn new(gradient: &Gradient) -> Self {
+        let space = gradient.interpolation;
+
+        // Offsets are clamped to the gradient, and stops that come before the
+        // stop preceding them are moved up to it. Interpolation is done with
+        // premultiplied alpha so that transparent stops don't darken their
+        // neighbors.
+        let mut previous = 0.0;
+        let stops = gradient
+            .stops
+            .iter()
+            .map(|stop| {
+                previous = stop.offset.clamp(previous, 1.0);
+                let color = stop.color.in_color_space(space);
+                (
+                    previous,
+                    [
+                        color.r * color.a,
+                        color.g * color.a,
+                        color.b * color.a,
+                        color.a,
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let colors = (0..RAMP_SIZE)
+            .map(|i| {
+                let offset = i as f32 / (RAMP_SIZE - 1) as f32;
+                let next = stops.partition_point(|(stop, _)| *stop <= offset);
+                let [r, g, b, a] = match (stops.get(next.wrapping_sub(1)), stops.get(next)) {
+                    (Some((start, from)), Some((end, to))) => {
+                        let t = (offset - start) / (end - start);
+                        [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t)
+                    }
+                    (Some((_, color)), None) | (None, Some((_, color))) => *color,
+                    (None, None) => [0.0; 4],
+                };
+
+                if a > 0.0 {
+                    Color {
+                        r: r / a,
+                        g: g / a,
+                        b: b / a,
+                        a,
+                        space,
+                    }
+                } else {
+                    Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 0.0,
+                        space,
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            kind: gradient.kind,
+            spread: gradient.spread,
+            colors,
+        }
+    }
+
+    /// The color of the gradient at `point`, in the gradient's coordinates.
+    pub fn sample(&self, point: Point) -> Color {
+        let offset = match self.kind.offset_at(point) {
+            Some(offset) => self.spread.apply(offset),
+            None => 1.0,
+        };
+        let index = (offset * (RAMP_SIZE - 1) as f32).round() as usize;
+        self.colors[index.min(RAMP_SIZE - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::Space as ColorSpace, paint::ColorStop};
+
+    fn assert_color(color: Color, expected: [f32; 4]) {
+        let actual = [color.r, color.g, color.b, color.a];
+        assert!(
+            actual
+                .iter()
+                .zip(&expected)
+                .all(|(a, e)| (a - e).abs() < 0.01),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn linear() {
+        let mut gradient = Gradient::linear(
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            vec![
+                ColorStop::new(0.0, Color::srgba(1.0, 0.0, 0.0, 1.0)),
+                ColorStop::new(1.0, Color::srgba(0.0, 0.0, 1.0, 1.0)),
+            ],
+        );
+
+        let ramp = GradientRamp::new(&gradient);
+        assert_color(ramp.sample(Point::new(0.0, 5.0)), [1.0, 0.0, 0.0, 1.0]);
+        assert_color(ramp.sample(Point::new(5.0, -5.0)), [0.5, 0.0, 0.5, 1.0]);
+        assert_color(ramp.sample(Point::new(15.0, 0.0)), [0.0, 0.0, 1.0, 1.0]);
+
+        gradient.spread = Spread::Repeat;
+        let ramp = GradientRamp::new(&gradient);
+        assert_color(ramp.sample(Point::new(12.5, 0.0)), [0.75, 0.0, 0.25, 1.0]);
+
+        gradient.spread = Spread::Reflect;
+        let ramp = GradientRamp::new(&gradient);
+        assert_color(ramp.sample(Point::new(12.5, 0.0)), [0.25, 0.0, 0.75, 1.0]);
+
+        // Halfway between red and blue in linear light is brighter in sRGB.
+        gradient.interpolation = ColorSpace::LinearSrgb;
+        let ramp = GradientRamp::new(&gradient);
+        let color = ramp.sample(Point::new(5.0, 0.0));
+        assert_eq!(color.space, ColorSpace::LinearSrgb);
+        assert_color(
+            color.in_color_space(ColorSpace::Srgb),
+            [0.735, 0.0, 0.735, 1.0],
+        );
+    }
+
+    #[test]
+    fn rec2020_interpolation() {
+        let mut gradient = Gradient::linear(
+            Point::new(0.0, 0.0),
+            Point::new(10.0, 0.0),
+            vec![
+                ColorStop::new(0.0, Color::srgba(1.0, 0.0, 0.0, 1.0)),
+                ColorStop::new(1.0, Color::srgba(0.0, 0.0, 1.0, 1.0)),
+            ],
+        );
+        gradient.interpolation = ColorSpace::Rec2020;
+        let ramp = GradientRamp::new(&gradient);
+
+        // The ends are the stops themselves, and the middle is halfway between
+        // their Rec. 2020 encodings.
+        let red = Color::srgba(1.0, 0.0, 0.0, 1.0).in_color_space(ColorSpace::Rec2020);
+        let blue = Color::srgba(0.0, 0.0, 1.0, 1.0).in_color_space(ColorSpace::Rec2020);
+        let start = ramp.sample(Point::new(0.0, 0.0));
+        assert_eq!(start.space, ColorSpace::Rec2020);
+        assert_color(start, [red.r, red.g, red.b, 1.0]);
+        assert_color(
+            ramp.sample(Point::new(5.0, 0.0)),
+            [
+                (red.r + blue.r) / 2.0,
+                (red.g + blue.g) / 2.0,
+                (red.b + blue.b) / 2.0,
+                1.0,
+            ],
+        );
+        assert_color(
+            ramp.sample(Point::new(10.0, 0.0))
+                .in_color_space(ColorSpace::Srgb),
+            [0.0, 0.0, 1.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn stops() {
+        let red = Color::srgba(1.0, 0.0, 0.0, 1.0);
+        let clear = Color::srgba(0.0, 0.0, 0.0, 0.0);
+        let gradient = Gradient::linear(
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            vec![
+                ColorStop::new(0.5, red),
+                ColorStop::new(0.25, clear),
+                ColorStop::new(1.0, clear),
+            ],
+        );
+
+        // The second stop is moved up to the first, making a hard edge.
+        let ramp = GradientRamp::new(&gradient);
+        assert_color(ramp.sample(Point::new(0.2, 0.0)), [1.0, 0.0, 0.0, 1.0]);
+        assert_color(ramp.sample(Point::new(0.6, 0.0)), [0.0, 0.0, 0.0, 0.0]);
+
+        // Transparent stops don't darken their neighbours.
+        let gradient = Gradient::linear(
+            Point::new(0.0, 0.0),
+            Point::new(1.0, 0.0),
+            vec![ColorStop::new(0.0, red), ColorStop::new(1.0, clear)],
+        );
+        let ramp = GradientRamp::new(&gradient);
+        assert_color(ramp.sample(Point::new(0.5, 0.0)), [1.0, 0.0, 0.0, 0.5]);
+
+        let degenerate = Gradient::linear(
+            Point::new(1.0, 1.0),
+            Point::new(1.0, 1.0),
+            gradient.stops.clone(),
+        );
+        let ramp = GradientRamp::new(&degenerate);
+        assert_color(ramp.sample(Point::new(0.0, 0.0)), [0.0, 0.0, 0.0, 0.0]);
+
+        let empty = Gradient::linear(Point::new(0.0, 0.0), Point::new(1.0, 0.0), vec![]);
+        let ramp = GradientRamp::new(&empty);
+        assert_color(ramp.sample(Point::new(0.5, 0.0)), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn radial_and_conic() {
+        let stops = vec![
+            ColorStop::new(0.0, Color::srgba(0.0, 0.0, 0.0, 1.0)),
+            ColorStop::new(1.0, Color::srgba(1.0, 1.0, 1.0, 1.0)),
+        ];
+
+        let mut radial = Gradient::radial(Point::new(0.0, 0.0), 10.0, stops.clone());
+        let ramp = GradientRamp::new(&radial);
+        assert_color(ramp.sample(Point::new(0.0, 0.0)), [0.0, 0.0, 0.0, 1.0]);
+        assert_color(ramp.sample(Point::new(0.0, -5.0)), [0.5, 0.5, 0.5, 1.0]);
+        assert_color(ramp.sample(Point::new(20.0, 0.0)), [1.0, 1.0, 1.0, 1.0]);
+
+        radial.kind = GradientKind::Radial {
+            center: Point::new(0.0, 0.0),
+            radius: 10.0,
+            focus: Point::new(5.0, 0.0),
+        };
+        let ramp = GradientRamp::new(&radial);
+        assert_color(ramp.sample(Point::new(5.0, 0.0)), [0.0, 0.0, 0.0, 1.0]);
+        assert_color(ramp.sample(Point::new(7.5, 0.0)), [0.5, 0.5, 0.5, 1.0]);
+        assert_color(ramp.sample(Point::new(-2.5, 0.0)), [0.5, 0.5, 0.5, 1.0]);
+
+        let conic = Gradient::conic(Point::new(0.0, 0.0), 0.0, stops);
+        let ramp = GradientRamp::new(&conic);
+        assert_color(ramp.sample(Point::new(1.0, 0.0)), [0.0, 0.0, 0.0, 1.0]);
+        assert_color(ramp.sample(Point::new(0.0, 1.0)), [0.25, 0.25, 0.25, 1.0]);
+        assert_color(ramp.sample(Point::new(-1.0, 0.001)), [0.5, 0.5, 0.5, 1.0]);
+    }
+}
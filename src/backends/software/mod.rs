@@ -12,9 +12,13 @@ use crate::{
     paint::PaintConfig,
 };
 
-use self::canvas::SoftwareCanvas;
+use self::{canvas::SoftwareCanvas, gradient::GradientRamp};
 
 pub mod canvas;
+mod clip;
+mod gradient;
+mod patch_rasterizer;
+mod rasterizer;
 
 pub struct Software {
     shared: Rc<RefCell<BackendState>>,
@@ -25,6 +29,7 @@ impl Software {
         Software {
             shared: Rc::new(RefCell::new(BackendState {
                 paints: HashMap::with_hasher(PassThroughHasher::default()),
+                gradients: HashMap::with_hasher(PassThroughHasher::default()),
             })),
         }
     }
@@ -48,6 +53,15 @@ impl Software {
     }
 }
 
+impl Default for Software {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 pub(super) struct BackendState {
     paints: HashMap<u64, PaintConfig, PassThroughHasher>,
+    /// The lookup tables for every gradient used by a paint, by the hash of
+    /// the gradient.
+    gradients: HashMap<u64, Rc<GradientRamp>, PassThroughHasher>,
 }
@@ -0,0 +1,341 @@
+//! Hierarchical rasterization of CPatches, after "Hierarchical Rasterization
+//! of Curved Primitives for Vector Graphics Rendering on the GPU" by Dockter
+//! et al., 2019.
+//!
+//! Each patch is tested against the tile of pixels covering its bounds. Tiles
+//! that lie entirely inside or outside of the patch are resolved at once, and
+//! tiles straddling its edges are split in half and tested again, down to
+//! single pixels. The coverage of a pixel is found by integrating the height of
+//! the patch across it.
+//!
+//! This is a reference for validating the CPatch pipeline against the scanline
+//! rasterizer, and makes no attempt at being fast.
+
+use crate::{
+    backends::common::cpatch::{CPatch, CPatchList},
+    math::cmp::{max, min},
+    shapes::rect::Rect,
+};
+
+/// The number of points at which the height of a patch is sampled when
+/// integrating its coverage of a pixel. Patches are monotonic, so this only
+/// has to capture how much their edges curve within a single pixel.
+const SAMPLES_PER_PIXEL: usize = 4;
+
+/// Coverage values smaller than this are not visible in any supported pixel
+/// format, and are skipped.
+const MIN_COVERAGE: f32 = 1.0 / 1024.0;
+
+enum Coverage {
+    Empty,
+    Full,
+    Partial,
+}
+
+#[derive(Default)]
+pub struct PatchRasterizer {
+    /// The coverage of each pixel in the drawing area.
+    coverage: Vec<f32>,
+    /// The left edge of the drawing area in canvas coordinates.
+    left: u32,
+    /// The top edge of the drawing area in canvas coordinates.
+    top: u32,
+    /// The width of the drawing area in pixels.
+    width: u32,
+    /// The height of the drawing area in pixels.
+    height: u32,
+}
+
+impl PatchRasterizer {
+    /// Computes the coverage of every pixel covered by `patches` and passes it
+    /// to `f` in canvas coordinates, alongside the pixel's coverage in the
+    /// range `(0.0, 1.0]`. Patches outside of `area` are clipped.
+    ///
+    /// The area must not extend into negative coordinates.
+    pub fn fill<F>(&mut self, patches: &CPatchList, area: Rect, mut f: F)
+    where
+        F: FnMut(u32, u32, f32),
+    {
+        debug_assert!(area.left >= 0.0 && area.top >= 0.0);
+
+        self.left = area.left.floor() as u32;
+        self.top = area.top.floor() as u32;
+        self.width = area.right.ceil() as u32 - self.left;
+        self.height = area.bottom.ceil() as u32 - self.top;
+
+        self.coverage.clear();
+        self.coverage
+            .resize(self.width as usize * self.height as usize, 0.0);
+
+        for patch in patches {
+            self.add_patch(patch);
+        }
+
+        for (i, coverage) in self.coverage.iter().enumerate() {
+            // Patches never overlap, so this only exceeds 1 by rounding error.
+            let coverage = coverage.min(1.0);
+            if coverage >= MIN_COVERAGE {
+                f(
+                    self.left + i as u32 % self.width,
+                    self.top + i as u32 / self.width,
+                    coverage,
+                );
+            }
+        }
+    }
+
+    fn add_patch(&mut self, patch: &CPatch) {
+        let bounds = patch.bounds();
+        let left = max!(bounds.left.floor(), self.left as f32) as u32;
+        let top = max!(bounds.top.floor(), self.top as f32) as u32;
+        let right = min!(bounds.right.ceil(), (self.left + self.width) as f32) as u32;
+        let bottom = min!(bounds.bottom.ceil(), (self.top + self.height) as f32) as u32;
+
+        if left < right && top < bottom {
+            self.add_tile(patch, left, top, right, bottom);
+        }
+    }
+
+    /// Adds the coverage of `patch` within a tile of pixels, given in canvas
+    /// coordinates.
+    fn add_tile(&mut self, patch: &CPatch, left: u32, top: u32, right: u32, bottom: u32) {
+        let tile = Rect::new(left as f32, right as f32, top as f32, bottom as f32);
+
+        match classify(patch, tile) {
+            Coverage::Empty => {}
+            Coverage::Full => {
+                for y in top..bottom {
+                    let row = ((y - self.top) * self.width) as usize;
+                    for cell in &mut self.coverage
+                        [row + (left - self.left) as usize..row + (right - self.left) as usize]
+                    {
+                        *cell += 1.0;
+                    }
+                }
+            }
+            Coverage::Partial => {
+                if right - left > 1 && right - left >= bottom - top {
+                    let middle = left + (right - left) / 2;
+                    self.add_tile(patch, left, top, middle, bottom);
+                    self.add_tile(patch, middle, top, right, bottom);
+                } else if bottom - top > 1 {
+                    let middle = top + (bottom - top) / 2;
+                    self.add_tile(patch, left, top, right, middle);
+                    self.add_tile(patch, left, middle, right, bottom);
+                } else {
+                    let index = ((top - self.top) * self.width + (left - self.left)) as usize;
+                    self.coverage[index] += pixel_coverage(patch, tile);
+                }
+            }
+        }
+    }
+}
+
+/// Determines how much of `tile` is covered by the patch. Because the curves
+/// of a patch are monotonic, their extent across the tile is bounded by their
+/// values at its left and right edges.
+fn classify(patch: &CPatch, tile: Rect) -> Coverage {
+    let bounds = patch.bounds();
+    let left = max!(tile.left, bounds.left);
+    let right = min!(tile.right, bounds.right);
+    if left >= right {
+        return Coverage::Empty;
+    }
+
+    let (top_left, bottom_left) = patch.span_at(left);
+    let (top_right, bottom_right) = patch.span_at(right);
+
+    if tile.bottom <= min!(top_left, top_right) || tile.top >= max!(bottom_left, bottom_right) {
+        Coverage::Empty
+    } else if left == tile.left
+        && right == tile.right
+        && tile.top >= max!(top_left, top_right)
+        && tile.bottom <= min!(bottom_left, bottom_right)
+    {
+        Coverage::Full
+    } else {
+        Coverage::Partial
+    }
+}
+
+/// Integrates the height of the patch within `pixel` across its width.
+fn pixel_coverage(patch: &CPatch, pixel: Rect) -> f32 {
+    let bounds = patch.bounds();
+    let left = max!(pixel.left, bounds.left);
+    let right = min!(pixel.right, bounds.right);
+    if left >= right {
+        return 0.0;
+    }
+
+    let step = (right - left) / SAMPLES_PER_PIXEL as f32;
+    let mut height = 0.0;
+    for i in 0..SAMPLES_PER_PIXEL {
+        let (top, bottom) = patch.span_at(left + (i as f32 + 0.5) * step);
+        height += max!(min!(bottom, pixel.bottom) - max!(top, pixel.top), 0.0);
+    }
+    height * step
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backends::{common::cpatch::Builder as PatchBuilder, software::rasterizer::Rasterizer},
+        color::{Color, Space as ColorSpace},
+        image::PixelFormat,
+        paint::FillRule,
+        pixel_buffer::PixelBuffer,
+        shapes::{
+            path::{Builder as PathBuilder, Path},
+            point::Point,
+        },
+    };
+
+    use super::*;
+
+    const WIDTH: u32 = 64;
+    const HEIGHT: u32 = 64;
+
+    fn canvas() -> PixelBuffer {
+        PixelBuffer::new(WIDTH, HEIGHT, PixelFormat::Rgba8, ColorSpace::LinearSrgb).unwrap()
+    }
+
+    fn area() -> Rect {
+        Rect::new(0.0, WIDTH as f32, 0.0, HEIGHT as f32)
+    }
+
+    fn fill_patches(path: &Path, fill_rule: FillRule) -> PixelBuffer {
+        let mut patches = CPatchList::default();
+        PatchBuilder::default().build(path, fill_rule, &mut patches);
+
+        let mut pixels = canvas();
+        PatchRasterizer::default().fill(&patches, area(), |x, y, coverage| {
+            pixels.blend(
+                x,
+                y,
+                Color {
+                    a: coverage,
+                    ..Color::WHITE
+                },
+            );
+        });
+        pixels
+    }
+
+    fn fill_scanline(path: &Path, fill_rule: FillRule) -> PixelBuffer {
+        let mut rasterizer = Rasterizer::default();
+        rasterizer.begin(area());
+        rasterizer.add_path(path);
+
+        let mut pixels = canvas();
+        rasterizer.finish(fill_rule, |x, y, coverage| {
+            pixels.blend(
+                x,
+                y,
+                Color {
+                    a: coverage,
+                    ..Color::WHITE
+                },
+            );
+        });
+        pixels
+    }
+
+    fn polygon(builder: &mut PathBuilder, points: &[(f32, f32)]) {
+        builder.move_to(Point::new(points[0].0, points[0].1));
+        for (x, y) in &points[1..] {
+            builder.line_to(Point::new(*x, *y)).unwrap();
+        }
+        builder.close().unwrap();
+    }
+
+    fn circle(builder: &mut PathBuilder, (cx, cy): (f32, f32), r: f32) {
+        let k = 0.552_284_8 * r;
+        builder.move_to(Point::new(cx + r, cy));
+        for [p1, p2, p3] in [
+            [(cx + r, cy + k), (cx + k, cy + r), (cx, cy + r)],
+            [(cx - k, cy + r), (cx - r, cy + k), (cx - r, cy)],
+            [(cx - r, cy - k), (cx - k, cy - r), (cx, cy - r)],
+            [(cx + k, cy - r), (cx + r, cy - k), (cx + r, cy)],
+        ] {
+            let [p1, p2, p3] = [p1, p2, p3].map(|(x, y)| Point::new(x, y));
+            builder.add_cubic(p1, p2, p3).unwrap();
+        }
+        builder.close().unwrap();
+    }
+
+    #[test]
+    fn rectangle() {
+        let mut builder = PathBuilder::default();
+        polygon(
+            &mut builder,
+            &[(10.25, 5.5), (20.75, 5.5), (20.75, 8.0), (10.25, 8.0)],
+        );
+        let path = builder.build().unwrap();
+        let pixels = fill_patches(&path, FillRule::NonZero);
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (x0, y0) = (x as f32, y as f32);
+                let width = (x0 + 1.0).clamp(10.25, 20.75) - x0.clamp(10.25, 20.75);
+                let height = (y0 + 1.0).clamp(5.5, 8.0) - y0.clamp(5.5, 8.0);
+                let expected = width * height;
+                let alpha = pixels.get(x, y).a;
+                assert!(
+                    (alpha - expected).abs() <= 1.0 / 255.0,
+                    "({x}, {y}): {alpha} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_scanline() {
+        // Overlapping circles, a pentagram, and a square with a hole in it.
+        let mut builder = PathBuilder::default();
+        circle(&mut builder, (20.0, 20.0), 12.5);
+        circle(&mut builder, (32.0, 24.0), 12.5);
+        polygon(
+            &mut builder,
+            &[
+                (48.0, 32.0),
+                (57.4, 60.9),
+                (32.8, 43.1),
+                (63.2, 43.1),
+                (38.6, 60.9),
+            ],
+        );
+        polygon(
+            &mut builder,
+            &[(4.5, 40.5), (28.5, 40.5), (28.5, 60.5), (4.5, 60.5)],
+        );
+        polygon(
+            &mut builder,
+            &[(10.0, 45.0), (10.0, 55.0), (20.0, 55.0), (20.0, 45.0)],
+        );
+        let path = builder.build().unwrap();
+
+        for fill_rule in [FillRule::NonZero, FillRule::EvenOdd] {
+            let patches = fill_patches(&path, fill_rule);
+            let scanline = fill_scanline(&path, fill_rule);
+
+            // The scanline rasterizer flattens curves to within a tenth of a
+            // pixel, so individual pixels along them may differ by that much.
+            let mut filled = 0;
+            let mut total_error = 0.0;
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let (a, b) = (patches.get(x, y).a, scanline.get(x, y).a);
+                    assert!((a - b).abs() <= 0.15, "({x}, {y}): {a} != {b}");
+                    total_error += (a - b).abs();
+                    filled += usize::from(a == 1.0);
+                }
+            }
+
+            assert!(filled > 800);
+            assert!(
+                total_error / ((WIDTH * HEIGHT) as f32) < 0.005,
+                "{total_error}"
+            );
+        }
+    }
+}
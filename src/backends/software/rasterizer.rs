@@ -0,0 +1,517 @@
+//! Scanline rasterization with exact area coverage.
+//!
+//! Curves are flattened into line segments, and each line deposits the signed
+//! area it covers into an accumulation buffer. Summing the buffer left to right
+//! along a row then yields the winding-weighted coverage of every pixel in that
+//! row. This is the same approach used by font-rs and the FreeType 'gray'
+//! rasterizer.
+//!
+//! The accumulation buffer only spans the region being drawn to (usually the
+//! bounds of the path clipped to the canvas), so small paths on large canvases
+//! remain cheap to draw.
+
+use crate::{
+    math::cmp::ApproxEq,
+    paint::FillRule,
+    shapes::{
+        bezier::{Bezier, CubicSlice},
+        path::Path,
+        point::Point,
+        rect::Rect,
+    },
+};
+
+/// The maximum distance, in pixels, between a curve and the line segments used
+/// to approximate it.
+const TOLERANCE: f32 = 0.1;
+
+/// Coverage values smaller than this are not visible in any supported pixel
+/// format, and are skipped.
+const MIN_COVERAGE: f32 = 1.0 / 1024.0;
+
+/// Upper bound on the number of line segments a single curve is flattened
+/// into. This only matters for absurdly large curves.
+const MAX_SEGMENTS_PER_CURVE: usize = 1 << 12;
+
+#[derive(Default)]
+pub struct Rasterizer {
+    /// The signed area deltas for each cell in the drawing area. Each row has 2
+    /// extra cells so that lines on the right edge don't need special
+    /// treatment.
+    accumulation: Vec<f32>,
+    /// The left edge of the drawing area in canvas coordinates.
+    left: u32,
+    /// The top edge of the drawing area in canvas coordinates.
+    top: u32,
+    /// The width of the drawing area in pixels.
+    width: usize,
+    /// The height of the drawing area in pixels.
+    height: usize,
+}
+
+impl Rasterizer {
+    /// Prepares the rasterizer to draw into the pixels touched by `area`,
+    /// discarding the results of any previous drawing. Geometry outside of the
+    /// area is clipped.
+    ///
+    /// The area must not extend into negative coordinates.
+    pub fn begin(&mut self, area: Rect) {
+        debug_assert!(area.left >= 0.0 && area.top >= 0.0);
+
+        let left = area.left.floor().max(0.0);
+        let top = area.top.floor().max(0.0);
+        let right = area.right.ceil().max(left);
+        let bottom = area.bottom.ceil().max(top);
+
+        self.left = left as u32;
+        self.top = top as u32;
+        self.width = (right - left) as usize;
+        self.height = (bottom - top) as usize;
+
+        // `finish()` zeroes the buffer as it reads it, but the previous drawing
+        // may have been abandoned without calling it, so the whole buffer is
+        // cleared regardless.
+        let len = (self.width + 2) * self.height;
+        self.accumulation.clear();
+        self.accumulation.resize(len, 0.0);
+    }
+
+    /// Adds every curve in the path to the rasterizer.
+    pub fn add_path(&mut self, path: &Path) {
+        for segment in path.iter() {
+            for curve in segment {
+                self.add_cubic(curve);
+            }
+        }
+    }
+
+    /// Flattens the curve into line segments and adds them to the rasterizer.
+    pub fn add_cubic(&mut self, curve: CubicSlice) {
+        // Wang's formula gives the number of uniform subdivisions needed to
+        // keep the flattened curve within `TOLERANCE` of the real curve.
+        let dd = {
+            let ddx0 = curve.x[0] - 2.0 * curve.x[1] + curve.x[2];
+            let ddy0 = curve.y[0] - 2.0 * curve.y[1] + curve.y[2];
+            let ddx1 = curve.x[1] - 2.0 * curve.x[2] + curve.x[3];
+            let ddy1 = curve.y[1] - 2.0 * curve.y[2] + curve.y[3];
+            (ddx0 * ddx0 + ddy0 * ddy0)
+                .max(ddx1 * ddx1 + ddy1 * ddy1)
+                .sqrt()
+        };
+
+        let num_segments =
+            ((0.75 * dd / TOLERANCE).sqrt().ceil() as usize).clamp(1, MAX_SEGMENTS_PER_CURVE);
+
+        let mut previous = curve.p0();
+        for i in 1..num_segments {
+            let point = curve.at(i as f32 / num_segments as f32);
+            self.add_line(previous, point);
+            previous = point;
+        }
+        self.add_line(previous, curve.p3());
+    }
+
+    /// Adds a single line segment to the rasterizer.
+    pub fn add_line(&mut self, p0: Point, p1: Point) {
+        let p0 = Point::new(p0.x - self.left as f32, p0.y - self.top as f32);
+        let p1 = Point::new(p1.x - self.left as f32, p1.y - self.top as f32);
+
+        if p0.y.approx_eq_within(&p1.y, f32::EPSILON) {
+            // Horizontal lines don't contribute any area.
+            return;
+        }
+
+        // Split the line where it crosses the left and right edges of the
+        // drawing area. The parts that lie outside are projected onto the edge
+        // so that they still contribute to the winding of the pixels within.
+        let right = self.width as f32;
+        let mut splits = [0.0, 1.0, 1.0, 1.0];
+        let mut num_splits = 1;
+        for edge in [0.0, right] {
+            if (p0.x - edge) * (p1.x - edge) < 0.0 {
+                splits[num_splits] = (edge - p0.x) / (p1.x - p0.x);
+                num_splits += 1;
+            }
+        }
+        splits[1..num_splits].sort_by(f32::total_cmp);
+        splits[num_splits] = 1.0;
+
+        let point_at = |t: f32| {
+            Point::new(
+                (p0.x + t * (p1.x - p0.x)).clamp(0.0, right),
+                p0.y + t * (p1.y - p0.y),
+            )
+        };
+
+        for window in splits[..=num_splits].windows(2) {
+            self.accumulate_line(point_at(window[0]), point_at(window[1]));
+        }
+    }
+
+    /// Computes the coverage of every pixel within the drawing area according
+    /// to the fill rule and passes it to `f` in canvas coordinates, alongside
+    /// the pixel's coverage in the range `(0.0, 1.0]`. Pixels without coverage
+    /// are skipped.
+    ///
+    /// The rasterizer is empty after this call.
+    pub fn finish<F>(&mut self, fill_rule: FillRule, mut f: F)
+    where
+        F: FnMut(u32, u32, f32),
+    {
+        let stride = self.width + 2;
+
+        for (y, row) in self.accumulation.chunks_exact_mut(stride).enumerate() {
+            let mut accumulator = 0.0;
+
+            for (x, cell) in row[..self.width].iter_mut().enumerate() {
+                accumulator += *cell;
+                *cell = 0.0;
+
+                let coverage = apply_fill_rule(fill_rule, accumulator);
+                if coverage >= MIN_COVERAGE {
+                    f(self.left + x as u32, self.top + y as u32, coverage);
+                }
+            }
+
+            row[self.width..].fill(0.0);
+        }
+    }
+
+    /// Deposits the signed area of the line into the accumulation buffer. Both
+    /// points must lie within `0.0..=self.width` on the x-axis.
+    fn accumulate_line(&mut self, p0: Point, p1: Point) {
+        if p0.y == p1.y {
+            return;
+        }
+
+        let stride = self.width + 2;
+        let (direction, p0, p1) = if p0.y < p1.y {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let mut x = p0.x;
+        if p0.y < 0.0 {
+            x -= p0.y * dxdy;
+        }
+
+        // Negative values saturate to 0 when cast.
+        let first_row = p0.y as usize;
+        let last_row = self.height.min(p1.y.ceil() as usize);
+
+        for y in first_row..last_row {
+            let row = &mut self.accumulation[y * stride..(y + 1) * stride];
+
+            let dy = ((y + 1) as f32).min(p1.y) - (y as f32).max(p0.y);
+            let x_next = x + dxdy * dy;
+            let d = dy * direction;
+
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as usize;
+
+            if x1i <= x0i + 1 {
+                // The line is contained within a single pixel on this row.
+                let xmf = 0.5 * (x + x_next) - x0_floor;
+                row[x0i] += d - d * xmf;
+                row[x0i + 1] += d * xmf;
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+
+                row[x0i] += d * a0;
+
+                if x1i == x0i + 2 {
+                    row[x0i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    row[x0i + 1] += d * (a1 - a0);
+
+                    for cell in &mut row[x0i + 2..x1i - 1] {
+                        *cell += d * s;
+                    }
+
+                    let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+                    row[x1i - 1] += d * (1.0 - a2 - am);
+                }
+
+                row[x1i] += d * am;
+            }
+
+            x = x_next;
+        }
+    }
+}
+
+/// Converts the winding-weighted coverage of a pixel into the proportion of
+/// the pixel that is filled. This is exact for pixels crossed by a single edge,
+/// and a close approximation otherwise.
+fn apply_fill_rule(fill_rule: FillRule, accumulated: f32) -> f32 {
+    match fill_rule {
+        FillRule::NonZero => accumulated.abs().min(1.0),
+        FillRule::EvenOdd => {
+            // Fold the coverage so that a winding of 2 is empty, 3 is full,
+            // etc.
+            let coverage = accumulated.abs() % 2.0;
+            if coverage > 1.0 {
+                2.0 - coverage
+            } else {
+                coverage
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shapes::path::Builder as PathBuilder;
+
+    use super::*;
+
+    fn rasterize(path: &Path, area: Rect) -> Vec<(u32, u32, f32)> {
+        rasterize_with(path, area, FillRule::NonZero)
+    }
+
+    fn rasterize_with(path: &Path, area: Rect, fill_rule: FillRule) -> Vec<(u32, u32, f32)> {
+        let mut rasterizer = Rasterizer::default();
+        rasterizer.begin(area);
+        rasterizer.add_path(path);
+
+        let mut out = vec![];
+        rasterizer.finish(fill_rule, |x, y, coverage| out.push((x, y, coverage)));
+        out
+    }
+
+    /// Reads the paths in tiger.svg, offset to keep them clear of the edges of
+    /// the canvas.
+    fn tiger_paths() -> Vec<Path> {
+        let data = include_str!("../../../test_files/tiger.svg");
+        let document = roxmltree::Document::parse(data).unwrap();
+
+        document
+            .descendants()
+            .filter(|node| node.has_tag_name("path"))
+            .map(|node| {
+                let mut path = Path::from_svg_path_data(node.attribute("d").unwrap()).unwrap();
+                for x in &mut path.x {
+                    *x += 200.0;
+                }
+                for y in &mut path.y {
+                    *y += 200.0;
+                }
+                path
+            })
+            .collect()
+    }
+
+    fn polygon(points: &[(f32, f32)]) -> Path {
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(points[0].0, points[0].1));
+        for (x, y) in &points[1..] {
+            builder.line_to(Point::new(*x, *y)).unwrap();
+        }
+        builder.close().unwrap();
+        builder.build().unwrap()
+    }
+
+    fn coverage_at(pixels: &[(u32, u32, f32)], x: u32, y: u32) -> f32 {
+        pixels
+            .iter()
+            .find(|p| p.0 == x && p.1 == y)
+            .map_or(0.0, |p| p.2)
+    }
+
+    #[test]
+    fn square() {
+        let square = polygon(&[(2.5, 2.5), (6.5, 2.5), (6.5, 6.5), (2.5, 6.5)]);
+        let pixels = rasterize(&square, Rect::new(0.0, 10.0, 0.0, 10.0));
+
+        let total: f32 = pixels.iter().map(|p| p.2).sum();
+        assert!(total.approx_eq_within(&16.0, 1e-4));
+
+        assert!(coverage_at(&pixels, 4, 4).approx_eq(&1.0));
+        assert!(coverage_at(&pixels, 2, 4).approx_eq(&0.5));
+        assert!(coverage_at(&pixels, 6, 4).approx_eq(&0.5));
+        assert!(coverage_at(&pixels, 4, 2).approx_eq(&0.5));
+        assert!(coverage_at(&pixels, 2, 2).approx_eq(&0.25));
+        assert!(coverage_at(&pixels, 7, 4).approx_eq(&0.0));
+    }
+
+    #[test]
+    fn orientation() {
+        let clockwise = polygon(&[(1.0, 1.0), (8.3, 2.0), (5.0, 7.7)]);
+        let counter_clockwise = polygon(&[(1.0, 1.0), (5.0, 7.7), (8.3, 2.0)]);
+
+        let area = Rect::new(0.0, 10.0, 0.0, 10.0);
+        let a = rasterize(&clockwise, area);
+        let b = rasterize(&counter_clockwise, area);
+
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!((a.0, a.1), (b.0, b.1));
+            assert!(a.2.approx_eq_within(&b.2, 1e-4));
+        }
+    }
+
+    #[test]
+    fn circle() {
+        // A circle approximated by 4 cubic curves.
+        const K: f32 = 0.552_284_8;
+        let (cx, cy, r) = (25.0, 25.0, 20.0);
+
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(cx + r, cy));
+        builder
+            .add_cubic(
+                Point::new(cx + r, cy + r * K),
+                Point::new(cx + r * K, cy + r),
+                Point::new(cx, cy + r),
+            )
+            .unwrap();
+        builder
+            .add_cubic(
+                Point::new(cx - r * K, cy + r),
+                Point::new(cx - r, cy + r * K),
+                Point::new(cx - r, cy),
+            )
+            .unwrap();
+        builder
+            .add_cubic(
+                Point::new(cx - r, cy - r * K),
+                Point::new(cx - r * K, cy - r),
+                Point::new(cx, cy - r),
+            )
+            .unwrap();
+        builder
+            .add_cubic(
+                Point::new(cx + r * K, cy - r),
+                Point::new(cx + r, cy - r * K),
+                Point::new(cx + r, cy),
+            )
+            .unwrap();
+        builder.close().unwrap();
+        let circle = builder.build().unwrap();
+
+        let pixels = rasterize(&circle, Rect::new(0.0, 50.0, 0.0, 50.0));
+        let total: f32 = pixels.iter().map(|p| p.2).sum();
+        let expected = std::f32::consts::PI * r * r;
+        // Flattening the curve into chords loses a little bit of area.
+        assert!((total - expected).abs() / expected < 0.01);
+
+        // Anti-aliased edges.
+        assert!(pixels.iter().any(|p| p.2 > 0.1 && p.2 < 0.9));
+    }
+
+    #[test]
+    fn clipped() {
+        // A square that extends beyond every edge of the drawing area.
+        let square = polygon(&[(-5.0, -5.0), (15.0, -5.0), (15.0, 15.0), (-5.0, 15.0)]);
+        let pixels = rasterize(&square, Rect::new(2.0, 8.0, 3.0, 6.0));
+
+        assert_eq!(pixels.len(), 6 * 3);
+        assert!(pixels.iter().all(|p| p.2.approx_eq(&1.0)));
+        assert!(pixels
+            .iter()
+            .all(|p| (2..8).contains(&p.0) && (3..6).contains(&p.1)));
+
+        // A triangle crossing the left edge of the drawing area.
+        let triangle = polygon(&[(-4.0, 0.0), (4.0, 0.0), (-4.0, 8.0)]);
+        let pixels = rasterize(&triangle, Rect::new(0.0, 10.0, 0.0, 10.0));
+        let total: f32 = pixels.iter().map(|p| p.2).sum();
+        assert!(total.approx_eq_within(&8.0, 1e-4));
+    }
+
+    #[test]
+    fn star_fill_rules() {
+        // A five-pointed star drawn with a single self-intersecting polygon.
+        // The pentagon in its center has a winding number of 2.
+        let star: Vec<(f32, f32)> = (0..5)
+            .map(|i| {
+                let angle = (i * 2) as f32 * std::f32::consts::TAU / 5.0;
+                (20.0 + 18.0 * angle.sin(), 20.0 - 18.0 * angle.cos())
+            })
+            .collect();
+        let star = polygon(&star);
+        let area = Rect::new(0.0, 40.0, 0.0, 40.0);
+
+        let non_zero = rasterize_with(&star, area, FillRule::NonZero);
+        let even_odd = rasterize_with(&star, area, FillRule::EvenOdd);
+
+        // The center is only filled with the non-zero rule.
+        assert!(coverage_at(&non_zero, 20, 20).approx_eq_within(&1.0, 1e-3));
+        assert!(coverage_at(&even_odd, 20, 20).approx_eq_within(&0.0, 1e-3));
+
+        // The points of the star are filled with both rules.
+        assert!(coverage_at(&non_zero, 20, 5).approx_eq_within(&1.0, 1e-3));
+        assert!(coverage_at(&even_odd, 20, 5).approx_eq_within(&1.0, 1e-3));
+
+        let non_zero_total: f32 = non_zero.iter().map(|p| p.2).sum();
+        let even_odd_total: f32 = even_odd.iter().map(|p| p.2).sum();
+        assert!(even_odd_total < non_zero_total);
+    }
+
+    #[test]
+    fn overlapping_subpaths() {
+        // Two overlapping squares with the same orientation.
+        let mut builder = PathBuilder::default();
+        for offset in [0.0, 4.0] {
+            builder.move_to(Point::new(offset, offset));
+            builder.line_to(Point::new(offset + 8.0, offset)).unwrap();
+            builder
+                .line_to(Point::new(offset + 8.0, offset + 8.0))
+                .unwrap();
+            builder.line_to(Point::new(offset, offset + 8.0)).unwrap();
+            builder.close().unwrap();
+        }
+        let squares = builder.build().unwrap();
+        let area = Rect::new(0.0, 16.0, 0.0, 16.0);
+
+        let non_zero = rasterize_with(&squares, area, FillRule::NonZero);
+        let even_odd = rasterize_with(&squares, area, FillRule::EvenOdd);
+
+        let non_zero_total: f32 = non_zero.iter().map(|p| p.2).sum();
+        let even_odd_total: f32 = even_odd.iter().map(|p| p.2).sum();
+        assert!(non_zero_total.approx_eq_within(&(64.0 + 64.0 - 16.0), 1e-3));
+        assert!(even_odd_total.approx_eq_within(&(64.0 + 64.0 - 32.0), 1e-3));
+        assert!(coverage_at(&even_odd, 6, 6).approx_eq(&0.0));
+    }
+
+    #[test]
+    fn tiger_fill_rules() {
+        let paths = tiger_paths();
+        assert!(!paths.is_empty());
+
+        let area = Rect::new(0.0, 500.0, 0.0, 500.0);
+        let mut non_zero = vec![0.0; 500 * 500];
+        let mut num_differing_paths = 0;
+
+        for path in &paths {
+            non_zero.fill(0.0);
+            for (x, y, coverage) in rasterize_with(path, area, FillRule::NonZero) {
+                non_zero[(y * 500 + x) as usize] = coverage;
+            }
+
+            let mut differs = false;
+            for (x, y, coverage) in rasterize_with(path, area, FillRule::EvenOdd) {
+                // Everything filled by even-odd is also filled by non-zero.
+                let expected = non_zero[(y * 500 + x) as usize];
+                assert!(coverage <= expected + 1e-4);
+                differs |= coverage + 1e-4 < expected;
+            }
+
+            if differs {
+                num_differing_paths += 1;
+            }
+        }
+
+        // Some paths in the tiger overlap themselves.
+        assert!(num_differing_paths > 0);
+    }
+}
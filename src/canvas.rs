@@ -1,14 +1,39 @@
+use std::ops::{Deref, DerefMut};
+
 use crate::{
     color::Color,
-    paint::{Paint, PaintConfig},
+    math::{affine::Affine, vector2::Vec2},
+    paint::{BlendMode, FillRule, Paint, PaintConfig},
     pixel_buffer::PixelBuffer,
-    shapes::path::{Builder as PathBuilder, Path},
+    shapes::{
+        path::{Builder as PathBuilder, Path},
+        point::Point,
+        rect::Rect,
+    },
 };
 
+#[derive(Default)]
 pub struct CanvasOptions {
     /// Set to enable randomization of the color used for every draw command,
     /// overriding the paint passed to the canvas.
     pub debug_randomize_color: bool,
+    /// The method used to fill paths, for backends that support more than one.
+    pub fill_method: FillMethod,
+    /// Set to keep a copy of every shape drawn so that it can be found by
+    /// `hit_test()`.
+    pub record_shapes: bool,
+}
+
+/// The algorithm used to fill paths.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FillMethod {
+    /// Scanline rasterization with exact area coverage.
+    #[default]
+    Scanline,
+    /// Conversion into CPatches, which are then rasterized hierarchically. On
+    /// the CPU, this is much slower than scanline rasterization, and serves as
+    /// a reference for the GPU implementation.
+    CPatch,
 }
 
 /// A 2D drawing context.
@@ -23,12 +48,19 @@ pub trait Canvas: CanvasOps {
     /// are complete.
     ///
     /// If the canvas is drawn to at a later time, the returned buffer will not
-    /// update, and another will have to be retrieved from the canvas.
+    /// update, and another will have to be retrieved from the canvas. Layers
+    /// that have not been popped yet are not included.
     fn get_pixels(&self) -> PixelBuffer;
 }
 
 /// Operations for rendering shapes to a render target, and querying its
 /// properties.
+///
+/// Canvases keep an implicit stack of drawing state, which consists of the
+/// transform from path coordinates to pixels and the clip, which limits the
+/// area that can be drawn to. `save()` pushes a copy of
+/// the current state onto the stack, and `restore()` pops it off again. See
+/// [`ScopedCanvas`] for a guard that does this automatically.
 pub trait CanvasOps {
     /// The width of the drawable area.
     fn width(&self) -> u32;
@@ -37,6 +69,9 @@ pub trait CanvasOps {
     fn height(&self) -> u32;
 
     /// Clears the area's contents to the given color.
+    ///
+    /// Within a layer, only the layer is cleared: pixels outside of the current
+    /// clip are left alone, as are shapes drawn before the layer was pushed.
     fn clear(&mut self, color: Color);
 
     /// Creates a new, immutable paint object, and returns a reference to it.
@@ -69,4 +104,135 @@ pub trait CanvasOps {
     /// with the painter's algorithm (back-to-front), so paths drawn first will
     /// be hidden by paths drawn over them.
     fn stroke_path(&mut self, path: &Path, paint: Paint);
+
+    /// Finds the topmost shape drawn since the canvas was last cleared that
+    /// covers `point`, returning its position in drawing order. Filling or
+    /// stroking a path draws one shape, and counts towards the position even if
+    /// it was clipped away or drawn entirely off of the canvas.
+    ///
+    /// The point is given in pixels, and is not affected by the current
+    /// transform. Shapes are only recorded if the canvas was created with
+    /// `record_shapes` set, so this always returns `None` otherwise.
+    fn hit_test(&self, point: Point) -> Option<usize>;
+
+    /// Pushes a copy of the current drawing state onto the state stack.
+    fn save(&mut self);
+
+    /// Replaces the current drawing state with the one most recently saved.
+    /// Does nothing if there is no saved state, or if it was saved before the
+    /// innermost layer was pushed, since only popping the layer restores it.
+    fn restore(&mut self);
+
+    /// The transform applied to paths before they are drawn.
+    fn transform(&self) -> Affine;
+
+    /// Replaces the current transform.
+    fn set_transform(&mut self, transform: Affine);
+
+    /// Moves the origin of subsequent drawing commands by `offset`.
+    fn translate(&mut self, offset: Vec2) {
+        self.set_transform(self.transform() * Affine::translate(offset));
+    }
+
+    /// Scales subsequent drawing commands about the origin.
+    fn scale(&mut self, x: f32, y: f32) {
+        self.set_transform(self.transform() * Affine::scale(x, y));
+    }
+
+    /// Rotates subsequent drawing commands by `angle` radians about the
+    /// origin.
+    fn rotate(&mut self, angle: f32) {
+        self.set_transform(self.transform() * Affine::rotate(angle));
+    }
+
+    /// Limits subsequent drawing commands to the area inside of `path`, as
+    /// well as the current clip. The path is transformed by the current
+    /// transform, and the clip is only removed when the state is restored.
+    fn clip_path(&mut self, path: &Path, fill_rule: FillRule);
+
+    /// Limits subsequent drawing commands to the area inside of `rect`, as
+    /// well as the current clip. See [`CanvasOps::clip_path`].
+    fn clip_rect(&mut self, rect: Rect) {
+        let mut builder = PathBuilder::default();
+        builder.move_to(Point::new(rect.left, rect.top));
+        for (x, y) in [
+            (rect.right, rect.top),
+            (rect.right, rect.bottom),
+            (rect.left, rect.bottom),
+        ] {
+            builder.line_to(Point::new(x, y)).unwrap();
+        }
+        builder.close().unwrap();
+        self.clip_path(&builder.build().unwrap(), FillRule::NonZero);
+    }
+
+    /// Begins a layer, which redirects subsequent drawing commands to a
+    /// transparent offscreen target until the matching `pop_layer()`. The
+    /// layer is then composited onto the canvas as a single unit, with its
+    /// alpha scaled by `opacity` and its colors combined using `blend_mode`.
+    ///
+    /// The drawing state is saved along with the layer and restored when it
+    /// is popped. If `clip` is given, it is intersected with the current clip
+    /// for the duration of the layer.
+    fn push_layer(&mut self, opacity: f32, blend_mode: BlendMode, clip: Option<(&Path, FillRule)>);
+
+    /// Composites the most recently pushed layer onto the layer or canvas
+    /// beneath it, and restores the drawing state that was current when the
+    /// layer was pushed. Does nothing if there are no layers.
+    fn pop_layer(&mut self);
+
+    /// Saves the current drawing state, returning a guard that restores it
+    /// when dropped.
+    fn scoped(&mut self) -> ScopedCanvas<'_, Self>
+    where
+        Self: Sized,
+    {
+        ScopedCanvas::new(self)
+    }
+}
+
+/// A canvas with an explicit scope for changes to its drawing state. The state
+/// is saved when the guard is created, and restored when it is dropped.
+///
+/// ```rust
+/// # use shiny::{canvas::{CanvasOps, ScopedCanvas}, math::vector2::Vec2};
+/// fn draw(canvas: &mut impl CanvasOps) {
+///     let before = canvas.transform();
+///     {
+///         let mut scope = ScopedCanvas::new(canvas);
+///         scope.translate(Vec2::new(4.0, 4.0));
+///         // Draws here have their origin at (4, 4).
+///     }
+///     assert_eq!(canvas.transform(), before);
+/// }
+/// ```
+pub struct ScopedCanvas<'a, C: CanvasOps + ?Sized> {
+    canvas: &'a mut C,
+}
+
+impl<'a, C: CanvasOps + ?Sized> ScopedCanvas<'a, C> {
+    pub fn new(canvas: &'a mut C) -> Self {
+        canvas.save();
+        Self { canvas }
+    }
+}
+
+impl<'a, C: CanvasOps + ?Sized> Deref for ScopedCanvas<'a, C> {
+    type Target = C;
+
+    fn deref(&self) -> &Self::Target {
+        self.canvas
+    }
+}
+
+impl<'a, C: CanvasOps + ?Sized> DerefMut for ScopedCanvas<'a, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.canvas
+    }
+}
+
+impl<'a, C: CanvasOps + ?Sized> Drop for ScopedCanvas<'a, C> {
+    fn drop(&mut self) {
+        self.canvas.restore();
+    }
 }
@@ -210,7 +210,9 @@ impl Color {
                         space: Space::LinearSrgb,
                     }
                 }
-                Space::Rec2020 => todo!(),
+                Space::Rec2020 => self
+                    .in_color_space(Space::LinearSrgb)
+                    .in_color_space(Space::Rec2020),
             },
             Space::LinearSrgb => match target {
                 Space::Unknown => self.as_unknown(),
@@ -218,19 +220,19 @@ impl Color {
                     let r = if self.r <= 0.0031308 {
                         self.r * 12.92
                     } else {
-                        (1.055 * self.r.powf(1.0 / 2.4) - 0.055).max(0.0).min(1.0)
+                        (1.055 * self.r.powf(1.0 / 2.4) - 0.055).clamp(0.0, 1.0)
                     };
 
                     let g = if self.g <= 0.0031308 {
                         self.g * 12.92
                     } else {
-                        (1.055 * self.g.powf(1.0 / 2.4) - 0.055).max(0.0).min(1.0)
+                        (1.055 * self.g.powf(1.0 / 2.4) - 0.055).clamp(0.0, 1.0)
                     };
 
                     let b = if self.b <= 0.0031308 {
                         self.b * 12.92
                     } else {
-                        (1.055 * self.b.powf(1.0 / 2.4) - 0.055).max(0.0).min(1.0)
+                        (1.055 * self.b.powf(1.0 / 2.4) - 0.055).clamp(0.0, 1.0)
                     };
 
                     Color {
@@ -242,12 +244,37 @@ impl Color {
                     }
                 }
                 Space::LinearSrgb => *self,
-                Space::Rec2020 => todo!(),
+                Space::Rec2020 => {
+                    let [r, g, b] = mul(&LINEAR_SRGB_TO_LINEAR_REC2020, [self.r, self.g, self.b]);
+                    Color {
+                        r: rec2020_encode(r),
+                        g: rec2020_encode(g),
+                        b: rec2020_encode(b),
+                        a: self.a,
+                        space: Space::Rec2020,
+                    }
+                }
             },
             Space::Rec2020 => match target {
                 Space::Unknown => self.as_unknown(),
-                Space::Srgb => todo!(),
-                Space::LinearSrgb => todo!(),
+                Space::Srgb => self
+                    .in_color_space(Space::LinearSrgb)
+                    .in_color_space(Space::Srgb),
+                Space::LinearSrgb => {
+                    let linear = [
+                        rec2020_decode(self.r),
+                        rec2020_decode(self.g),
+                        rec2020_decode(self.b),
+                    ];
+                    let [r, g, b] = mul(&LINEAR_REC2020_TO_LINEAR_SRGB, linear);
+                    Color {
+                        r,
+                        g,
+                        b,
+                        a: self.a,
+                        space: Space::LinearSrgb,
+                    }
+                }
                 Space::Rec2020 => *self,
             },
         }
@@ -305,6 +332,47 @@ impl Distribution<Color> for Standard {
     }
 }
 
+/// Converts linear sRGB to linear Rec. 2020. Both share the D65 white point, so
+/// only the primaries differ.
+const LINEAR_SRGB_TO_LINEAR_REC2020: [[f32; 3]; 3] = [
+    [0.627_404, 0.329_282, 0.043_314],
+    [0.069_097, 0.919_540, 0.011_361],
+    [0.016_392, 0.088_013, 0.895_595],
+];
+
+/// The inverse of `LINEAR_SRGB_TO_LINEAR_REC2020`.
+const LINEAR_REC2020_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [1.660_491, -0.587_641, -0.072_850],
+    [-0.124_550, 1.132_9, -0.008_349],
+    [-0.018_151, -0.100_579, 1.118_73],
+];
+
+/// The constants of the Rec. 2020 transfer function.
+const REC2020_ALPHA: f32 = 1.099_296_8;
+const REC2020_BETA: f32 = 0.018_053_97;
+
+fn mul(m: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// Applies the Rec. 2020 transfer function to a linear channel value.
+fn rec2020_encode(v: f32) -> f32 {
+    if v < REC2020_BETA {
+        v * 4.5
+    } else {
+        REC2020_ALPHA * v.powf(0.45) - (REC2020_ALPHA - 1.0)
+    }
+}
+
+/// Inverts the Rec. 2020 transfer function, producing a linear channel value.
+fn rec2020_decode(v: f32) -> f32 {
+    if v < REC2020_BETA * 4.5 {
+        v / 4.5
+    } else {
+        ((v + (REC2020_ALPHA - 1.0)) / REC2020_ALPHA).powf(1.0 / 0.45)
+    }
+}
+
 /// A color space describes the relationship between colors as represented by
 /// [`Color`] and what is percieved by the human eye.
 ///
@@ -314,7 +382,7 @@ impl Distribution<Color> for Standard {
 /// 1.0, 1.0)` determines just what 'white' means within the color space. A
 /// color such as `(0.5, 1.0, 0.3)` may produce produce different colors
 /// depending on the color space used.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub enum Space {
     Unknown,
     /// The sRGB color space, which is the most commonly used color space today.
@@ -338,6 +406,15 @@ impl Space {
         matches!(self, Space::LinearSrgb)
     }
 
+    /// The linear color space with the same primaries and white point, if it
+    /// is supported.
+    pub fn linear(&self) -> Option<Space> {
+        match self {
+            Space::Srgb | Space::LinearSrgb => Some(Space::LinearSrgb),
+            Space::Unknown | Space::Rec2020 => None,
+        }
+    }
+
     /// Queries the minimum number of bits per channel required to represent the
     /// color space.
     ///
@@ -351,3 +428,41 @@ impl Space {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Color, b: Color) {
+        assert!(
+            (a.r - b.r).abs() < 1e-4 && (a.g - b.g).abs() < 1e-4 && (a.b - b.b).abs() < 1e-4,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn rec2020() {
+        // White and black are the same in every space.
+        let white = Color::srgba(1.0, 1.0, 1.0, 1.0).in_color_space(Space::Rec2020);
+        assert_close(white, Color::srgba(1.0, 1.0, 1.0, 1.0));
+        let black = Color::srgba(0.0, 0.0, 0.0, 1.0).in_color_space(Space::Rec2020);
+        assert_close(black, Color::srgba(0.0, 0.0, 0.0, 1.0));
+
+        // sRGB's red is inside of Rec. 2020's gamut, so it isn't saturated.
+        let red = Color::srgba(1.0, 0.0, 0.0, 0.5).in_color_space(Space::Rec2020);
+        assert!(red.r < 1.0 && red.g > 0.0 && red.b > 0.0);
+        assert_eq!(red.a, 0.5);
+
+        for color in [
+            Color::srgba(0.2, 0.5, 0.8, 1.0),
+            Color::srgba(1.0, 0.0, 0.0, 1.0),
+            Color::srgba(0.01, 0.02, 0.03, 1.0),
+        ] {
+            let linear = color.in_color_space(Space::LinearSrgb);
+            let rec2020 = color.in_color_space(Space::Rec2020);
+            assert_close(rec2020.in_color_space(Space::Srgb), color);
+            assert_close(rec2020.in_color_space(Space::LinearSrgb), linear);
+            assert_close(linear.in_color_space(Space::Rec2020), rec2020);
+        }
+    }
+}
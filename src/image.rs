@@ -25,6 +25,10 @@ pub enum PixelFormat {
     /// 4-component RGBA with 10-bit unsigned normalized integer components, and
     /// 2-bit alpha.
     Rgb10a2,
+
+    /// 4-component RGBA with 16-bit unsigned normalized integer components,
+    /// stored little-endian.
+    Rgba16,
 }
 
 impl PixelFormat {
@@ -34,6 +38,7 @@ impl PixelFormat {
         match self {
             PixelFormat::Rgba8 => 4,
             PixelFormat::Rgb10a2 => 4,
+            PixelFormat::Rgba16 => 8,
         }
     }
 
@@ -47,6 +52,7 @@ impl PixelFormat {
         match self {
             PixelFormat::Rgba8 => 8,
             PixelFormat::Rgb10a2 => 10,
+            PixelFormat::Rgba16 => 16,
         }
     }
 
@@ -74,6 +80,12 @@ impl PixelFormat {
                 let a = (v & 0x3) as f32 / 3.0;
                 Color::unknown(r, g, b, a)
             }
+            PixelFormat::Rgba16 => {
+                let channel = |i: usize| {
+                    u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]) as f32 / 65535.0
+                };
+                Color::unknown(channel(0), channel(1), channel(2), channel(3))
+            }
         }
     }
 
@@ -95,6 +107,12 @@ impl PixelFormat {
                 let v = (r << 22) | (g << 12) | (b << 2) | a;
                 dest[0..4].copy_from_slice(&v.to_le_bytes());
             }
+            PixelFormat::Rgba16 => {
+                for (i, c) in [color.r, color.g, color.b, color.a].into_iter().enumerate() {
+                    let v = (c.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+                    dest[2 * i..2 * i + 2].copy_from_slice(&v.to_le_bytes());
+                }
+            }
         }
     }
 }
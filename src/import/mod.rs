@@ -0,0 +1,4 @@
+//! Readers for documents in other vector graphics formats.
+
+#[cfg(feature = "import-svg")]
+pub mod svg;
@@ -0,0 +1,1281 @@
+//! Reads SVG documents into lists of draw commands.
+//!
+//! Only the static subset of SVG that maps directly onto the canvas is
+//! supported: paths, the basic shapes, groups, transforms, solid and gradient
+//! fills and strokes, opacity, and blend modes. Text, images, patterns,
+//! filters, masks, clip paths, and markers are ignored.
+
+use std::collections::HashMap;
+
+use roxmltree::Node;
+
+use crate::{
+    canvas::CanvasOps,
+    color::{Color, Space as ColorSpace},
+    math::{affine::Affine, vector2::Vec2},
+    paint::{BlendMode, ColorStop, FillRule, Gradient, GradientKind, PaintConfig, Spread},
+    shapes::{
+        path::{Builder as PathBuilder, Error as PathError, ParseErrorKind, Path},
+        point::Point,
+        rect::Rect,
+        stroke::{LineCap, LineJoin, StrokeStyle},
+    },
+};
+
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+/// The number of gradients that may be chained together with `href` when
+/// looking for attributes and color stops. This guards against reference
+/// cycles.
+const MAX_GRADIENT_REFERENCES: usize = 8;
+
+/// The color keywords from CSS 2, except for `transparent`.
+const NAMED_COLORS: [(&str, [u8; 3]); 17] = [
+    ("black", [0, 0, 0]),
+    ("silver", [192, 192, 192]),
+    ("gray", [128, 128, 128]),
+    ("white", [255, 255, 255]),
+    ("maroon", [128, 0, 0]),
+    ("red", [255, 0, 0]),
+    ("purple", [128, 0, 128]),
+    ("fuchsia", [255, 0, 255]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("olive", [128, 128, 0]),
+    ("yellow", [255, 255, 0]),
+    ("navy", [0, 0, 128]),
+    ("blue", [0, 0, 255]),
+    ("teal", [0, 128, 128]),
+    ("aqua", [0, 255, 255]),
+    ("orange", [255, 165, 0]),
+];
+
+#[derive(Debug)]
+pub enum Error {
+    /// The document is not well-formed XML.
+    Xml(roxmltree::Error),
+    /// The root element of the document is not `<svg>`.
+    NotSvg,
+    /// The `d` attribute of a `<path>` could not be parsed. The offset is in
+    /// bytes from the start of the document.
+    PathData { offset: usize, kind: ParseErrorKind },
+}
+
+/// A single drawing operation, in the coordinate space of the document.
+#[derive(Clone)]
+pub enum DrawCommand {
+    /// Fills the path with `paint.fill_color` or `paint.fill_gradient`, using
+    /// `paint.fill_rule`.
+    Fill { path: Path, paint: PaintConfig },
+    /// Strokes the path with `paint.stroke_color` or `paint.stroke_gradient`,
+    /// using `paint.stroke_style`.
+    Stroke { path: Path, paint: PaintConfig },
+    /// Begins a layer that is composited with the given opacity and blend mode
+    /// once the matching `PopLayer` is reached. Layers are used for the opacity
+    /// and blend mode of groups, and of shapes that are both filled and
+    /// stroked.
+    PushLayer { opacity: f32, blend_mode: BlendMode },
+    /// Ends the most recently pushed layer.
+    PopLayer,
+}
+
+/// The contents of an SVG document.
+pub struct Document {
+    /// The width of the document, or 0 if it doesn't have one.
+    pub width: f32,
+    /// The height of the document, or 0 if it doesn't have one.
+    pub height: f32,
+    /// The commands that draw the document, in the order they must be drawn.
+    pub commands: Vec<DrawCommand>,
+}
+
+impl Document {
+    /// Reads an SVG document.
+    ///
+    /// ```rust
+    /// # use shiny::import::svg::{Document, DrawCommand};
+    /// let document = Document::parse(
+    ///     r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+    ///         <rect width="10" height="10" fill="red" stroke="blue"/>
+    ///     </svg>"#,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(document.width, 20.0);
+    /// assert!(matches!(document.commands[0], DrawCommand::Fill { .. }));
+    /// assert!(matches!(document.commands[1], DrawCommand::Stroke { .. }));
+    /// ```
+    pub fn parse(data: &str) -> Result<Document, Error> {
+        let xml = roxmltree::Document::parse(data).map_err(Error::Xml)?;
+        let root = xml.root_element();
+        if !root.has_tag_name("svg") {
+            return Err(Error::NotSvg);
+        }
+
+        let view_box = root.attribute("viewBox").and_then(|value| {
+            match numbers(value).collect::<Option<Vec<_>>>()?[..] {
+                [x, y, width, height] if width > 0.0 && height > 0.0 => Some([x, y, width, height]),
+                _ => None,
+            }
+        });
+
+        let width = root.attribute("width").and_then(length);
+        let height = root.attribute("height").and_then(length);
+        let width = width.or(view_box.map(|[_, _, w, _]| w)).unwrap_or(0.0);
+        let height = height.or(view_box.map(|[_, _, _, h]| h)).unwrap_or(0.0);
+
+        // Percentages in gradients are relative to the size of the view box.
+        let viewport = match view_box {
+            Some([_, _, w, h]) => Vec2::new(w, h),
+            None => Vec2::new(width, height),
+        };
+
+        // The view box is scaled uniformly to fit the document, and centered
+        // within it.
+        let mut state = State::default();
+        if let Some([x, y, w, h]) = view_box {
+            if width > 0.0 && height > 0.0 {
+                let scale = (width / w).min(height / h);
+                state.transform = Affine::translate(Vec2::new(
+                    (width - w * scale) / 2.0 - x * scale,
+                    (height - h * scale) / 2.0 - y * scale,
+                )) * Affine::scale(scale, scale);
+            }
+        }
+
+        let mut importer = Importer {
+            ids: xml
+                .descendants()
+                .filter_map(|node| Some((node.attribute("id")?, node)))
+                .collect(),
+            viewport,
+            commands: Vec::new(),
+        };
+        importer.element(root, &state)?;
+
+        Ok(Document {
+            width,
+            height,
+            commands: importer.commands,
+        })
+    }
+
+    /// Draws the document onto `canvas`, creating a paint for each command.
+    pub fn draw(&self, canvas: &mut impl CanvasOps) {
+        for command in &self.commands {
+            match command {
+                DrawCommand::Fill { path, paint } => {
+                    let paint = canvas.create_paint(paint.clone());
+                    canvas.fill_path(path, paint);
+                }
+                DrawCommand::Stroke { path, paint } => {
+                    let paint = canvas.create_paint(paint.clone());
+                    canvas.stroke_path(path, paint);
+                }
+                DrawCommand::PushLayer {
+                    opacity,
+                    blend_mode,
+                } => {
+                    canvas.push_layer(*opacity, *blend_mode, None);
+                }
+                DrawCommand::PopLayer => canvas.pop_layer(),
+            }
+        }
+    }
+}
+
+struct Importer<'a, 'input> {
+    ids: HashMap<&'a str, Node<'a, 'input>>,
+    /// The width and height that percentages in user space are relative to.
+    viewport: Vec2,
+    commands: Vec<DrawCommand>,
+}
+
+impl<'a, 'input> Importer<'a, 'input> {
+    fn element(&mut self, node: Node<'a, 'input>, parent: &State) -> Result<(), Error> {
+        let name = node.tag_name().name();
+        let is_container = matches!(name, "svg" | "g" | "a");
+        let is_shape = matches!(
+            name,
+            "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon"
+        );
+
+        if !(is_container || is_shape) || property(node, "display") == Some("none") {
+            return Ok(());
+        }
+
+        let mut state = parent.clone();
+        state.apply(node, self);
+
+        if is_container {
+            let layer = state.opacity < 1.0 || state.blend_mode != BlendMode::Normal;
+            if layer {
+                self.commands.push(DrawCommand::PushLayer {
+                    opacity: state.opacity,
+                    blend_mode: state.blend_mode,
+                });
+            }
+            for child in node.children().filter(Node::is_element) {
+                self.element(child, &state)?;
+            }
+            if layer {
+                self.commands.push(DrawCommand::PopLayer);
+            }
+            return Ok(());
+        }
+
+        let path = if name == "path" {
+            let data = match node.attribute_node("d") {
+                Some(data) => data,
+                None => return Ok(()),
+            };
+            Path::from_svg_path_data(data.value()).map_err(|error| Error::PathData {
+                offset: data.value_range().start + error.offset,
+                kind: error.kind,
+            })?
+        } else {
+            basic_shape(node, name).expect("basic shapes are too small to overflow a path")
+        };
+
+        if state.visible && !path.segments.is_empty() {
+            // Lines enclose no area, so there is nothing to fill.
+            self.draw(path, &state, name != "line");
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, mut path: Path, state: &State, fill: bool) {
+        let bounds = path.bounds();
+        path.transform(&state.transform);
+
+        // Strokes are widened by the average scale of the transform, which is
+        // exact as long as it scales both axes equally.
+        let scale = state.transform.scale_factor();
+        let style = &state.stroke_style;
+        let fill = state.fill.as_ref().filter(|_| fill);
+        let stroke = state.stroke.as_ref().filter(|_| style.width * scale > 0.0);
+
+        // The fill and stroke are made transparent and blended together, so
+        // they need a layer if there are both. Otherwise, the opacity and blend
+        // mode can be applied to the paint directly.
+        let layer = fill.is_some()
+            && stroke.is_some()
+            && (state.opacity < 1.0 || state.blend_mode != BlendMode::Normal);
+        let (opacity, blend_mode) = if layer {
+            self.commands.push(DrawCommand::PushLayer {
+                opacity: state.opacity,
+                blend_mode: state.blend_mode,
+            });
+            (1.0, BlendMode::Normal)
+        } else {
+            (state.opacity, state.blend_mode)
+        };
+
+        let fill = fill
+            .and_then(|paint| paint.resolve(state.fill_opacity * opacity, state.transform, bounds));
+        let stroke = stroke.and_then(|paint| {
+            paint.resolve(state.stroke_opacity * opacity, state.transform, bounds)
+        });
+
+        if let Some((fill_color, fill_gradient)) = fill {
+            self.commands.push(DrawCommand::Fill {
+                path: path.clone(),
+                paint: PaintConfig {
+                    fill_color,
+                    fill_gradient,
+                    fill_rule: state.fill_rule,
+                    blend_mode,
+                    ..Default::default()
+                },
+            });
+        }
+
+        if let Some((stroke_color, stroke_gradient)) = stroke {
+            self.commands.push(DrawCommand::Stroke {
+                path,
+                paint: PaintConfig {
+                    stroke_color,
+                    stroke_gradient,
+                    stroke_style: StrokeStyle {
+                        width: style.width * scale,
+                        dash_array: style.dash_array.iter().map(|l| l * scale).collect(),
+                        dash_offset: style.dash_offset * scale,
+                        ..style.clone()
+                    },
+                    blend_mode,
+                    ..Default::default()
+                },
+            });
+        }
+
+        if layer {
+            self.commands.push(DrawCommand::PopLayer);
+        }
+    }
+
+    /// Parses the value of a `fill` or `stroke` property. The outer option is
+    /// `None` if the value is invalid, and the inner one if it is `none`.
+    fn paint(&self, value: &str, current_color: Color) -> Option<Option<Paint>> {
+        match value {
+            "none" => Some(None),
+            "currentColor" => Some(Some(Paint::Color(current_color))),
+            _ => {
+                let reference = match value.strip_prefix("url(") {
+                    Some(reference) => reference,
+                    None => return parse_color(value).map(|color| Some(Paint::Color(color))),
+                };
+
+                let (reference, fallback) = reference.split_once(')')?;
+                let gradient = reference
+                    .trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .strip_prefix('#')
+                    .and_then(|id| self.ids.get(id))
+                    .filter(|node| is_gradient(**node))
+                    .and_then(|node| self.gradient(*node));
+
+                match (gradient, fallback.trim()) {
+                    (Some(gradient), _) => Some(Some(gradient)),
+                    (None, "") => Some(None),
+                    (None, fallback) => self.paint(fallback, current_color),
+                }
+            }
+        }
+    }
+
+    /// Reads a `<linearGradient>` or `<radialGradient>`. Attributes that the
+    /// gradient doesn't set are taken from the gradient it references, if
+    /// any, and so are its stops if it has none of its own.
+    fn gradient(&self, node: Node<'a, 'input>) -> Option<Paint> {
+        let mut chain = vec![node];
+        while chain.len() < MAX_GRADIENT_REFERENCES {
+            let last = chain[chain.len() - 1];
+            let next = last
+                .attribute((XLINK_NAMESPACE, "href"))
+                .or_else(|| last.attribute("href"))
+                .and_then(|href| self.ids.get(href.strip_prefix('#')?))
+                .filter(|node| is_gradient(**node));
+            match next {
+                Some(next) => chain.push(*next),
+                None => break,
+            }
+        }
+
+        let attribute = |name: &str| chain.iter().find_map(|node| node.attribute(name));
+
+        let stops = chain.iter().find_map(|node| {
+            let stops = node
+                .children()
+                .filter(|node| node.has_tag_name("stop"))
+                .map(|stop| {
+                    let offset = stop.attribute("offset").and_then(opacity).unwrap_or(0.0);
+                    let color = property(stop, "stop-color")
+                        .and_then(parse_color)
+                        .unwrap_or(Color::srgba(0.0, 0.0, 0.0, 1.0));
+                    let opacity = property(stop, "stop-opacity")
+                        .and_then(opacity)
+                        .unwrap_or(1.0);
+                    ColorStop::new(
+                        offset,
+                        Color {
+                            a: color.a * opacity,
+                            ..color
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+            (!stops.is_empty()).then_some(stops)
+        })?;
+
+        // Coordinates are fractions of the bounding box of the element being
+        // painted, unless they are in user space. Percentages in user space
+        // are relative to the viewport, with radii relative to its normalized
+        // diagonal.
+        let bounding_box = attribute("gradientUnits") != Some("userSpaceOnUse");
+        let (width, height) = (self.viewport.x(), self.viewport.y());
+        let diagonal = ((width * width + height * height) / 2.0).sqrt();
+        let coordinate = |name: &str, default: f32, extent: f32| {
+            let extent = if bounding_box { 1.0 } else { extent };
+            let value = attribute(name).and_then(|value| match value.trim().strip_suffix('%') {
+                Some(percent) => Some(percent.trim().parse::<f32>().ok()? / 100.0 * extent),
+                None => length(value),
+            });
+            value.unwrap_or(default * extent)
+        };
+
+        let mut gradient = if node.has_tag_name("linearGradient") {
+            Gradient::linear(
+                Point::new(coordinate("x1", 0.0, width), coordinate("y1", 0.0, height)),
+                Point::new(coordinate("x2", 1.0, width), coordinate("y2", 0.0, height)),
+                stops,
+            )
+        } else {
+            let center = Point::new(coordinate("cx", 0.5, width), coordinate("cy", 0.5, height));
+            let radius = coordinate("r", 0.5, diagonal);
+            if radius < 0.0 {
+                return None;
+            }
+
+            // The focus defaults to the center, wherever that came from.
+            let focus = Point::new(
+                attribute("fx").map_or(center.x, |_| coordinate("fx", 0.0, width)),
+                attribute("fy").map_or(center.y, |_| coordinate("fy", 0.0, height)),
+            );
+            let mut gradient = Gradient::radial(center, radius, stops);
+            gradient.kind = GradientKind::Radial {
+                center,
+                radius,
+                focus,
+            };
+            gradient
+        };
+
+        gradient.spread = match attribute("spreadMethod") {
+            Some("reflect") => Spread::Reflect,
+            Some("repeat") => Spread::Repeat,
+            _ => Spread::Pad,
+        };
+
+        if let Some(transform) = attribute("gradientTransform").and_then(parse_transform) {
+            gradient.transform = transform;
+        }
+
+        if property(node, "color-interpolation") == Some("linearRGB") {
+            gradient.interpolation = ColorSpace::LinearSrgb;
+        }
+
+        Some(Paint::Gradient {
+            gradient,
+            bounding_box,
+        })
+    }
+}
+
+/// The value of a `fill` or `stroke` property.
+#[derive(Clone)]
+enum Paint {
+    Color(Color),
+    /// A gradient in user space, or in the bounding box of the element it
+    /// paints if `bounding_box` is set, where (0, 0) is the top left corner
+    /// and (1, 1) the bottom right.
+    Gradient {
+        gradient: Gradient,
+        bounding_box: bool,
+    },
+}
+
+impl Paint {
+    /// Finds the color or gradient that paints an element with the given
+    /// bounds and transform, with its opacity multiplied by `alpha`. Returns
+    /// `None` if nothing should be painted, which is the case for gradients
+    /// in the bounding box of an element with no width or height.
+    fn resolve(
+        &self,
+        alpha: f32,
+        transform: Affine,
+        bounds: Rect,
+    ) -> Option<(Color, Option<Gradient>)> {
+        match self {
+            Paint::Color(color) => Some((
+                Color {
+                    a: color.a * alpha,
+                    ..*color
+                },
+                None,
+            )),
+            Paint::Gradient {
+                gradient,
+                bounding_box,
+            } => {
+                let mut gradient = gradient.clone();
+                for stop in &mut gradient.stops {
+                    stop.color.a *= alpha;
+                }
+
+                let space = if *bounding_box {
+                    if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+                        return None;
+                    }
+                    Affine::translate(Vec2::new(bounds.left, bounds.top))
+                        * Affine::scale(bounds.width(), bounds.height())
+                } else {
+                    Affine::IDENTITY
+                };
+                gradient.transform = transform * space * gradient.transform;
+
+                Some((Color::default(), Some(gradient)))
+            }
+        }
+    }
+}
+
+/// The properties that an element inherits from its parent, and the ones that
+/// accumulate from its ancestors.
+#[derive(Clone)]
+struct State {
+    transform: Affine,
+    /// The value of `currentColor`.
+    color: Color,
+    fill: Option<Paint>,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Option<Paint>,
+    stroke_opacity: f32,
+    stroke_style: StrokeStyle,
+    /// The opacity of the element itself, which is not inherited.
+    opacity: f32,
+    /// The blend mode of the element itself, which is not inherited.
+    blend_mode: BlendMode,
+    visible: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            transform: Affine::IDENTITY,
+            color: Color::srgba(0.0, 0.0, 0.0, 1.0),
+            fill: Some(Paint::Color(Color::srgba(0.0, 0.0, 0.0, 1.0))),
+            fill_opacity: 1.0,
+            fill_rule: FillRule::NonZero,
+            stroke: None,
+            stroke_opacity: 1.0,
+            stroke_style: StrokeStyle::default(),
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            visible: true,
+        }
+    }
+}
+
+impl State {
+    /// Updates the state with the properties set on `node`. Invalid values are
+    /// ignored.
+    fn apply(&mut self, node: Node, importer: &Importer) {
+        if let Some(transform) = node.attribute("transform").and_then(parse_transform) {
+            self.transform = self.transform * transform;
+        }
+
+        if let Some(color) = property(node, "color").and_then(parse_color) {
+            self.color = color;
+        }
+
+        if let Some(fill) = property(node, "fill").and_then(|v| importer.paint(v, self.color)) {
+            self.fill = fill;
+        }
+
+        if let Some(stroke) = property(node, "stroke").and_then(|v| importer.paint(v, self.color)) {
+            self.stroke = stroke;
+        }
+
+        if let Some(value) = property(node, "fill-opacity").and_then(opacity) {
+            self.fill_opacity = value;
+        }
+
+        if let Some(value) = property(node, "stroke-opacity").and_then(opacity) {
+            self.stroke_opacity = value;
+        }
+
+        self.opacity = property(node, "opacity").and_then(opacity).unwrap_or(1.0);
+        self.blend_mode = property(node, "mix-blend-mode")
+            .and_then(blend_mode)
+            .unwrap_or(BlendMode::Normal);
+
+        match property(node, "fill-rule") {
+            Some("nonzero") => self.fill_rule = FillRule::NonZero,
+            Some("evenodd") => self.fill_rule = FillRule::EvenOdd,
+            _ => {}
+        }
+
+        match property(node, "visibility") {
+            Some("visible") => self.visible = true,
+            Some("hidden" | "collapse") => self.visible = false,
+            _ => {}
+        }
+
+        let style = &mut self.stroke_style;
+        if let Some(width) = property(node, "stroke-width").and_then(length) {
+            if width >= 0.0 {
+                style.width = width;
+            }
+        }
+
+        match property(node, "stroke-linecap") {
+            Some("butt") => style.cap = LineCap::Butt,
+            Some("round") => style.cap = LineCap::Round,
+            Some("square") => style.cap = LineCap::Square,
+            _ => {}
+        }
+
+        match property(node, "stroke-linejoin") {
+            Some("miter" | "miter-clip" | "arcs") => style.join = LineJoin::Miter,
+            Some("round") => style.join = LineJoin::Round,
+            Some("bevel") => style.join = LineJoin::Bevel,
+            _ => {}
+        }
+
+        if let Some(limit) = property(node, "stroke-miterlimit").and_then(|v| v.parse().ok()) {
+            if limit >= 1.0 {
+                style.miter_limit = limit;
+            }
+        }
+
+        match property(node, "stroke-dasharray") {
+            Some("none") => style.dash_array.clear(),
+            Some(value) => {
+                let dashes = value
+                    .split(|c: char| c == ',' || c.is_ascii_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(length)
+                    .collect::<Option<Vec<_>>>();
+
+                // Dashing is disabled by invalid patterns, and patterns with an
+                // odd number of lengths are repeated to make it even.
+                if let Some(mut dashes) = dashes {
+                    if dashes.iter().any(|l| *l < 0.0) || dashes.iter().sum::<f32>() <= 0.0 {
+                        dashes.clear();
+                    } else if dashes.len() % 2 == 1 {
+                        dashes.extend_from_within(..);
+                    }
+                    style.dash_array = dashes;
+                }
+            }
+            None => {}
+        }
+
+        if let Some(offset) = property(node, "stroke-dashoffset").and_then(length) {
+            style.dash_offset = offset;
+        }
+    }
+}
+
+/// Parses the value of a `transform` attribute, which is a list of transforms
+/// that are applied from last to first.
+fn parse_transform(value: &str) -> Option<Affine> {
+    let is_separator = |c: char| c == ',' || c.is_ascii_whitespace();
+
+    let mut transform = Affine::IDENTITY;
+    let mut rest = value.trim_start_matches(is_separator);
+    while !rest.is_empty() {
+        let (name, tail) = rest.split_once('(')?;
+        let (arguments, tail) = tail.split_once(')')?;
+        let arguments = numbers(arguments).collect::<Option<Vec<_>>>()?;
+
+        let next = match (name.trim(), &arguments[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => Affine::new(a, b, c, d, e, f),
+            ("translate", &[x]) => Affine::translate(Vec2::new(x, 0.0)),
+            ("translate", &[x, y]) => Affine::translate(Vec2::new(x, y)),
+            ("scale", &[s]) => Affine::scale(s, s),
+            ("scale", &[x, y]) => Affine::scale(x, y),
+            ("rotate", &[angle]) => Affine::rotate(angle.to_radians()),
+            ("rotate", &[angle, x, y]) => Affine::rotate_about(angle.to_radians(), Vec2::new(x, y)),
+            ("skewX", &[angle]) => Affine::skew(angle.to_radians(), 0.0),
+            ("skewY", &[angle]) => Affine::skew(0.0, angle.to_radians()),
+            _ => return None,
+        };
+
+        transform = transform * next;
+        rest = tail.trim_start_matches(is_separator);
+    }
+
+    Some(transform)
+}
+
+/// Builds the path outlining a shape other than `<path>`, or returns an empty
+/// path if the shape's attributes describe nothing to draw.
+fn basic_shape(node: Node, name: &str) -> Result<Path, PathError> {
+    let number = |name: &str| node.attribute(name).and_then(length);
+    let mut builder = PathBuilder::default();
+
+    match name {
+        "rect" => {
+            let (x, y) = (number("x").unwrap_or(0.0), number("y").unwrap_or(0.0));
+            let width = number("width").unwrap_or(0.0);
+            let height = number("height").unwrap_or(0.0);
+            if width <= 0.0 || height <= 0.0 {
+                return builder.build();
+            }
+
+            // A missing corner radius is the same as the other one.
+            let (rx, ry) = match (
+                number("rx").filter(|r| *r >= 0.0),
+                number("ry").filter(|r| *r >= 0.0),
+            ) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            let (rx, ry) = (rx.min(width / 2.0), ry.min(height / 2.0));
+
+            if rx > 0.0 && ry > 0.0 {
+                let radii = Vec2::new(rx, ry);
+                let corner = |builder: &mut PathBuilder, x: f32, y: f32| {
+                    builder.arc_to(radii, 0.0, false, true, Point::new(x, y))
+                };
+
+                builder.move_to(Point::new(x + rx, y));
+                builder.horizontal_line_to(x + width - rx)?;
+                corner(&mut builder, x + width, y + ry)?;
+                builder.vertical_line_to(y + height - ry)?;
+                corner(&mut builder, x + width - rx, y + height)?;
+                builder.horizontal_line_to(x + rx)?;
+                corner(&mut builder, x, y + height - ry)?;
+                builder.vertical_line_to(y + ry)?;
+                corner(&mut builder, x + rx, y)?;
+            } else {
+                builder.move_to(Point::new(x, y));
+                builder.horizontal_line_to(x + width)?;
+                builder.vertical_line_to(y + height)?;
+                builder.horizontal_line_to(x)?;
+            }
+            builder.close()?;
+        }
+        "circle" | "ellipse" => {
+            let (cx, cy) = (number("cx").unwrap_or(0.0), number("cy").unwrap_or(0.0));
+            let (rx, ry) = if name == "circle" {
+                let r = number("r").unwrap_or(0.0);
+                (r, r)
+            } else {
+                (number("rx").unwrap_or(0.0), number("ry").unwrap_or(0.0))
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return builder.build();
+            }
+
+            let radii = Vec2::new(rx, ry);
+            builder.move_to(Point::new(cx + rx, cy));
+            for (x, y) in [(cx, cy + ry), (cx - rx, cy), (cx, cy - ry), (cx + rx, cy)] {
+                builder.arc_to(radii, 0.0, false, true, Point::new(x, y))?;
+            }
+            builder.close()?;
+        }
+        "line" => {
+            builder.move_to(Point::new(
+                number("x1").unwrap_or(0.0),
+                number("y1").unwrap_or(0.0),
+            ));
+            builder.line_to(Point::new(
+                number("x2").unwrap_or(0.0),
+                number("y2").unwrap_or(0.0),
+            ))?;
+        }
+        "polyline" | "polygon" => {
+            // Points are drawn up to the first one that is invalid.
+            let coordinates = numbers(node.attribute("points").unwrap_or(""))
+                .map_while(|n| n)
+                .collect::<Vec<_>>();
+            let mut points = coordinates.chunks_exact(2).map(|p| Point::new(p[0], p[1]));
+
+            if let Some(first) = points.next() {
+                builder.move_to(first);
+                for point in points {
+                    builder.line_to(point)?;
+                }
+                if name == "polygon" {
+                    builder.close()?;
+                }
+            }
+        }
+        _ => unreachable!("not a basic shape: {name}"),
+    }
+
+    builder.build()
+}
+
+/// Finds the value of a property, which may be set either in the element's
+/// `style` attribute or as an attribute of its own. The style takes priority.
+fn property<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let declared = node.attribute("style").and_then(|style| {
+        style
+            .split(';')
+            .rev()
+            .filter_map(|declaration| declaration.split_once(':'))
+            .find(|(property, _)| property.trim() == name)
+            .map(|(_, value)| value.trim())
+    });
+
+    // Inherited values are already in the parent's state.
+    declared
+        .or_else(|| node.attribute(name).map(str::trim))
+        .filter(|value| *value != "inherit")
+}
+
+/// Splits a list of numbers separated by commas or whitespace, such as the
+/// points of a polygon. Numbers that are invalid are `None`.
+fn numbers(value: &str) -> impl Iterator<Item = Option<f32>> + '_ {
+    value
+        .split(|c: char| c == ',' || c.is_ascii_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+}
+
+/// Parses a length in user units, which may have a `px` suffix.
+fn length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    value.strip_suffix("px").unwrap_or(value).parse().ok()
+}
+
+/// Parses an opacity or a gradient stop's offset, which may be given as a
+/// number or a percentage.
+fn opacity(value: &str) -> Option<f32> {
+    let value = match value.strip_suffix('%') {
+        Some(percent) => percent.parse::<f32>().ok()? / 100.0,
+        None => value.parse().ok()?,
+    };
+    Some(value.clamp(0.0, 1.0))
+}
+
+fn is_gradient(node: Node) -> bool {
+    matches!(node.tag_name().name(), "linearGradient" | "radialGradient")
+}
+
+/// Parses the value of a `mix-blend-mode` property.
+fn blend_mode(value: &str) -> Option<BlendMode> {
+    Some(match value {
+        "normal" => BlendMode::Normal,
+        "multiply" => BlendMode::Multiply,
+        "screen" => BlendMode::Screen,
+        "overlay" => BlendMode::Overlay,
+        "darken" => BlendMode::Darken,
+        "lighten" => BlendMode::Lighten,
+        "color-dodge" => BlendMode::ColorDodge,
+        "color-burn" => BlendMode::ColorBurn,
+        "hard-light" => BlendMode::HardLight,
+        "soft-light" => BlendMode::SoftLight,
+        "difference" => BlendMode::Difference,
+        "exclusion" => BlendMode::Exclusion,
+        "hue" => BlendMode::Hue,
+        "saturation" => BlendMode::Saturation,
+        "color" => BlendMode::Color,
+        "luminosity" => BlendMode::Luminosity,
+        _ => return None,
+    })
+}
+
+/// Parses a color in hexadecimal notation, functional `rgb()` notation, or as
+/// one of the keywords from CSS 2.
+fn parse_color(value: &str) -> Option<Color> {
+    let rgb8 = |[r, g, b]: [u8; 3]| {
+        Color::srgba(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, 1.0)
+    };
+
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()?;
+
+        return match digits[..] {
+            [r, g, b] => Some(rgb8([r * 17, g * 17, b * 17])),
+            [r1, r0, g1, g0, b1, b0] => Some(rgb8([r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0])),
+            _ => None,
+        };
+    }
+
+    if let Some(arguments) = value.strip_prefix("rgb(").and_then(|v| v.strip_suffix(')')) {
+        let channels = arguments
+            .split(',')
+            .map(|channel| {
+                let channel = channel.trim();
+                let value = match channel.strip_suffix('%') {
+                    Some(percent) => percent.trim_end().parse::<f32>().ok()? / 100.0,
+                    None => channel.parse::<f32>().ok()? / 255.0,
+                };
+                Some(value.clamp(0.0, 1.0))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        return match channels[..] {
+            [r, g, b] => Some(Color::srgba(r, g, b, 1.0)),
+            _ => None,
+        };
+    }
+
+    if value.eq_ignore_ascii_case("transparent") {
+        return Some(Color::srgba(0.0, 0.0, 0.0, 0.0));
+    }
+
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, rgb)| rgb8(*rgb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::cmp::ApproxEq;
+
+    fn parse(body: &str) -> Document {
+        Document::parse(&format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">{body}</svg>"#
+        ))
+        .unwrap()
+    }
+
+    fn fills(document: &Document) -> Vec<(&Path, &PaintConfig)> {
+        document
+            .commands
+            .iter()
+            .filter_map(|command| match command {
+                DrawCommand::Fill { path, paint } => Some((path, paint)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn assert_bounds(path: &Path, left: f32, right: f32, top: f32, bottom: f32) {
+        let bounds = path.bounds();
+        let bounds = (bounds.left, bounds.right, bounds.top, bounds.bottom);
+        let expected = (left, right, top, bottom);
+        assert!(
+            bounds.0.approx_eq(&expected.0)
+                && bounds.1.approx_eq(&expected.1)
+                && bounds.2.approx_eq(&expected.2)
+                && bounds.3.approx_eq(&expected.3),
+            "{bounds:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn shapes() {
+        let document = parse(
+            r#"
+            <path d="M0 0 L10 0 L10 10 Z"/>
+            <rect x="1" y="2" width="3" height="4"/>
+            <rect x="0" y="0" width="10" height="4" rx="1"/>
+            <rect width="0" height="10"/>
+            <circle cx="5" cy="5" r="2"/>
+            <ellipse cx="5" cy="5" rx="3" ry="1"/>
+            <line x1="0" y1="0" x2="5" y2="5" stroke="black"/>
+            <polyline points="0,0 5,0 5,5 7"/>
+            <polygon points="0 0, 5 0, 5 5"/>
+            <text>ignored</text>
+            "#,
+        );
+
+        let fills = fills(&document);
+        assert_eq!(fills.len(), 7);
+        assert_bounds(fills[0].0, 0.0, 10.0, 0.0, 10.0);
+        assert_bounds(fills[1].0, 1.0, 4.0, 2.0, 6.0);
+        assert_bounds(fills[2].0, 0.0, 10.0, 0.0, 4.0);
+        assert_bounds(fills[3].0, 3.0, 7.0, 3.0, 7.0);
+        assert_bounds(fills[4].0, 2.0, 8.0, 4.0, 6.0);
+        assert_bounds(fills[5].0, 0.0, 5.0, 0.0, 5.0);
+        assert!(!fills[5].0.segments[0].closed);
+        assert!(fills[6].0.segments[0].closed);
+
+        // The line is only stroked.
+        assert!(matches!(
+            &document.commands[5],
+            DrawCommand::Stroke { path, .. } if path.segments.len() == 1
+        ));
+    }
+
+    #[test]
+    fn transforms() {
+        let document = parse(
+            r#"
+            <g transform="translate(10, 20)">
+                <g transform="scale(2) rotate(90)">
+                    <rect width="2" height="1" stroke="red" stroke-width="0.5"/>
+                </g>
+                <rect width="1" height="1" transform="matrix(1 0 0 1 5 5) skewX(45)"/>
+                <rect width="1" height="1" transform="rotate(180, 1, 1)"/>
+            </g>
+            <rect width="1" height="1" transform="bogus(1)"/>
+            "#,
+        );
+
+        let fills = fills(&document);
+        assert_bounds(fills[0].0, 8.0, 10.0, 20.0, 24.0);
+        assert_bounds(fills[1].0, 15.0, 17.0, 25.0, 26.0);
+        assert_bounds(fills[2].0, 11.0, 12.0, 21.0, 22.0);
+        assert_bounds(fills[3].0, 0.0, 1.0, 0.0, 1.0);
+
+        // Strokes are scaled along with the path.
+        match &document.commands[1] {
+            DrawCommand::Stroke { paint, .. } => assert_eq!(paint.stroke_style.width, 1.0),
+            _ => panic!("expected a stroke"),
+        }
+    }
+
+    #[test]
+    fn view_box() {
+        let document = Document::parse(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="-10 0 20 20">
+                <rect x="-10" width="20" height="20"/>
+            </svg>"#,
+        )
+        .unwrap();
+
+        assert_eq!((document.width, document.height), (200.0, 100.0));
+        assert_bounds(fills(&document)[0].0, 50.0, 150.0, 0.0, 100.0);
+    }
+
+    #[test]
+    fn styles() {
+        let document = parse(
+            r##"
+            <linearGradient id="base">
+                <stop offset="0" stop-color="#ff0000"/>
+                <stop offset="1" style="stop-color: #0000ff; stop-opacity: 0"/>
+            </linearGradient>
+            <radialGradient id="linked" xlink:href="#base"/>
+            <g fill="red" stroke="blue" opacity="0.5" style="fill-rule: evenodd">
+                <rect width="1" height="1" fill-opacity="50%" style="stroke-width: 2px"/>
+                <rect width="1" height="1" style="fill: rgb(0, 100%, 51); stroke: none"/>
+                <rect width="1" height="1" fill="url(#linked)" stroke="url(#missing) #0f0"/>
+                <rect width="1" height="1" fill="url(#missing)" stroke="url(#missing)"/>
+                <rect width="1" height="1" fill="inherit" color="teal" stroke="currentColor"/>
+                <rect width="1" height="1" fill="not a color" display="none"/>
+                <g visibility="hidden">
+                    <rect width="1" height="1"/>
+                    <rect width="1" height="1" visibility="visible"/>
+                </g>
+            </g>
+            "##,
+        );
+
+        // The group's opacity is applied to all of its children at once.
+        let commands = &document.commands;
+        assert!(matches!(
+            commands[0],
+            DrawCommand::PushLayer { opacity, blend_mode: BlendMode::Normal } if opacity == 0.5
+        ));
+        assert!(matches!(
+            commands[commands.len() - 1],
+            DrawCommand::PopLayer
+        ));
+
+        let colors = commands[1..commands.len() - 1]
+            .iter()
+            .map(|command| match command {
+                DrawCommand::Fill { paint, .. } => {
+                    assert_eq!(paint.fill_rule, FillRule::EvenOdd);
+                    match &paint.fill_gradient {
+                        Some(gradient) => ('g', gradient.stops[1].color.to_rgba8()),
+                        None => ('f', paint.fill_color.to_rgba8()),
+                    }
+                }
+                DrawCommand::Stroke { paint, .. } => ('s', paint.stroke_color.to_rgba8()),
+                _ => panic!("unexpected layer"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            colors,
+            [
+                ('f', [255, 0, 0, 128]),
+                ('s', [0, 0, 255, 255]),
+                ('f', [0, 255, 51, 255]),
+                ('g', [0, 0, 255, 0]),
+                ('s', [0, 255, 0, 255]),
+                ('f', [255, 0, 0, 255]),
+                ('s', [0, 128, 128, 255]),
+                ('f', [255, 0, 0, 255]),
+                ('s', [0, 0, 255, 255]),
+            ]
+        );
+
+        match &document.commands[2] {
+            DrawCommand::Stroke { paint, .. } => assert_eq!(paint.stroke_style.width, 2.0),
+            _ => panic!("expected a stroke"),
+        }
+    }
+
+    #[test]
+    fn gradients() {
+        let document = Document::parse(
+            r##"<svg xmlns="http://www.w3.org/2000/svg"
+                xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="50">
+            <linearGradient id="base" x2="50%" spreadMethod="reflect" gradientTransform="scale(2)">
+                <stop offset="0" stop-color="red"/>
+                <stop offset="150%" stop-color="blue" stop-opacity="0.5"/>
+            </linearGradient>
+            <radialGradient id="radial" xlink:href="#base" cx="10" fy="2" r="50%"
+                gradientUnits="userSpaceOnUse" style="color-interpolation: linearRGB"/>
+            <rect x="10" y="20" width="10" height="20" fill="url(#base)" fill-opacity="0.5"/>
+            <g transform="translate(5, 0)">
+                <rect width="1" height="1" fill="url(#radial)"/>
+            </g>
+            <line x2="10" stroke="url(#base)"/>
+            </svg>"##,
+        )
+        .unwrap();
+
+        let gradients = fills(&document)
+            .into_iter()
+            .map(|(_, paint)| paint.fill_gradient.as_ref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(gradients.len(), 2);
+        assert_eq!(document.commands.len(), 2);
+
+        // Bounding box units are mapped onto the untransformed shape.
+        let linear = gradients[0];
+        assert_eq!(
+            linear.kind,
+            GradientKind::Linear {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(0.5, 0.0),
+            }
+        );
+        assert_eq!(linear.spread, Spread::Reflect);
+        assert_eq!(linear.interpolation, ColorSpace::Srgb);
+        assert!(linear
+            .transform
+            .approx_eq(&Affine::new(20.0, 0.0, 0.0, 40.0, 10.0, 20.0)));
+
+        let stops = linear
+            .stops
+            .iter()
+            .map(|stop| (stop.offset, stop.color.to_rgba8()))
+            .collect::<Vec<_>>();
+        assert_eq!(stops, [(0.0, [255, 0, 0, 128]), (1.0, [0, 0, 255, 64])]);
+
+        // Attributes and stops are inherited through references, and user
+        // space percentages are relative to the viewport.
+        let radial = gradients[1];
+        let diagonal = ((100.0f32.powi(2) + 50.0f32.powi(2)) / 2.0).sqrt();
+        assert_eq!(
+            radial.kind,
+            GradientKind::Radial {
+                center: Point::new(10.0, 0.5 * 50.0),
+                radius: 0.5 * diagonal,
+                focus: Point::new(10.0, 2.0),
+            }
+        );
+        assert_eq!(radial.spread, Spread::Reflect);
+        assert_eq!(radial.interpolation, ColorSpace::LinearSrgb);
+        assert!(radial
+            .transform
+            .approx_eq(&Affine::new(2.0, 0.0, 0.0, 2.0, 5.0, 0.0)));
+        assert_eq!(radial.stops.len(), 2);
+    }
+
+    #[test]
+    fn layers() {
+        let document = parse(
+            r#"
+            <rect width="1" height="1" fill="red" stroke="blue" opacity="0.5"/>
+            <rect width="1" height="1" fill="red" opacity="0.5"/>
+            <g opacity="1">
+                <rect width="1" height="1" fill="red" style="mix-blend-mode: multiply"/>
+            </g>
+            <g style="mix-blend-mode: color-dodge">
+                <rect width="1" height="1" fill="red" mix-blend-mode="bogus"/>
+            </g>
+            "#,
+        );
+
+        let commands = document
+            .commands
+            .iter()
+            .map(|command| match command {
+                DrawCommand::Fill { paint, .. } => {
+                    format!("f{} {:?}", paint.fill_color.to_rgba8()[3], paint.blend_mode)
+                }
+                DrawCommand::Stroke { paint, .. } => {
+                    format!(
+                        "s{} {:?}",
+                        paint.stroke_color.to_rgba8()[3],
+                        paint.blend_mode
+                    )
+                }
+                DrawCommand::PushLayer {
+                    opacity,
+                    blend_mode,
+                } => format!("({opacity} {blend_mode:?}"),
+                DrawCommand::PopLayer => ")".to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        // Shapes with both a fill and a stroke need a layer so that the fill
+        // doesn't show through the stroke.
+        assert_eq!(
+            commands,
+            [
+                "(0.5 Normal",
+                "f255 Normal",
+                "s255 Normal",
+                ")",
+                "f128 Normal",
+                "f255 Multiply",
+                "(1 ColorDodge",
+                "f255 Normal",
+                ")",
+            ]
+        );
+    }
+
+    #[test]
+    fn colors() {
+        let rgba8 = |value| parse_color(value).map(|c| c.to_rgba8());
+        assert_eq!(rgba8("#fff"), Some([255, 255, 255, 255]));
+        assert_eq!(rgba8("#2F528F"), Some([47, 82, 143, 255]));
+        assert_eq!(rgba8(" rgb(255, 0, 50%) "), Some([255, 0, 128, 255]));
+        assert_eq!(rgba8("Navy"), Some([0, 0, 128, 255]));
+        assert_eq!(rgba8("transparent"), Some([0, 0, 0, 0]));
+        assert_eq!(rgba8("#ff"), None);
+        assert_eq!(rgba8("rgb(1, 2)"), None);
+        assert_eq!(rgba8("octarine"), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Document::parse("<svg"), Err(Error::Xml(_))));
+        assert!(matches!(Document::parse("<html/>"), Err(Error::NotSvg)));
+
+        let data = r#"<svg><path d="M0 0 L1"/></svg>"#;
+        assert!(matches!(
+            Document::parse(data),
+            Err(Error::PathData {
+                offset: 21,
+                kind: ParseErrorKind::ExpectedNumber
+            })
+        ));
+    }
+
+    #[cfg(feature = "backend-software")]
+    #[test]
+    fn test_files() {
+        use crate::{
+            backends::software::Software,
+            canvas::{Canvas, CanvasOptions},
+            color::Space as ColorSpace,
+            image::PixelFormat,
+        };
+
+        let backend = Software::new();
+        for (data, min_commands) in [
+            (include_str!("../../test_files/tiger.svg"), 240),
+            (include_str!("../../test_files/car.svg"), 300),
+            (include_str!("../../test_files/p1.svg"), 3),
+        ] {
+            let document = Document::parse(data).unwrap();
+            assert!(document.commands.len() >= min_commands);
+
+            // The tiger doesn't have a width, but fits within its height.
+            let width = document.width.max(document.height) as u32;
+            let height = document.height as u32;
+            let mut canvas = backend
+                .new_canvas(
+                    width,
+                    height,
+                    PixelFormat::Rgba8,
+                    ColorSpace::LinearSrgb,
+                    CanvasOptions::default(),
+                )
+                .unwrap();
+            document.draw(&mut canvas);
+
+            // Each document covers a good part of its area.
+            let pixels = canvas.get_pixels();
+            let drawn = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|(x, y)| pixels.get(*x, *y).a > 0.0)
+                .count();
+            assert!(drawn as u32 > width * height / 10, "{drawn}");
+        }
+    }
+}
@@ -4,6 +4,7 @@ pub mod backends;
 pub mod canvas;
 pub mod color;
 pub mod image;
+pub mod import;
 pub mod math;
 pub mod paint;
 pub mod pixel_buffer;
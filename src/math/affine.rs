@@ -0,0 +1,254 @@
+use std::{hash::Hash, ops::Mul};
+
+use super::{cmp::ApproxEq, simd::Float4, vector2::Vec2};
+
+/// A 2D affine transform, stored as the first two rows of a 3x3 matrix:
+///
+/// ```text
+/// | a c e |
+/// | b d f |
+/// | 0 0 1 |
+/// ```
+///
+/// Transforms are composed by multiplication, where `a * b` applies `b` first
+/// and then `a`. [`Affine::then`] composes them in reading order instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Affine {
+    pub const IDENTITY: Self = Self::new(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+
+    #[inline]
+    #[must_use]
+    pub const fn new(a: f32, b: f32, c: f32, d: f32, e: f32, f: f32) -> Self {
+        Self { a, b, c, d, e, f }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn translate(offset: Vec2) -> Self {
+        Self::new(1.0, 0.0, 0.0, 1.0, offset.x(), offset.y())
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self::new(x, 0.0, 0.0, y, 0.0, 0.0)
+    }
+
+    /// Rotates by `angle` radians about the origin. With the y axis pointing
+    /// down, positive angles rotate clockwise.
+    #[must_use]
+    pub fn rotate(angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self::new(cos, sin, -sin, cos, 0.0, 0.0)
+    }
+
+    /// Rotates by `angle` radians about `center`.
+    #[must_use]
+    pub fn rotate_about(angle: f32, center: Vec2) -> Self {
+        Self::translate(center) * Self::rotate(angle) * Self::translate(-center)
+    }
+
+    /// Slants the x axis by `x` radians and the y axis by `y` radians.
+    #[must_use]
+    pub fn skew(x: f32, y: f32) -> Self {
+        Self::new(1.0, y.tan(), x.tan(), 1.0, 0.0, 0.0)
+    }
+
+    /// The transform that applies `self`, and then `next`.
+    ///
+    /// ```rust
+    /// # use shiny::math::{affine::Affine, vector2::Vec2};
+    /// let t = Affine::scale(2.0, 2.0).then(Affine::translate(Vec2::new(1.0, 0.0)));
+    /// assert_eq!(t * Vec2::new(1.0, 1.0), Vec2::new(3.0, 2.0));
+    /// assert_eq!(t, Affine::new(2.0, 0.0, 0.0, 2.0, 1.0, 0.0));
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn then(self, next: Self) -> Self {
+        next * self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+
+    /// Finds the transform that undoes this one, if there is one. Transforms
+    /// that collapse the plane onto a line or a point cannot be undone.
+    #[must_use]
+    pub fn invert(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let inv = 1.0 / det;
+        Some(Self::new(
+            self.d * inv,
+            -self.b * inv,
+            -self.c * inv,
+            self.a * inv,
+            (self.c * self.f - self.d * self.e) * inv,
+            (self.b * self.e - self.a * self.f) * inv,
+        ))
+    }
+
+    /// The factor by which the transform scales lengths, on average. This is
+    /// exact for transforms that scale both axes equally.
+    #[must_use]
+    pub fn scale_factor(&self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+
+    /// Transforms a list of points, given as separate lists of x and y
+    /// coordinates. Four points are transformed at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` and `y` are not the same length.
+    pub fn apply(&self, x: &mut [f32], y: &mut [f32]) {
+        assert_eq!(x.len(), y.len());
+
+        let [a, b, c, d, e, f] =
+            [self.a, self.b, self.c, self.d, self.e, self.f].map(Float4::splat);
+        let mut xs = x.chunks_exact_mut(4);
+        let mut ys = y.chunks_exact_mut(4);
+        for (x, y) in (&mut xs).zip(&mut ys) {
+            let px = Float4::from_array((&*x).try_into().unwrap());
+            let py = Float4::from_array((&*y).try_into().unwrap());
+            (x[0], x[1], x[2], x[3]) = (a * px + c * py + e).unpack();
+            (y[0], y[1], y[2], y[3]) = (b * px + d * py + f).unpack();
+        }
+
+        for (x, y) in xs.into_remainder().iter_mut().zip(ys.into_remainder()) {
+            (*x, *y) = (
+                self.a * *x + self.c * *y + self.e,
+                self.b * *x + self.d * *y + self.f,
+            );
+        }
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Affine {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            a: self.a * rhs.a + self.c * rhs.b,
+            b: self.b * rhs.a + self.d * rhs.b,
+            c: self.a * rhs.c + self.c * rhs.d,
+            d: self.b * rhs.c + self.d * rhs.d,
+            e: self.a * rhs.e + self.c * rhs.f + self.e,
+            f: self.b * rhs.e + self.d * rhs.f + self.f,
+        }
+    }
+}
+
+/// Transforms a vector as a position, so that it is translated as well.
+impl Mul<Vec2> for Affine {
+    type Output = Vec2;
+
+    fn mul(self, rhs: Vec2) -> Self::Output {
+        Vec2::new(
+            self.a * rhs.x() + self.c * rhs.y() + self.e,
+            self.b * rhs.x() + self.d * rhs.y() + self.f,
+        )
+    }
+}
+
+impl Hash for Affine {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for value in [self.a, self.b, self.c, self.d, self.e, self.f] {
+            value.to_bits().hash(state);
+        }
+    }
+}
+
+impl ApproxEq for Affine {
+    fn approx_eq(&self, other: &Self) -> bool {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+            .iter()
+            .zip(&[other.a, other.b, other.c, other.d, other.e, other.f])
+            .all(|(a, b)| a.approx_eq(b))
+    }
+
+    fn approx_eq_within(&self, other: &Self, epsilon: f32) -> bool {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+            .iter()
+            .zip(&[other.a, other.b, other.c, other.d, other.e, other.f])
+            .all(|(a, b)| a.approx_eq_within(b, epsilon))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn compose() {
+        let translate = Affine::translate(Vec2::new(10.0, 0.0));
+        let rotate = Affine::rotate(FRAC_PI_2);
+        let p = Vec2::new(1.0, 0.0);
+
+        assert!((rotate * translate * p).approx_eq(&Vec2::new(0.0, 11.0)));
+        assert!((translate * rotate * p).approx_eq(&Vec2::new(10.0, 1.0)));
+        assert!(translate.then(rotate).approx_eq(&(rotate * translate)));
+
+        let about = Affine::rotate_about(FRAC_PI_2, Vec2::new(1.0, 1.0));
+        assert!((about * Vec2::new(2.0, 1.0)).approx_eq(&Vec2::new(1.0, 2.0)));
+
+        let skew = Affine::skew(FRAC_PI_2 / 2.0, 0.0);
+        assert!((skew * Vec2::new(0.0, 2.0)).approx_eq(&Vec2::new(2.0, 2.0)));
+        assert_eq!(Affine::default(), Affine::IDENTITY);
+    }
+
+    #[test]
+    fn invert() {
+        let t = Affine::translate(Vec2::new(3.0, -2.0))
+            * Affine::rotate(0.7)
+            * Affine::scale(2.0, 0.5)
+            * Affine::skew(0.2, 0.1);
+        let inverse = t.invert().unwrap();
+        assert!((t * inverse).approx_eq(&Affine::IDENTITY));
+        assert!((inverse * t).approx_eq(&Affine::IDENTITY));
+
+        assert!((Affine::scale(2.0, 3.0).scale_factor()).approx_eq(&6f32.sqrt()));
+        assert_eq!(Affine::scale(1.0, 0.0).invert(), None);
+    }
+
+    #[test]
+    fn apply() {
+        let t = Affine::new(1.0, 2.0, 3.0, 4.0, 5.0, 6.0);
+        let mut x = [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let mut y = [7.0, 6.0, 5.0, 4.0, 3.0, 2.0, 1.0];
+        let expected = x
+            .iter()
+            .zip(&y)
+            .map(|(x, y)| t * Vec2::new(*x, *y))
+            .collect::<Vec<_>>();
+
+        // Covers both the vectorized chunks and the remainder.
+        t.apply(&mut x, &mut y);
+        for ((x, y), expected) in x.iter().zip(&y).zip(expected) {
+            assert_eq!(Vec2::new(*x, *y), expected);
+        }
+    }
+}
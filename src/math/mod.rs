@@ -1,3 +1,4 @@
+pub mod affine;
 pub mod cmp;
 pub mod matrix4;
 pub mod ops;
@@ -1,4 +1,10 @@
-use crate::color::Color;
+use std::{f32::consts::TAU, hash::Hash};
+
+use crate::{
+    color::{Color, Space as ColorSpace},
+    math::affine::Affine,
+    shapes::{point::Point, stroke::StrokeStyle},
+};
 
 #[derive(Clone, Copy)]
 pub struct Paint {
@@ -15,4 +21,594 @@ impl Paint {
 pub struct PaintConfig {
     pub fill_color: Color,
     pub stroke_color: Color,
+    /// Fills with a gradient instead of `fill_color`, if set.
+    pub fill_gradient: Option<Gradient>,
+    /// Strokes with a gradient instead of `stroke_color`, if set.
+    pub stroke_gradient: Option<Gradient>,
+    pub fill_rule: FillRule,
+    pub stroke_style: StrokeStyle,
+    pub blend_mode: BlendMode,
+    pub composite_op: CompositeOp,
+}
+
+/// Determines which regions enclosed by a path are considered to be inside of
+/// it, and are therefore filled.
+///
+/// Both rules are defined in terms of the winding number of a point: the number
+/// of times the path winds around it, counting clockwise turns as positive and
+/// counter-clockwise turns as negative.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum FillRule {
+    /// A point is inside the path if its winding number is not zero.
+    #[default]
+    NonZero,
+    /// A point is inside the path if its winding number is odd. Overlapping
+    /// regions of the path alternate between being filled and being empty.
+    EvenOdd,
+}
+
+impl FillRule {
+    /// Determines if a point with the given winding number is filled.
+    ///
+    /// ```rust
+    /// # use shiny::paint::FillRule;
+    /// assert!(FillRule::NonZero.is_filled(2));
+    /// assert!(!FillRule::EvenOdd.is_filled(2));
+    /// assert!(FillRule::EvenOdd.is_filled(-1));
+    /// ```
+    #[must_use]
+    pub fn is_filled(self, winding: i32) -> bool {
+        match self {
+            FillRule::NonZero => winding != 0,
+            FillRule::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+/// A color that varies smoothly across the area being painted, blending from
+/// one color stop to the next.
+///
+/// Each point is assigned a position along the gradient according to its
+/// kind, where 0 is the position of the first stop and 1 is the position of
+/// the last. Positions outside of that range are brought back in according to
+/// the spread mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub kind: GradientKind,
+    /// The colors of the gradient, by increasing offset.
+    pub stops: Vec<ColorStop>,
+    pub spread: Spread,
+    /// The transform from the gradient's coordinates to those of the paths
+    /// that it is drawn with.
+    pub transform: Affine,
+    /// The color space in which colors between stops are interpolated.
+    pub interpolation: ColorSpace,
+}
+
+impl Gradient {
+    /// A gradient that varies along the line from `start` to `end`.
+    #[must_use]
+    pub fn linear(start: Point, end: Point, stops: Vec<ColorStop>) -> Self {
+        Self::new(GradientKind::Linear { start, end }, stops)
+    }
+
+    /// A gradient that varies from the center of a circle to its edge.
+    #[must_use]
+    pub fn radial(center: Point, radius: f32, stops: Vec<ColorStop>) -> Self {
+        Self::new(
+            GradientKind::Radial {
+                center,
+                radius,
+                focus: center,
+            },
+            stops,
+        )
+    }
+
+    /// A gradient that varies with the angle around `center`, starting at
+    /// `angle` radians and continuing clockwise.
+    #[must_use]
+    pub fn conic(center: Point, angle: f32, stops: Vec<ColorStop>) -> Self {
+        Self::new(GradientKind::Conic { center, angle }, stops)
+    }
+
+    fn new(kind: GradientKind, stops: Vec<ColorStop>) -> Self {
+        Self {
+            kind,
+            stops,
+            spread: Spread::Pad,
+            transform: Affine::IDENTITY,
+            interpolation: ColorSpace::Srgb,
+        }
+    }
+}
+
+impl Hash for Gradient {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.stops.hash(state);
+        self.spread.hash(state);
+        self.transform.hash(state);
+        self.interpolation.hash(state);
+    }
+}
+
+/// The shape of a gradient.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientKind {
+    /// Colors vary along the line from `start` to `end`, and are constant
+    /// along lines perpendicular to it.
+    Linear { start: Point, end: Point },
+    /// Colors vary from `focus` to the edge of the circle, and are constant
+    /// along circles that grow from the focus to the edge.
+    Radial {
+        center: Point,
+        radius: f32,
+        focus: Point,
+    },
+    /// Colors vary with the angle around `center`, starting at `angle`
+    /// radians.
+    Conic { center: Point, angle: f32 },
+}
+
+impl GradientKind {
+    /// Finds the position along the gradient of `point`, which is in the
+    /// gradient's coordinates, before the spread mode is applied. Returns
+    /// `None` if the gradient is degenerate, in which case it is drawn with
+    /// the color of its last stop.
+    #[must_use]
+    pub fn offset_at(&self, point: Point) -> Option<f32> {
+        match *self {
+            GradientKind::Linear { start, end } => {
+                let direction = end - start;
+                let length_squared = direction.dot(direction);
+                if length_squared > 0.0 {
+                    Some((point - start).dot(direction) / length_squared)
+                } else {
+                    None
+                }
+            }
+            GradientKind::Radial {
+                center,
+                radius,
+                focus,
+            } => {
+                if radius <= 0.0 {
+                    return None;
+                }
+
+                // Foci on or outside of the circle produce cones that don't
+                // cover the plane, so they are moved just inside of it.
+                let max_distance = radius * 0.999;
+                let focus_offset = focus - center;
+                let focus_distance = focus_offset.length();
+                let focus = if focus_distance > max_distance {
+                    center + focus_offset * (max_distance / focus_distance)
+                } else {
+                    focus
+                };
+
+                // Solves for the circle at `t`, interpolated between a circle
+                // of radius 0 at the focus and the gradient's circle, that
+                // passes through the point.
+                let d = center - focus;
+                let q = point - focus;
+                let a = d.dot(d) - radius * radius;
+                let b = q.dot(d);
+                let c = q.dot(q);
+                Some((b - (b * b - a * c).max(0.0).sqrt()) / a)
+            }
+            GradientKind::Conic { center, angle } => {
+                let offset = point - center;
+                Some(((offset.y().atan2(offset.x()) - angle) / TAU).rem_euclid(1.0))
+            }
+        }
+    }
+}
+
+impl Hash for GradientKind {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            GradientKind::Linear { start, end } => {
+                start.hash(state);
+                end.hash(state);
+            }
+            GradientKind::Radial {
+                center,
+                radius,
+                focus,
+            } => {
+                center.hash(state);
+                radius.to_bits().hash(state);
+                focus.hash(state);
+            }
+            GradientKind::Conic { center, angle } => {
+                center.hash(state);
+                angle.to_bits().hash(state);
+            }
+        }
+    }
+}
+
+/// A color at a position along a gradient, between 0 and 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+impl ColorStop {
+    #[must_use]
+    pub fn new(offset: f32, color: Color) -> Self {
+        Self { offset, color }
+    }
+}
+
+impl Hash for ColorStop {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.offset.to_bits().hash(state);
+        self.color.hash(state);
+    }
+}
+
+/// Determines the color of points that lie beyond the ends of a gradient.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum Spread {
+    /// The colors of the first and last stops extend outwards.
+    #[default]
+    Pad,
+    /// The gradient repeats from the start.
+    Repeat,
+    /// The gradient repeats, alternating in direction each time.
+    Reflect,
+}
+
+impl Spread {
+    /// Brings a position along a gradient into the range `[0, 1]`.
+    ///
+    /// ```rust
+    /// # use shiny::paint::Spread;
+    /// assert_eq!(Spread::Pad.apply(1.25), 1.0);
+    /// assert_eq!(Spread::Repeat.apply(1.25), 0.25);
+    /// assert_eq!(Spread::Reflect.apply(1.25), 0.75);
+    /// ```
+    #[must_use]
+    pub fn apply(self, offset: f32) -> f32 {
+        match self {
+            Spread::Pad => offset.clamp(0.0, 1.0),
+            Spread::Repeat => offset - offset.floor(),
+            Spread::Reflect => {
+                let offset = offset.rem_euclid(2.0);
+                if offset > 1.0 {
+                    2.0 - offset
+                } else {
+                    offset
+                }
+            }
+        }
+    }
+}
+
+/// The Porter-Duff operators, which determine how a shape's colors (the source)
+/// are combined with the colors already drawn beneath it (the destination).
+///
+/// Each operator weighs the source and destination by a factor that depends on
+/// their alphas, and sums them.
+///
+/// Operators are bounded by the shape: only the pixels it covers are affected,
+/// in proportion to how much of each it covers. This differs from the
+/// unbounded operators of the HTML canvas, which treat everything outside of
+/// the shape as a transparent source. `Clear`, `Source`, `SourceIn`,
+/// `DestinationIn` and `DestinationAtop` would erase the destination outside
+/// of the shape if they were unbounded, but here they leave it alone.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum CompositeOp {
+    /// Neither the source nor the destination is kept within the shape.
+    Clear,
+    /// Only the source is kept within the shape.
+    Source,
+    /// Only the destination is kept.
+    Destination,
+    /// The source is drawn over the destination.
+    #[default]
+    SourceOver,
+    /// The destination is drawn over the source.
+    DestinationOver,
+    /// The source is kept where it overlaps the destination. The destination
+    /// outside of the shape is left alone.
+    SourceIn,
+    /// The destination is kept where it overlaps the source. The destination
+    /// outside of the shape is left alone.
+    DestinationIn,
+    /// The source is kept where it doesn't overlap the destination.
+    SourceOut,
+    /// The destination is kept where it doesn't overlap the source.
+    DestinationOut,
+    /// The source is drawn over the destination, but only where they overlap.
+    SourceAtop,
+    /// The destination is drawn over the source, but only where they overlap.
+    /// The destination outside of the shape is left alone.
+    DestinationAtop,
+    /// The source and destination are kept where they don't overlap.
+    Xor,
+}
+
+impl CompositeOp {
+    /// The factors that the premultiplied source and destination colors are
+    /// multiplied by before they are summed, given the source's alpha and the
+    /// destination's alpha.
+    ///
+    /// ```rust
+    /// # use shiny::paint::CompositeOp;
+    /// assert_eq!(CompositeOp::SourceOver.factors(0.5, 1.0), (1.0, 0.5));
+    /// assert_eq!(CompositeOp::Xor.factors(1.0, 1.0), (0.0, 0.0));
+    /// ```
+    #[must_use]
+    pub fn factors(self, src_alpha: f32, dst_alpha: f32) -> (f32, f32) {
+        match self {
+            CompositeOp::Clear => (0.0, 0.0),
+            CompositeOp::Source => (1.0, 0.0),
+            CompositeOp::Destination => (0.0, 1.0),
+            CompositeOp::SourceOver => (1.0, 1.0 - src_alpha),
+            CompositeOp::DestinationOver => (1.0 - dst_alpha, 1.0),
+            CompositeOp::SourceIn => (dst_alpha, 0.0),
+            CompositeOp::DestinationIn => (0.0, src_alpha),
+            CompositeOp::SourceOut => (1.0 - dst_alpha, 0.0),
+            CompositeOp::DestinationOut => (0.0, 1.0 - src_alpha),
+            CompositeOp::SourceAtop => (dst_alpha, 1.0 - src_alpha),
+            CompositeOp::DestinationAtop => (1.0 - dst_alpha, src_alpha),
+            CompositeOp::Xor => (1.0 - dst_alpha, 1.0 - src_alpha),
+        }
+    }
+}
+
+/// The blend modes from the W3C Compositing and Blending specification, which
+/// determine the color that results from drawing a source color over a
+/// backdrop color, before the two are composited together.
+///
+/// The separable modes treat each color channel independently. The others
+/// (`Hue`, `Saturation`, `Color`, and `Luminosity`) mix properties of the
+/// source and backdrop colors as a whole.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum BlendMode {
+    /// The source color replaces the backdrop.
+    #[default]
+    Normal,
+    /// Multiplies the colors, which is at least as dark as either of them.
+    Multiply,
+    /// Multiplies the complements of the colors, which is at least as light as
+    /// either of them.
+    Screen,
+    /// Multiplies or screens the colors, depending on the backdrop.
+    Overlay,
+    /// Keeps the darker of the colors.
+    Darken,
+    /// Keeps the lighter of the colors.
+    Lighten,
+    /// Brightens the backdrop to reflect the source.
+    ColorDodge,
+    /// Darkens the backdrop to reflect the source.
+    ColorBurn,
+    /// Multiplies or screens the colors, depending on the source.
+    HardLight,
+    /// Darkens or lightens the colors, depending on the source.
+    SoftLight,
+    /// Subtracts the darker of the colors from the lighter one.
+    Difference,
+    /// Like `Difference`, but with lower contrast.
+    Exclusion,
+    /// The hue of the source with the saturation and luminosity of the
+    /// backdrop.
+    Hue,
+    /// The saturation of the source with the hue and luminosity of the
+    /// backdrop.
+    Saturation,
+    /// The hue and saturation of the source with the luminosity of the
+    /// backdrop.
+    Color,
+    /// The luminosity of the source with the hue and saturation of the
+    /// backdrop.
+    Luminosity,
+}
+
+impl BlendMode {
+    /// Blends the straight (not premultiplied) RGB components of a source
+    /// color with those of the backdrop. Components are clamped to `[0, 1]`.
+    ///
+    /// ```rust
+    /// # use shiny::paint::BlendMode;
+    /// assert_eq!(BlendMode::Multiply.blend([0.5, 1.0, 0.0], [0.5, 0.5, 1.0]), [0.25, 0.5, 0.0]);
+    /// assert_eq!(BlendMode::Screen.blend([0.5, 0.0, 1.0], [0.5, 1.0, 0.0]), [0.75, 1.0, 1.0]);
+    /// ```
+    #[must_use]
+    pub fn blend(self, backdrop: [f32; 3], source: [f32; 3]) -> [f32; 3] {
+        let b = backdrop.map(|c| c.clamp(0.0, 1.0));
+        let s = source.map(|c| c.clamp(0.0, 1.0));
+        let separable = |f: fn(f32, f32) -> f32| [f(b[0], s[0]), f(b[1], s[1]), f(b[2], s[2])];
+
+        match self {
+            BlendMode::Normal => s,
+            BlendMode::Multiply => separable(|b, s| b * s),
+            BlendMode::Screen => separable(screen),
+            BlendMode::Overlay => separable(|b, s| hard_light(s, b)),
+            BlendMode::Darken => separable(f32::min),
+            BlendMode::Lighten => separable(f32::max),
+            BlendMode::ColorDodge => separable(|b, s| {
+                if b == 0.0 {
+                    0.0
+                } else if s == 1.0 {
+                    1.0
+                } else {
+                    (b / (1.0 - s)).min(1.0)
+                }
+            }),
+            BlendMode::ColorBurn => separable(|b, s| {
+                if b == 1.0 {
+                    1.0
+                } else if s == 0.0 {
+                    0.0
+                } else {
+                    1.0 - ((1.0 - b) / s).min(1.0)
+                }
+            }),
+            BlendMode::HardLight => separable(hard_light),
+            BlendMode::SoftLight => separable(|b, s| {
+                if s <= 0.5 {
+                    b - (1.0 - 2.0 * s) * b * (1.0 - b)
+                } else {
+                    let d = if b <= 0.25 {
+                        ((16.0 * b - 12.0) * b + 4.0) * b
+                    } else {
+                        b.sqrt()
+                    };
+                    b + (2.0 * s - 1.0) * (d - b)
+                }
+            }),
+            BlendMode::Difference => separable(|b, s| (b - s).abs()),
+            BlendMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+            BlendMode::Hue => set_lum(set_sat(s, sat(b)), lum(b)),
+            BlendMode::Saturation => set_lum(set_sat(b, sat(s)), lum(b)),
+            BlendMode::Color => set_lum(s, lum(b)),
+            BlendMode::Luminosity => set_lum(b, lum(s)),
+        }
+    }
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b * 2.0 * s
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+/// The luminosity of a color, as defined by the W3C blending spec.
+fn lum([r, g, b]: [f32; 3]) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+/// Shifts a color to the given luminosity, then brings components that fall
+/// outside of `[0, 1]` back in while preserving the luminosity.
+fn set_lum(color: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(color);
+    let color = color.map(|c| c + d);
+
+    let l = lum(color);
+    let n = color[0].min(color[1]).min(color[2]);
+    let x = color[0].max(color[1]).max(color[2]);
+    color.map(|mut c| {
+        if n < 0.0 {
+            c = l + (c - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            c = l + (c - l) * (1.0 - l) / (x - l);
+        }
+        c
+    })
+}
+
+/// The saturation of a color, as defined by the W3C blending spec.
+fn sat([r, g, b]: [f32; 3]) -> f32 {
+    r.max(g).max(b) - r.min(g).min(b)
+}
+
+/// Scales a color's components so that its saturation is `s`, keeping the
+/// order of the components.
+fn set_sat(color: [f32; 3], s: f32) -> [f32; 3] {
+    let mut order = [0, 1, 2];
+    order.sort_by(|a, b| color[*a].total_cmp(&color[*b]));
+    let [min, mid, max] = order;
+
+    let mut result = [0.0; 3];
+    if color[max] > color[min] {
+        result[mid] = (color[mid] - color[min]) * s / (color[max] - color[min]);
+        result[max] = s;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::cmp::ApproxEq;
+
+    fn assert_blend(mode: BlendMode, backdrop: [f32; 3], source: [f32; 3], expected: [f32; 3]) {
+        let actual = mode.blend(backdrop, source);
+        assert!(
+            actual
+                .iter()
+                .zip(&expected)
+                .all(|(a, e)| a.approx_eq_within(e, 1e-4)),
+            "{mode:?}: {actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn separable_blend_modes() {
+        let b = [0.2, 0.5, 0.8];
+        let s = [0.6, 0.5, 0.1];
+        assert_blend(BlendMode::Normal, b, s, s);
+        assert_blend(BlendMode::Multiply, b, s, [0.12, 0.25, 0.08]);
+        assert_blend(BlendMode::Screen, b, s, [0.68, 0.75, 0.82]);
+        assert_blend(BlendMode::Overlay, b, s, [0.24, 0.5, 0.64]);
+        assert_blend(BlendMode::Darken, b, s, [0.2, 0.5, 0.1]);
+        assert_blend(BlendMode::Lighten, b, s, [0.6, 0.5, 0.8]);
+        assert_blend(BlendMode::ColorDodge, b, s, [0.5, 1.0, 0.8 / 0.9]);
+        assert_blend(BlendMode::ColorBurn, b, s, [0.0, 0.0, 0.0]);
+        assert_blend(BlendMode::HardLight, b, s, [0.36, 0.5, 0.16]);
+        assert_blend(BlendMode::SoftLight, b, s, [0.2496, 0.5, 0.672]);
+        assert_blend(BlendMode::Difference, b, s, [0.4, 0.0, 0.7]);
+        assert_blend(BlendMode::Exclusion, b, s, [0.56, 0.5, 0.74]);
+
+        // Edge cases that would otherwise divide by zero.
+        assert_blend(
+            BlendMode::ColorDodge,
+            [0.0, 0.5, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        );
+        assert_blend(
+            BlendMode::ColorBurn,
+            [1.0, 0.5, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn non_separable_blend_modes() {
+        let red = [1.0, 0.0, 0.0];
+        let gray = [0.5, 0.5, 0.5];
+        let dark_blue = [0.0, 0.0, 0.5];
+
+        // Gray has no hue or saturation to give.
+        assert_blend(BlendMode::Hue, gray, red, gray);
+        assert_blend(BlendMode::Saturation, gray, red, gray);
+        assert_blend(BlendMode::Hue, red, gray, [0.3, 0.3, 0.3]);
+
+        // Red at the luminosity of gray is out of range, so it is clipped back
+        // in while keeping the luminosity.
+        let color = BlendMode::Color.blend(gray, red);
+        assert!((0.3 * color[0] + 0.59 * color[1] + 0.11 * color[2]).approx_eq(&0.5));
+        assert!(color.iter().all(|c| (0.0..=1.0).contains(c)));
+        assert!(color[0] > color[1] && color[1].approx_eq(&color[2]));
+
+        assert_blend(
+            BlendMode::Luminosity,
+            dark_blue,
+            gray,
+            [0.4450, 0.4450, 0.9450],
+        );
+        assert_blend(
+            BlendMode::Saturation,
+            [0.2, 0.4, 0.6],
+            red,
+            [0.0, 0.44691, 0.89383],
+        );
+    }
 }
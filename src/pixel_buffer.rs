@@ -3,6 +3,7 @@ use std::rc::Rc;
 use crate::{
     color::{Color, Space as ColorSpace},
     image::{Error as ImageError, Image, PixelFormat},
+    paint::{BlendMode, CompositeOp},
 };
 
 /// A copy-on-write buffer of pixels.
@@ -38,6 +39,28 @@ impl PixelBuffer {
         }
     }
 
+    /// Creates a transparent buffer in the same color space as this one, for
+    /// drawing that will later be composited onto it. The buffer has at least
+    /// as many bits per channel as this one, and a full alpha channel so that
+    /// partial coverage survives until it is composited.
+    pub(crate) fn new_layer(&self, width: u32, height: u32) -> Self {
+        // `new()` rejects formats with more bits per channel than the color
+        // space needs, which Rgba16 always has, so the buffer is made directly.
+        let format = if self.pixel_format().bits_per_channel() <= 8 {
+            PixelFormat::Rgba8
+        } else {
+            PixelFormat::Rgba16
+        };
+        Self {
+            raw: Rc::new(RawPixelBuffer::new(
+                width,
+                height,
+                format,
+                self.color_space(),
+            )),
+        }
+    }
+
     pub fn iter(&self) -> PixelBufferIter {
         PixelBufferIter {
             buffer: self.raw.clone(),
@@ -64,6 +87,33 @@ impl PixelBuffer {
         }
     }
 
+    /// Composites `color` over a single pixel according to the color's alpha,
+    /// copying the buffer if other owning references exist.
+    pub fn blend(&mut self, x: u32, y: u32, color: Color) {
+        self.composite(x, y, color, 1.0, BlendMode::Normal, CompositeOp::SourceOver);
+    }
+
+    /// Blends `color` with a single pixel using `blend_mode`, then combines
+    /// them using `op`, copying the buffer if other owning references exist.
+    /// `coverage` is the fraction of the pixel covered by the shape being
+    /// drawn, and blends between the pixel's original color and the result.
+    ///
+    /// Compositing is done with premultiplied alpha in the linear version of
+    /// the buffer's color space, if there is one.
+    pub fn composite(
+        &mut self,
+        x: u32,
+        y: u32,
+        color: Color,
+        coverage: f32,
+        blend_mode: BlendMode,
+        op: CompositeOp,
+    ) {
+        if (x < self.width()) & (y < self.height()) {
+            Rc::make_mut(&mut self.raw).composite(x, y, color, coverage, blend_mode, op);
+        }
+    }
+
     pub fn clear(&mut self, color: Color) {
         Rc::make_mut(&mut self.raw).clear(color);
     }
@@ -178,6 +228,68 @@ impl RawPixelBuffer {
         );
     }
 
+    pub fn composite(
+        &mut self,
+        x: u32,
+        y: u32,
+        color: Color,
+        coverage: f32,
+        blend_mode: BlendMode,
+        op: CompositeOp,
+    ) {
+        let offset = self.offset_of(x, y);
+        let space = self.color_space.linear().unwrap_or(self.color_space);
+        let mut src = color.in_color_space(self.color_space).in_color_space(space);
+        let dst = self
+            .format
+            .read_color(&self.bytes[offset..])
+            .in_color_space(self.color_space)
+            .in_color_space(space);
+
+        // The source is blended with the destination in proportion to how
+        // opaque the destination is.
+        if blend_mode != BlendMode::Normal && dst.a > 0.0 {
+            let [r, g, b] = blend_mode.blend([dst.r, dst.g, dst.b], [src.r, src.g, src.b]);
+            src.r += (r - src.r) * dst.a;
+            src.g += (g - src.g) * dst.a;
+            src.b += (b - src.b) * dst.a;
+        }
+
+        let src = premultiply(src);
+        let dst = premultiply(dst);
+
+        let (src_factor, dst_factor) = op.factors(src[3], dst[3]);
+        let mut result = [0.0; 4];
+        for i in 0..4 {
+            let composited = src[i] * src_factor + dst[i] * dst_factor;
+            result[i] = dst[i] + (composited - dst[i]) * coverage;
+        }
+
+        let [r, g, b, a] = result;
+        let composited = if a > 0.0 {
+            Color {
+                r: r / a,
+                g: g / a,
+                b: b / a,
+                a,
+                space,
+            }
+        } else {
+            Color {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                a: 0.0,
+                space,
+            }
+        };
+
+        self.format.write_color(
+            composited.in_color_space(self.color_space),
+            &mut self.bytes[offset..],
+        );
+    }
+
     pub fn clear(&mut self, color: Color) {
         for i in (0..self.bytes.len()).step_by(self.format.bytes_per_pixel()) {
             self.format
@@ -211,3 +323,153 @@ impl RawPixelBuffer {
             + self.format.bytes_per_pixel() * usize::try_from(x).unwrap()
     }
 }
+
+/// Converts a color with straight alpha into its premultiplied components.
+fn premultiply(color: Color) -> [f32; 4] {
+    [
+        color.r * color.a,
+        color.g * color.a,
+        color.b * color.a,
+        color.a,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Composites `src` onto a single pixel of `dst`, returning the result.
+    fn composite(dst: Color, src: Color, coverage: f32, op: CompositeOp) -> Color {
+        let mut pixels =
+            PixelBuffer::new(1, 1, PixelFormat::Rgba8, ColorSpace::LinearSrgb).unwrap();
+        pixels.set(0, 0, dst);
+        pixels.composite(0, 0, src, coverage, BlendMode::Normal, op);
+        pixels.get(0, 0)
+    }
+
+    fn assert_color(color: Color, expected: [f32; 4]) {
+        let actual = [color.r, color.g, color.b, color.a];
+        assert!(
+            actual
+                .iter()
+                .zip(&expected)
+                .all(|(a, e)| (a - e).abs() < 0.01),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let red = Color::RED;
+        let half_blue = Color {
+            a: 0.5,
+            ..Color::BLUE
+        };
+        let clear = Color {
+            a: 0.0,
+            ..Color::BLACK
+        };
+
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::SourceOver),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::DestinationOver),
+            [1.0, 0.0, 0.0, 1.0],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::Source),
+            [0.0, 0.0, 1.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::Destination),
+            [1.0, 0.0, 0.0, 1.0],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::SourceIn),
+            [0.0, 0.0, 1.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::DestinationIn),
+            [1.0, 0.0, 0.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::SourceOut),
+            [0.0, 0.0, 0.0, 0.0],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::DestinationOut),
+            [1.0, 0.0, 0.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::SourceAtop),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::DestinationAtop),
+            [1.0, 0.0, 0.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::Xor),
+            [1.0, 0.0, 0.0, 0.5],
+        );
+        assert_color(
+            composite(red, half_blue, 1.0, CompositeOp::Clear),
+            [0.0, 0.0, 0.0, 0.0],
+        );
+
+        // Operators apply in proportion to coverage.
+        assert_color(
+            composite(red, Color::BLUE, 0.5, CompositeOp::SourceOver),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_color(
+            composite(red, half_blue, 0.5, CompositeOp::Clear),
+            [1.0, 0.0, 0.0, 0.5],
+        );
+        assert_color(
+            composite(clear, half_blue, 1.0, CompositeOp::SourceIn),
+            [0.0, 0.0, 0.0, 0.0],
+        );
+        assert_color(
+            composite(clear, half_blue, 1.0, CompositeOp::SourceOut),
+            [0.0, 0.0, 1.0, 0.5],
+        );
+    }
+
+    #[test]
+    fn rec2020_compositing() {
+        let mut pixels = PixelBuffer::new(1, 1, PixelFormat::Rgba8, ColorSpace::Rec2020).unwrap();
+        pixels.set(0, 0, Color::srgba(0.0, 0.0, 0.0, 1.0));
+        pixels.blend(0, 0, Color::srgba(1.0, 0.0, 0.0, 0.5));
+
+        // Rec. 2020 has no linear counterpart, so the colors are composited
+        // as they are encoded.
+        let red = Color::srgba(1.0, 0.0, 0.0, 1.0).in_color_space(ColorSpace::Rec2020);
+        assert_color(
+            pixels.get(0, 0),
+            [red.r * 0.5, red.g * 0.5, red.b * 0.5, 1.0],
+        );
+    }
+
+    #[test]
+    fn linear_compositing() {
+        let mut pixels = PixelBuffer::new(1, 1, PixelFormat::Rgba8, ColorSpace::Srgb).unwrap();
+        pixels.set(0, 0, Color::BLACK);
+        pixels.blend(
+            0,
+            0,
+            Color {
+                a: 0.5,
+                ..Color::WHITE
+            },
+        );
+
+        // Half of the light of white is much brighter than half of its sRGB
+        // encoding.
+        let color = pixels.get(0, 0);
+        assert!((color.r - 0.735).abs() < 0.01, "{color:?}");
+        assert_eq!(color.a, 1.0);
+    }
+}
@@ -4,10 +4,14 @@ use crate::{
         cmp::{max, min, ApproxEq},
         simd::Float4,
     },
-    shapes::{bezier::Bezier, line::Line},
+    shapes::{bezier::Bezier, line::Line, rect::Rect},
     utils::arrayvec::ArrayVec,
 };
 
+/// The distance, relative to the magnitude of their coordinates, below which
+/// the bounds of two curves are considered to touch.
+const SEPARATION_EPSILON: f32 = 1e-5;
+
 /// Calculates the t-value for every intersection between the two curves `a` and
 /// `b`.
 #[must_use]
@@ -135,6 +139,11 @@ fn find_intersections_in_range(
                 intersections_right.push(b.start);
             }
             break;
+        } else if are_separated(a.get().coarse_bounds(), b.get().coarse_bounds()) {
+            // Clipping can leave behind parts of the curves that lie nowhere
+            // near each other, particularly when one of them collapses to a
+            // point. Such parts can't intersect.
+            break;
         } else if proportion_remaining > 0.8 {
             // The clip did not result in a significant reduction in the curve's
             // length, so split the longest curve in half and look for
@@ -155,6 +164,28 @@ fn find_intersections_in_range(
     }
 }
 
+/// Checks whether two bounding boxes are separated by more than rounding error.
+/// Curves that meet on an axis-aligned line can have bounds that miss each
+/// other by a few ulps.
+fn are_separated(a: Rect, b: Rect) -> bool {
+    let magnitude = max!(
+        a.left.abs(),
+        a.right.abs(),
+        a.top.abs(),
+        a.bottom.abs(),
+        b.left.abs(),
+        b.right.abs(),
+        b.top.abs(),
+        b.bottom.abs()
+    );
+    let margin = magnitude * SEPARATION_EPSILON;
+
+    a.left > b.right + margin
+        || b.left > a.right + margin
+        || a.top > b.bottom + margin
+        || b.top > a.bottom + margin
+}
+
 /// Clips `a` against `b`, producing t-bounds where `a` lies within `b`'s fat
 /// line.
 fn clip(curve: CubicSlice, against: CubicSlice) -> (f32, f32) {
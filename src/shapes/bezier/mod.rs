@@ -4,21 +4,57 @@ use super::{
 };
 use crate::{
     math::{
+        affine::Affine,
         matrix4::{Mat1x4, Mat4x2, Mat4x4},
         ops::Interpolate,
         simd::Float4,
+        vector2::Vec2,
     },
     utils::arrayvec::ArrayVec,
 };
 
 mod intersection;
 
+/// The relative error permitted when measuring the length of a curve.
+const ARC_LENGTH_PRECISION: f32 = 1e-5;
+
+/// The maximum number of times an interval will be halved when measuring the
+/// length of a curve.
+const MAX_ARC_LENGTH_DEPTH: u32 = 8;
+
+/// The maximum number of refinement steps taken when searching for the
+/// parameter at a given length along a curve.
+const MAX_ARC_LENGTH_ITERATIONS: u32 = 16;
+
+/// Abscissae and weights for 5-point Gauss-Legendre quadrature over [-1, 1].
+const GAUSS_LEGENDRE_5: [(f32, f32); 5] = [
+    (0.0, 0.568_888_9),
+    (-0.538_469_3, 0.478_628_67),
+    (0.538_469_3, 0.478_628_67),
+    (-0.906_179_85, 0.236_926_88),
+    (0.906_179_85, 0.236_926_88),
+];
+
 pub trait Bezier: Sized + BoundingBox {
     type Owning;
 
     #[must_use]
     fn at(&self, t: f32) -> Point;
 
+    /// The first derivative of the curve at `t`. Its direction is the tangent
+    /// of the curve at that point.
+    #[must_use]
+    fn derivative(&self, t: f32) -> Vec2;
+
+    /// The length of the curve.
+    #[must_use]
+    fn arc_length(&self) -> f32;
+
+    /// Finds the parameter at which the length of the curve measured from its
+    /// start is `length`. Lengths outside of the curve are clamped to its ends.
+    #[must_use]
+    fn t_at_arc_length(&self, length: f32) -> f32;
+
     #[must_use]
     fn p0(&self) -> Point;
 
@@ -65,9 +101,15 @@ impl Cubic {
     }
 
     #[must_use]
-    pub fn as_slice(&self) -> CubicSlice {
+    pub fn as_slice(&self) -> CubicSlice<'_> {
         CubicSlice::new(&self.x, &self.y)
     }
+
+    /// Applies `transform` to the control points of the curve. The curve is
+    /// transformed exactly, since affine transforms preserve bezier curves.
+    pub fn transform(&mut self, transform: &Affine) {
+        transform.apply(&mut self.x, &mut self.y);
+    }
 }
 
 impl Bezier for Cubic {
@@ -78,6 +120,21 @@ impl Bezier for Cubic {
         evaluate(self.as_slice(), t)
     }
 
+    #[inline]
+    fn derivative(&self, t: f32) -> Vec2 {
+        derivative(self.as_slice(), t)
+    }
+
+    #[inline]
+    fn arc_length(&self) -> f32 {
+        arc_length(self.as_slice(), 0.0, 1.0)
+    }
+
+    #[inline]
+    fn t_at_arc_length(&self, length: f32) -> f32 {
+        t_at_arc_length(self.as_slice(), length)
+    }
+
     #[inline]
     fn p0(&self) -> Point {
         Point::new(self.x[0], self.y[0])
@@ -174,6 +231,21 @@ impl<'a> Bezier for CubicSlice<'a> {
         evaluate(*self, t)
     }
 
+    #[inline]
+    fn derivative(&self, t: f32) -> Vec2 {
+        derivative(*self, t)
+    }
+
+    #[inline]
+    fn arc_length(&self) -> f32 {
+        arc_length(*self, 0.0, 1.0)
+    }
+
+    #[inline]
+    fn t_at_arc_length(&self, length: f32) -> f32 {
+        t_at_arc_length(*self, length)
+    }
+
     #[inline]
     fn p0(&self) -> Point {
         Point::new(self.x[0], self.y[0])
@@ -246,6 +318,86 @@ fn evaluate(curve: CubicSlice, t: f32) -> Point {
     Point::new(tmp.x(), tmp.y())
 }
 
+fn derivative(curve: CubicSlice, t: f32) -> Vec2 {
+    let mt = 1.0 - t;
+    let a = 3.0 * mt * mt;
+    let b = 6.0 * mt * t;
+    let c = 3.0 * t * t;
+
+    let x = a * (curve.x[1] - curve.x[0])
+        + b * (curve.x[2] - curve.x[1])
+        + c * (curve.x[3] - curve.x[2]);
+    let y = a * (curve.y[1] - curve.y[0])
+        + b * (curve.y[2] - curve.y[1])
+        + c * (curve.y[3] - curve.y[2]);
+    Vec2::new(x, y)
+}
+
+/// Measures the length of the curve between `t0` and `t1` by integrating its
+/// speed, halving the interval until the estimate stops changing.
+fn arc_length(curve: CubicSlice, t0: f32, t1: f32) -> f32 {
+    fn quadrature(curve: CubicSlice, t0: f32, t1: f32) -> f32 {
+        let half_width = (t1 - t0) * 0.5;
+        let center = t0 + half_width;
+        GAUSS_LEGENDRE_5
+            .iter()
+            .map(|(x, w)| w * derivative(curve, center + x * half_width).length())
+            .sum::<f32>()
+            * half_width
+    }
+
+    fn refine(curve: CubicSlice, t0: f32, t1: f32, whole: f32, depth: u32) -> f32 {
+        let mid = (t0 + t1) * 0.5;
+        let left = quadrature(curve, t0, mid);
+        let right = quadrature(curve, mid, t1);
+
+        if depth >= MAX_ARC_LENGTH_DEPTH
+            || (left + right - whole).abs() <= ARC_LENGTH_PRECISION * (left + right)
+        {
+            left + right
+        } else {
+            refine(curve, t0, mid, left, depth + 1) + refine(curve, mid, t1, right, depth + 1)
+        }
+    }
+
+    refine(curve, t0, t1, quadrature(curve, t0, t1), 0)
+}
+
+/// Inverts [`arc_length`] with Newton's method, falling back to bisection
+/// whenever a step would leave the interval known to contain the solution.
+fn t_at_arc_length(curve: CubicSlice, length: f32) -> f32 {
+    let total = arc_length(curve, 0.0, 1.0);
+    if length <= 0.0 {
+        return 0.0;
+    } else if length >= total {
+        return 1.0;
+    }
+
+    let (mut low, mut high) = (0.0, 1.0);
+    let mut t = length / total;
+    for _ in 0..MAX_ARC_LENGTH_ITERATIONS {
+        let error = arc_length(curve, 0.0, t) - length;
+        if error.abs() <= ARC_LENGTH_PRECISION * total {
+            break;
+        }
+
+        if error > 0.0 {
+            high = t;
+        } else {
+            low = t;
+        }
+
+        let next = t - error / derivative(curve, t).length();
+        t = if next > low && next < high {
+            next
+        } else {
+            (low + high) * 0.5
+        };
+    }
+
+    t
+}
+
 fn coarse_bounds(curve: CubicSlice) -> Rect {
     let (min, max) = Float4::horizontal_min_max4(
         curve.x.into(),
@@ -306,11 +458,11 @@ fn split2(curve: CubicSlice, t1: f32, t2: f32) -> (Cubic, Cubic, Cubic) {
     (left, mid, right)
 }
 
-fn splitn<'a, 'b>(
+fn splitn(
     curve: CubicSlice,
     mut t: impl Iterator<Item = f32>,
-    buffer_x: &'a mut Vec<f32>,
-    buffer_y: &'b mut Vec<f32>,
+    buffer_x: &mut Vec<f32>,
+    buffer_y: &mut Vec<f32>,
 ) {
     if let Some(first_split) = t.next() {
         let mut prev_t = 0.0;
@@ -361,6 +513,46 @@ mod tests {
         assert!(bezier.at(1.0).approx_eq(&Point::new(6.0, 15.0)));
     }
 
+    #[test]
+    fn derivative() {
+        let bezier = Cubic {
+            x: [10.0, 3.0, 12.0, 6.0],
+            y: [5.0, 11.0, 20.0, 15.0],
+        };
+
+        assert!(bezier.derivative(0.0).approx_eq(&Vec2::new(-21.0, 18.0)));
+        assert!(bezier.derivative(0.5).approx_eq(&Vec2::new(3.75, 14.25)));
+        assert!(bezier.derivative(1.0).approx_eq(&Vec2::new(-18.0, -15.0)));
+    }
+
+    #[test]
+    fn arc_length() {
+        // Control points of a line need not be evenly spaced.
+        let line = Cubic {
+            x: [0.0, 3.0, 27.0, 30.0],
+            y: [0.0, 4.0, 36.0, 40.0],
+        };
+        assert!(line.arc_length().approx_eq_within(&50.0, 1e-3));
+
+        for length in [0.0, 10.0, 25.0, 42.0, 50.0] {
+            let t = line.t_at_arc_length(length);
+            let distance = (line.at(t) - line.p0()).length();
+            assert!(distance.approx_eq_within(&length, 1e-3));
+        }
+
+        // A quarter circle with a radius of 100.
+        const K: f32 = 55.228_474;
+        let arc = Cubic {
+            x: [100.0, 100.0, K, 0.0],
+            y: [0.0, K, 100.0, 100.0],
+        };
+        let quarter = std::f32::consts::FRAC_PI_2 * 100.0;
+        assert!((arc.arc_length() / quarter).approx_eq_within(&1.0, 1e-3));
+
+        let halfway = arc.at(arc.t_at_arc_length(arc.arc_length() * 0.5));
+        assert!(halfway.x.approx_eq_within(&halfway.y, 1e-3));
+    }
+
     #[test]
     fn coarse_bounds() {
         let bezier = Cubic {
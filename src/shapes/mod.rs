@@ -3,3 +3,4 @@ pub mod line;
 pub mod path;
 pub mod point;
 pub mod rect;
+pub mod stroke;
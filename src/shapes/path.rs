@@ -1,8 +1,19 @@
-use std::hash::Hash;
+use std::{
+    f32::consts::{FRAC_PI_2, TAU},
+    hash::Hash,
+};
 
-use crate::math::cmp::ApproxEq;
+use crate::math::{
+    affine::Affine,
+    cmp::{max, min, ApproxEq},
+    vector2::Vec2,
+};
 
-use super::{bezier::CubicSlice, point::Point};
+use super::{bezier::CubicSlice, point::Point, rect::Rect};
+
+mod svg;
+
+pub use svg::{ParseError, ParseErrorKind};
 
 #[derive(Clone)]
 pub struct Path {
@@ -12,19 +23,94 @@ pub struct Path {
 }
 
 impl Path {
-    pub fn iter(&self) -> SegmentIter {
+    pub fn iter(&self) -> SegmentIter<'_> {
         SegmentIter {
             path: self,
             segment_idx: 0,
             point_offset: 0,
         }
     }
+
+    /// Computes the smallest rectangle that contains every point in the path,
+    /// including control points. Because a curve always lies within the convex
+    /// hull of its control points, the path is guaranteed to lie within this
+    /// rectangle.
+    #[must_use]
+    pub fn bounds(&self) -> Rect {
+        if self.x.is_empty() {
+            return Rect::default();
+        }
+
+        let mut bounds = Rect::new(self.x[0], self.x[0], self.y[0], self.y[0]);
+        for (x, y) in self.x.iter().zip(self.y.iter()) {
+            bounds.left = min!(bounds.left, *x);
+            bounds.right = max!(bounds.right, *x);
+            bounds.top = min!(bounds.top, *y);
+            bounds.bottom = max!(bounds.bottom, *y);
+        }
+        bounds
+    }
+
+    /// Applies `transform` to every point in the path.
+    pub fn transform(&mut self, transform: &Affine) {
+        transform.apply(&mut self.x, &mut self.y);
+    }
+
+    /// Determines if every segment in the path is closed.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.segments.iter().all(|segment| segment.closed)
+    }
+
+    /// Copies the path, closing every open segment with a straight line back
+    /// to its first point. This is the area covered when the path is filled.
+    #[must_use]
+    pub fn to_closed(&self) -> Path {
+        let mut closed = Path {
+            segments: Vec::with_capacity(self.segments.len()),
+            x: Vec::with_capacity(self.x.len()),
+            y: Vec::with_capacity(self.y.len()),
+        };
+
+        let mut offset = 0;
+        for segment in &self.segments {
+            let points = offset..offset + segment.length as usize;
+            let (first, last) = (points.start, points.end - 1);
+            closed.x.extend(&self.x[points.clone()]);
+            closed.y.extend(&self.y[points]);
+            offset += segment.length as usize;
+
+            let mut length = segment.length;
+            if !segment.closed {
+                let points = Builder::line_as_cubic(
+                    self.x[last],
+                    self.y[last],
+                    self.x[first],
+                    self.y[first],
+                );
+                closed.x.extend(&points[0][1..]);
+                closed.y.extend(&points[1][1..]);
+                length += 3;
+            }
+
+            closed.segments.push(Segment {
+                length,
+                closed: true,
+            });
+        }
+
+        closed
+    }
 }
 
 #[derive(Clone, Copy, Hash)]
-#[repr(transparent)]
 pub struct Segment {
-    pub length: u16,
+    /// The number of points in the segment.
+    pub length: u32,
+    /// Set if the segment was closed, in which case it ends where it begins.
+    /// Open segments are capped when stroked, and are implicitly closed with
+    /// a straight line when filled.
+    pub closed: bool,
 }
 
 pub struct SegmentIter<'a> {
@@ -45,8 +131,8 @@ impl<'a> Iterator for SegmentIter<'a> {
             self.point_offset += segment.length as usize;
 
             Some(CurveIter::over_points(
-                &self.path.x[offset as usize..offset + segment.length as usize],
-                &self.path.y[offset as usize..offset + segment.length as usize],
+                &self.path.x[offset..offset + segment.length as usize],
+                &self.path.y[offset..offset + segment.length as usize],
             ))
         } else {
             None
@@ -90,79 +176,321 @@ pub enum Error {
     TooManyCurves,
 }
 
+/// The last control point of the previous curve, which smooth curves reflect
+/// through the current point to find their first control point.
+#[derive(Clone, Copy, Default)]
+enum LastControl {
+    #[default]
+    None,
+    Cubic(Point),
+    Quadratic(Point),
+}
+
 #[derive(Default)]
 pub struct Builder {
     segments: Vec<Segment>,
     current: Option<Segment>,
     x: Vec<f32>,
     y: Vec<f32>,
-    num_curves: u16,
+    num_curves: u32,
+    /// The point that the next command starts from.
+    cursor: Option<Point>,
+    /// The first point of the current segment, or of the last one if it was
+    /// closed.
+    start: Point,
+    last_control: LastControl,
 }
 
 impl Builder {
+    /// Starts a new segment at `point`. The current segment, if any, is kept
+    /// as an open segment.
     pub fn move_to(&mut self, point: Point) {
+        self.end_segment();
         self.x.push(point.x);
         self.y.push(point.y);
-        self.current = Some(Segment { length: 1 });
+        self.current = Some(Segment {
+            length: 1,
+            closed: false,
+        });
+        self.cursor = Some(point);
+        self.start = point;
+        self.last_control = LastControl::None;
+    }
+
+    /// Starts a new segment offset from the current point by `delta`.
+    pub fn rel_move_to(&mut self, delta: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.move_to(cursor + delta);
+        Ok(())
     }
 
     pub fn line_to(&mut self, point: Point) -> Result<(), Error> {
-        let mut current = self.current.as_mut().ok_or(Error::PathNotStarted)?;
+        let from = self.begin_curve()?;
 
-        let points = Self::line_as_cubic(
-            *self.x.last().unwrap(),
-            *self.y.last().unwrap(),
-            point.x,
-            point.y,
-        );
+        let points = Self::line_as_cubic(from.x, from.y, point.x, point.y);
         self.x.extend(&points[0][1..]);
         self.y.extend(&points[1][1..]);
-        current.length += 3;
-        self.num_curves.checked_add(1).ok_or(Error::TooManyCurves)?;
+        self.cursor = Some(point);
+        self.last_control = LastControl::None;
 
         Ok(())
     }
 
+    pub fn rel_line_to(&mut self, delta: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.line_to(cursor + delta)
+    }
+
+    /// Adds a horizontal line from the current point to `x`.
+    pub fn horizontal_line_to(&mut self, x: f32) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.line_to(Point::new(x, cursor.y))
+    }
+
+    pub fn rel_horizontal_line_to(&mut self, dx: f32) -> Result<(), Error> {
+        self.rel_line_to(Vec2::new(dx, 0.0))
+    }
+
+    /// Adds a vertical line from the current point to `y`.
+    pub fn vertical_line_to(&mut self, y: f32) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.line_to(Point::new(cursor.x, y))
+    }
+
+    pub fn rel_vertical_line_to(&mut self, dy: f32) -> Result<(), Error> {
+        self.rel_line_to(Vec2::new(0.0, dy))
+    }
+
     pub fn add_cubic(&mut self, p1: Point, p2: Point, p3: Point) -> Result<(), Error> {
-        let mut current = self.current.as_mut().ok_or(Error::PathNotStarted)?;
+        self.begin_curve()?;
 
         self.x.extend(&[p1.x, p2.x, p3.x]);
         self.y.extend(&[p1.y, p2.y, p3.y]);
-        current.length += 3;
-        self.num_curves.checked_add(1).ok_or(Error::TooManyCurves)?;
+        self.cursor = Some(p3);
+        self.last_control = LastControl::Cubic(p2);
 
         Ok(())
     }
 
-    pub fn close(&mut self) -> Result<(), Error> {
-        let mut current = self.current.take().ok_or(Error::PathNotStarted)?;
+    /// Adds a cubic bezier curve with every point given relative to the
+    /// current point.
+    pub fn rel_cubic_to(&mut self, d1: Vec2, d2: Vec2, d3: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.add_cubic(cursor + d1, cursor + d2, cursor + d3)
+    }
+
+    /// Adds a quadratic bezier curve with control point `p1`, ending at `p2`.
+    /// Every quadratic is also a cubic, so this is exact.
+    pub fn quad_to(&mut self, p1: Point, p2: Point) -> Result<(), Error> {
+        let p0 = self.cursor.ok_or(Error::PathNotStarted)?;
+
+        self.add_cubic(
+            p0 + (p1 - p0) * (2.0 / 3.0),
+            p2 + (p1 - p2) * (2.0 / 3.0),
+            p2,
+        )?;
+        self.last_control = LastControl::Quadratic(p1);
+
+        Ok(())
+    }
+
+    pub fn rel_quad_to(&mut self, d1: Vec2, d2: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.quad_to(cursor + d1, cursor + d2)
+    }
+
+    /// Adds a cubic bezier curve whose first control point is the reflection
+    /// of the previous curve's last control point through the current point,
+    /// so that the two meet smoothly. If the previous curve was not a cubic,
+    /// the first control point is the current point.
+    pub fn smooth_cubic_to(&mut self, p2: Point, p3: Point) -> Result<(), Error> {
+        let p0 = self.cursor.ok_or(Error::PathNotStarted)?;
+
+        let p1 = match self.last_control {
+            LastControl::Cubic(control) => p0 + (p0 - control),
+            _ => p0,
+        };
+
+        self.add_cubic(p1, p2, p3)
+    }
+
+    pub fn rel_smooth_cubic_to(&mut self, d2: Vec2, d3: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.smooth_cubic_to(cursor + d2, cursor + d3)
+    }
+
+    /// Adds a quadratic bezier curve whose control point is the reflection of
+    /// the previous curve's control point through the current point. If the
+    /// previous curve was not a quadratic, the control point is the current
+    /// point, and the curve is a straight line.
+    pub fn smooth_quad_to(&mut self, p2: Point) -> Result<(), Error> {
+        let p0 = self.cursor.ok_or(Error::PathNotStarted)?;
+
+        let p1 = match self.last_control {
+            LastControl::Quadratic(control) => p0 + (p0 - control),
+            _ => p0,
+        };
+
+        self.quad_to(p1, p2)
+    }
+
+    pub fn rel_smooth_quad_to(&mut self, d2: Vec2) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.smooth_quad_to(cursor + d2)
+    }
+
+    /// Adds an elliptical arc from the current point to `to`, as described by
+    /// SVG's endpoint parametrization. The ellipse has the given `radii` and is
+    /// rotated by `x_rotation` radians. Of the four arcs that fit, `large_arc`
+    /// picks one that spans more than half of the ellipse, and `sweep` picks
+    /// one that runs in the direction of increasing angles.
+    ///
+    /// Radii that are too small to reach `to` are scaled up until they do, and
+    /// an arc with a zero radius is a straight line. The arc is approximated by
+    /// cubics that span at most a quarter of the ellipse each, which keeps them
+    /// within 0.03% of the larger radius of it.
+    pub fn arc_to(
+        &mut self,
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Point,
+    ) -> Result<(), Error> {
+        let from = self.cursor.ok_or(Error::PathNotStarted)?;
+
+        if from.approx_eq(&to) {
+            return Ok(());
+        }
+
+        let (mut rx, mut ry) = (radii.x().abs(), radii.y().abs());
+        if rx.approx_eq(&0.0) || ry.approx_eq(&0.0) {
+            return self.line_to(to);
+        }
+
+        let (sin, cos) = x_rotation.sin_cos();
+        let rotate = |v: Vec2| Vec2::new(cos * v.x() - sin * v.y(), sin * v.x() + cos * v.y());
+
+        // The midpoint between the end points, in the ellipse's own frame.
+        let half = (from - to) * 0.5;
+        let mid = Vec2::new(
+            cos * half.x() + sin * half.y(),
+            cos * half.y() - sin * half.x(),
+        );
+
+        let scale = (mid.x() / rx).powi(2) + (mid.y() / ry).powi(2);
+        if scale > 1.0 {
+            rx *= scale.sqrt();
+            ry *= scale.sqrt();
+        }
+
+        let (rx2, ry2) = (rx * rx, ry * ry);
+        let (mx2, my2) = (mid.x() * mid.x(), mid.y() * mid.y());
+        let numerator = rx2 * ry2 - rx2 * my2 - ry2 * mx2;
+        let mut coefficient = (max!(numerator, 0.0) / (rx2 * my2 + ry2 * mx2)).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+
+        let center = Vec2::new(
+            coefficient * rx * mid.y() / ry,
+            -coefficient * ry * mid.x() / rx,
+        );
 
-        let first_x: f32 = self.x[self.x.len() - current.length as usize];
-        let first_y: f32 = self.y[self.y.len() - current.length as usize];
-        let last_x = self.x[self.x.len() - 1];
-        let last_y = self.y[self.y.len() - 1];
+        // The end points on a unit circle that the ellipse is stretched from.
+        let unstretch = |v: Vec2| Vec2::new(v.x() / rx, v.y() / ry);
+        let start = unstretch(mid - center);
+        let end = unstretch(-mid - center);
+
+        let start_angle = start.y().atan2(start.x());
+        let mut sweep_angle = end.y().atan2(end.x()) - start_angle;
+        if sweep && sweep_angle < 0.0 {
+            sweep_angle += TAU;
+        } else if !sweep && sweep_angle > 0.0 {
+            sweep_angle -= TAU;
+        }
 
-        if !(first_x.approx_eq(&last_x) && first_y.approx_eq(&last_y)) {
-            let points = Self::line_as_cubic(last_x, last_y, first_x, first_y);
-            self.x.extend(&points[0][1..]);
-            self.y.extend(&points[1][1..]);
-            current.length += 3;
-            self.num_curves.checked_add(1).ok_or(Error::TooManyCurves)?;
+        let origin = from + (to - from) * 0.5 + rotate(center);
+        let map = |v: Vec2| origin + rotate(Vec2::new(v.x() * rx, v.y() * ry));
+
+        let pieces = (sweep_angle.abs() / FRAC_PI_2).ceil().max(1.0);
+        let step = sweep_angle / pieces;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+
+        let unit = |angle: f32| Vec2::new(angle.cos(), angle.sin());
+        let normal = |v: Vec2| Vec2::new(-v.y(), v.x());
+
+        let mut current = unit(start_angle);
+        for i in 1..=pieces as u32 {
+            let next = unit(start_angle + step * i as f32);
+            let p3 = if i == pieces as u32 { to } else { map(next) };
+            self.add_cubic(
+                map(current + normal(current) * k),
+                map(next - normal(next) * k),
+                p3,
+            )?;
+            current = next;
         }
 
+        self.last_control = LastControl::None;
+        Ok(())
+    }
+
+    /// Adds an elliptical arc to the point offset from the current point by
+    /// `delta`. See [`Builder::arc_to`].
+    pub fn rel_arc_to(
+        &mut self,
+        radii: Vec2,
+        x_rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        delta: Vec2,
+    ) -> Result<(), Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        self.arc_to(radii, x_rotation, large_arc, sweep, cursor + delta)
+    }
+
+    /// Closes the current segment with a line back to its first point, if it
+    /// doesn't already end there. Drawing after closing a segment starts a
+    /// new one from the same point.
+    pub fn close(&mut self) -> Result<(), Error> {
+        if self.current.is_none() {
+            return Err(Error::PathNotStarted);
+        }
+
+        let last = self.cursor.unwrap();
+        if !last.approx_eq(&self.start) {
+            self.line_to(self.start)?;
+        }
+
+        let mut current = self.current.take().unwrap();
+        current.closed = true;
         self.segments.push(current);
+        self.cursor = Some(self.start);
+        self.last_control = LastControl::None;
 
         Ok(())
     }
 
+    /// The point that the next command starts from: the end of the last
+    /// command, or the start of the last segment if it was closed.
     pub fn cursor(&self) -> Option<Point> {
-        self.x
-            .last()
-            .zip(self.y.last())
-            .map(|(x, y)| Point::new(*x, *y))
+        self.cursor
     }
 
-    pub fn build(self) -> Result<Path, Error> {
+    /// The last control point of the previous command, if it was a curve.
+    /// Quadratic curves report their single control point.
+    pub fn last_control(&self) -> Option<Point> {
+        match self.last_control {
+            LastControl::None => None,
+            LastControl::Cubic(p) | LastControl::Quadratic(p) => Some(p),
+        }
+    }
+
+    /// Finishes the path. The current segment, if any, is kept as an open
+    /// segment.
+    pub fn build(mut self) -> Result<Path, Error> {
+        self.end_segment();
         Ok(Path {
             segments: self.segments,
             x: self.x,
@@ -170,6 +498,33 @@ impl Builder {
         })
     }
 
+    /// Ends the current segment without closing it. Segments without any
+    /// curves are dropped.
+    fn end_segment(&mut self) {
+        if let Some(current) = self.current.take() {
+            if current.length > 1 {
+                self.segments.push(current);
+            } else {
+                self.x.pop();
+                self.y.pop();
+            }
+        }
+    }
+
+    /// Makes room for another curve in the current segment, returning the
+    /// point that it starts from. If the last segment was closed, a new one is
+    /// started from the same point.
+    fn begin_curve(&mut self) -> Result<Point, Error> {
+        let cursor = self.cursor.ok_or(Error::PathNotStarted)?;
+        if self.current.is_none() {
+            self.move_to(cursor);
+        }
+
+        self.num_curves = self.num_curves.checked_add(1).ok_or(Error::TooManyCurves)?;
+        self.current.as_mut().unwrap().length += 3;
+        Ok(cursor)
+    }
+
     fn line_as_cubic(x0: f32, y0: f32, x3: f32, y3: f32) -> [[f32; 4]; 2] {
         let dx = x3 - x0;
         let dy = y3 - y0;
@@ -182,3 +537,294 @@ impl Builder {
         [[x0, x1, x2, x3], [y0, y1, y2, y3]]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::bezier::Bezier;
+
+    fn curves(path: &Path) -> Vec<[Point; 4]> {
+        path.iter()
+            .flatten()
+            .map(|c| [c.p0(), c.p1(), c.p2(), c.p3()])
+            .collect()
+    }
+
+    #[test]
+    fn quadratics() {
+        let (p0, p1, p2) = (
+            Point::new(0.0, 0.0),
+            Point::new(3.0, 6.0),
+            Point::new(6.0, 0.0),
+        );
+
+        let mut builder = Builder::default();
+        builder.move_to(p0);
+        builder.quad_to(p1, p2).unwrap();
+        builder.smooth_quad_to(Point::new(12.0, 0.0)).unwrap();
+        builder.close().unwrap();
+        let path = builder.build().unwrap();
+
+        let curves = curves(&path);
+        let cubic = path.iter().flatten().next().unwrap();
+        for i in 0..=8 {
+            let t = i as f32 / 8.0;
+            let s = 1.0 - t;
+            let expected = Point::new(
+                s * s * p0.x + 2.0 * s * t * p1.x + t * t * p2.x,
+                s * s * p0.y + 2.0 * s * t * p1.y + t * t * p2.y,
+            );
+            assert!(cubic.at(t).approx_eq(&expected));
+        }
+
+        // The smooth quadratic's control point is (9, -6), the reflection of
+        // (3, 6) through (6, 0).
+        assert!(curves[1][1].approx_eq(&Point::new(8.0, -4.0)));
+        assert!(curves[1][2].approx_eq(&Point::new(10.0, -4.0)));
+    }
+
+    #[test]
+    fn smooth_cubics() {
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(0.0, 0.0));
+        builder
+            .add_cubic(
+                Point::new(1.0, 2.0),
+                Point::new(3.0, 2.0),
+                Point::new(4.0, 0.0),
+            )
+            .unwrap();
+        builder
+            .smooth_cubic_to(Point::new(7.0, -2.0), Point::new(8.0, 0.0))
+            .unwrap();
+        builder.line_to(Point::new(8.0, 4.0)).unwrap();
+        // Not preceded by a cubic, so its first control point is the current
+        // point.
+        builder
+            .smooth_cubic_to(Point::new(2.0, 4.0), Point::new(0.0, 0.0))
+            .unwrap();
+        builder.close().unwrap();
+        let curves = curves(&builder.build().unwrap());
+
+        assert_eq!(curves.len(), 4);
+        assert!(curves[1][1].approx_eq(&Point::new(5.0, -2.0)));
+        assert!(curves[3][1].approx_eq(&Point::new(8.0, 4.0)));
+    }
+
+    #[test]
+    fn relative_commands() {
+        let mut absolute = Builder::default();
+        absolute.move_to(Point::new(1.0, 1.0));
+        absolute.line_to(Point::new(5.0, 1.0)).unwrap();
+        absolute.vertical_line_to(4.0).unwrap();
+        absolute
+            .add_cubic(
+                Point::new(5.0, 6.0),
+                Point::new(3.0, 6.0),
+                Point::new(3.0, 4.0),
+            )
+            .unwrap();
+        absolute
+            .smooth_cubic_to(Point::new(1.0, 2.0), Point::new(1.0, 4.0))
+            .unwrap();
+        absolute
+            .quad_to(Point::new(0.0, 3.0), Point::new(1.0, 2.0))
+            .unwrap();
+        absolute.smooth_quad_to(Point::new(1.0, 1.0)).unwrap();
+        absolute.close().unwrap();
+
+        let mut relative = Builder::default();
+        relative.move_to(Point::new(1.0, 1.0));
+        relative.rel_horizontal_line_to(4.0).unwrap();
+        relative.rel_vertical_line_to(3.0).unwrap();
+        relative
+            .rel_cubic_to(
+                Vec2::new(0.0, 2.0),
+                Vec2::new(-2.0, 2.0),
+                Vec2::new(-2.0, 0.0),
+            )
+            .unwrap();
+        relative
+            .rel_smooth_cubic_to(Vec2::new(-2.0, -2.0), Vec2::new(-2.0, 0.0))
+            .unwrap();
+        relative
+            .rel_quad_to(Vec2::new(-1.0, -1.0), Vec2::new(0.0, -2.0))
+            .unwrap();
+        assert!(relative
+            .last_control()
+            .unwrap()
+            .approx_eq(&Point::new(0.0, 3.0)));
+        relative.rel_smooth_quad_to(Vec2::new(0.0, -1.0)).unwrap();
+        relative.close().unwrap();
+
+        let (absolute, relative) = (
+            curves(&absolute.build().unwrap()),
+            curves(&relative.build().unwrap()),
+        );
+        assert_eq!(absolute.len(), relative.len());
+        for (a, b) in absolute.iter().flatten().zip(relative.iter().flatten()) {
+            assert!(a.approx_eq(b));
+        }
+    }
+
+    #[test]
+    fn cursor() {
+        let mut builder = Builder::default();
+        assert!(builder.cursor().is_none());
+        assert!(builder.rel_line_to(Vec2::new(1.0, 0.0)).is_err());
+
+        builder.move_to(Point::new(1.0, 2.0));
+        builder.rel_line_to(Vec2::new(3.0, 0.0)).unwrap();
+        builder.rel_line_to(Vec2::new(0.0, 3.0)).unwrap();
+        assert!(builder.cursor().unwrap().approx_eq(&Point::new(4.0, 5.0)));
+        assert!(builder.last_control().is_none());
+
+        // Closing returns to the start of the segment, where the next one
+        // begins if there is no move in between.
+        builder.close().unwrap();
+        assert!(builder.cursor().unwrap().approx_eq(&Point::new(1.0, 2.0)));
+        builder.rel_line_to(Vec2::new(-1.0, 0.0)).unwrap();
+        builder.rel_line_to(Vec2::new(0.0, -1.0)).unwrap();
+        builder.close().unwrap();
+
+        builder.rel_move_to(Vec2::new(10.0, 0.0)).unwrap();
+        assert!(builder.cursor().unwrap().approx_eq(&Point::new(11.0, 2.0)));
+
+        let path = builder.build().unwrap();
+        assert_eq!(path.segments.len(), 2);
+        assert_eq!(path.segments[1].length, 10);
+        assert!(Point::new(path.x[10], path.y[10]).approx_eq(&Point::new(1.0, 2.0)));
+    }
+
+    #[test]
+    fn open_segments() {
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(0.0, 0.0));
+        builder.line_to(Point::new(4.0, 0.0)).unwrap();
+        builder.line_to(Point::new(4.0, 4.0)).unwrap();
+        // A move without any curves after it is dropped.
+        builder.move_to(Point::new(10.0, 10.0));
+        builder.move_to(Point::new(10.0, 0.0));
+        builder.line_to(Point::new(14.0, 0.0)).unwrap();
+        builder.line_to(Point::new(14.0, 4.0)).unwrap();
+        builder.close().unwrap();
+        builder.line_to(Point::new(10.0, 4.0)).unwrap();
+        let path = builder.build().unwrap();
+
+        let segments = path
+            .segments
+            .iter()
+            .map(|segment| (segment.length, segment.closed))
+            .collect::<Vec<_>>();
+        assert_eq!(segments, [(7, false), (10, true), (4, false)]);
+        assert_eq!(path.x.len(), 21);
+        assert!(!path.is_closed());
+
+        let closed = path.to_closed();
+        assert!(closed.is_closed());
+        let segments = closed
+            .segments
+            .iter()
+            .map(|segment| segment.length)
+            .collect::<Vec<_>>();
+        assert_eq!(segments, [10, 10, 7]);
+        for segment in closed.iter() {
+            let curves = segment.collect::<Vec<_>>();
+            let (first, last) = (curves[0], curves[curves.len() - 1]);
+            assert!(first.p0().approx_eq(&last.p3()));
+        }
+    }
+
+    #[test]
+    fn arcs() {
+        let on_ellipse = |p: Point, center: Point, rx: f32, ry: f32| {
+            let d = p - center;
+            ((d.x() / rx).powi(2) + (d.y() / ry).powi(2)).sqrt()
+        };
+
+        // Of the four arcs between these points on a circle of radius 10, the
+        // flags pick out the centers (10, 0) and (0, 10), and the direction.
+        let (from, to) = (Point::new(0.0, 0.0), Point::new(10.0, 10.0));
+        for (large_arc, sweep, center, pieces) in [
+            (false, false, Point::new(10.0, 0.0), 1),
+            (false, true, Point::new(0.0, 10.0), 1),
+            (true, false, Point::new(0.0, 10.0), 3),
+            (true, true, Point::new(10.0, 0.0), 3),
+        ] {
+            let mut builder = Builder::default();
+            builder.move_to(from);
+            builder
+                .arc_to(Vec2::new(10.0, 10.0), 0.0, large_arc, sweep, to)
+                .unwrap();
+            builder.close().unwrap();
+            let path = builder.build().unwrap();
+
+            let arc = &path.iter().flatten().collect::<Vec<_>>()[..pieces];
+            assert!(arc.last().unwrap().p3().approx_eq(&to));
+            for curve in arc {
+                for i in 0..=16 {
+                    let r = on_ellipse(curve.at(i as f32 / 16.0), center, 10.0, 10.0);
+                    assert!((r - 1.0).abs() < 3e-4, "{r}");
+                }
+
+                // A positive sweep runs in the direction of increasing angles.
+                let (d, t) = (curve.p0() - center, curve.derivative(0.5));
+                assert_eq!(d.x() * t.y() - d.y() * t.x() > 0.0, sweep);
+            }
+        }
+
+        // Radii too small to span the end points are scaled up, giving half of
+        // an ellipse. Rotating it by a quarter turn swaps its axes.
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(0.0, 0.0));
+        builder
+            .arc_to(
+                Vec2::new(1.0, 2.0),
+                FRAC_PI_2,
+                false,
+                true,
+                Point::new(0.0, 20.0),
+            )
+            .unwrap();
+        builder.close().unwrap();
+        let path = builder.build().unwrap();
+        for curve in path.iter().flatten().take(2) {
+            for i in 0..=16 {
+                let r = on_ellipse(curve.at(i as f32 / 16.0), Point::new(0.0, 10.0), 20.0, 10.0);
+                assert!((r - 1.0).abs() < 3e-4, "{r}");
+            }
+        }
+
+        // A zero radius gives a straight line, and an arc to the current point
+        // is skipped entirely.
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(0.0, 0.0));
+        builder
+            .arc_to(Vec2::new(0.0, 5.0), 0.0, false, false, Point::new(5.0, 0.0))
+            .unwrap();
+        builder
+            .arc_to(Vec2::new(5.0, 5.0), 0.0, false, false, Point::new(5.0, 0.0))
+            .unwrap();
+        builder.close().unwrap();
+        assert_eq!(curves(&builder.build().unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn transform() {
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(1.0, 2.0));
+        builder
+            .quad_to(Point::new(3.0, 4.0), Point::new(5.0, 2.0))
+            .unwrap();
+        let mut path = builder.build().unwrap();
+        let expected = curves(&path)
+            .iter()
+            .map(|c| c.map(|p| Point::new(2.0 * p.x + 1.0, -p.y)))
+            .collect::<Vec<_>>();
+
+        path.transform(&(Affine::translate(Vec2::new(1.0, 0.0)) * Affine::scale(2.0, -1.0)));
+        for (a, b) in curves(&path).iter().zip(&expected) {
+            assert!(a.iter().zip(b).all(|(a, b)| a.approx_eq(b)));
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Conversion between paths and SVG path data, the syntax of the `d` attribute
+//! of SVG's `<path>` element.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::{math::vector2::Vec2, shapes::point::Point};
+
+use super::{Builder, Error, Path};
+
+/// Describes what went wrong while parsing SVG path data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Path data must begin with a move command.
+    ExpectedMoveTo,
+    /// A character that is neither a command nor part of a number, or a number
+    /// where a command was expected.
+    ExpectedCommand,
+    /// A command ended before all of its arguments were given.
+    ExpectedNumber,
+    /// The large arc and sweep flags of an arc must be `0` or `1`.
+    ExpectedFlag,
+    /// The path has more curves than it can hold.
+    TooManyCurves,
+}
+
+/// An error in SVG path data, and the byte offset in the data at which it was
+/// found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl Path {
+    /// Parses SVG path data, such as `"M 10 10 h 20 v 20 z"`. Every command in
+    /// the SVG specification is supported, in both its absolute and relative
+    /// forms.
+    ///
+    /// Unlike SVG renderers, which draw a path up to the first error in its
+    /// data, this rejects the path entirely.
+    ///
+    /// ```rust
+    /// # use shiny::shapes::path::{Path, ParseErrorKind};
+    /// let path = Path::from_svg_path_data("M0 0 L10 0 10 10z").unwrap();
+    /// assert_eq!(path.segments.len(), 1);
+    /// assert!(path.segments[0].closed);
+    ///
+    /// let error = Path::from_svg_path_data("M0 0 L10").err().unwrap();
+    /// assert_eq!(error.offset, 8);
+    /// assert_eq!(error.kind, ParseErrorKind::ExpectedNumber);
+    /// ```
+    pub fn from_svg_path_data(data: &str) -> Result<Path, ParseError> {
+        let mut parser = Parser {
+            data: data.as_bytes(),
+            offset: 0,
+        };
+        let mut builder = Builder::default();
+
+        parser.skip_whitespace();
+        let mut previous: Option<u8> = None;
+        while let Some(next) = parser.peek() {
+            let start = parser.offset;
+
+            // Commands other than close may be repeated by giving more
+            // arguments without repeating the command.
+            let command = if next.is_ascii_alphabetic() {
+                if !b"MLHVCSQTAZ".contains(&next.to_ascii_uppercase()) {
+                    return Err(parser.error(ParseErrorKind::ExpectedCommand));
+                }
+                parser.offset += 1;
+                parser.skip_whitespace();
+                next
+            } else {
+                if !matches!(next, b'0'..=b'9' | b'+' | b'-' | b'.') {
+                    return Err(parser.error(ParseErrorKind::ExpectedCommand));
+                }
+
+                match previous {
+                    Some(b'M') => b'L',
+                    Some(b'm') => b'l',
+                    Some(command) if !matches!(command, b'Z' | b'z') => command,
+                    _ => return Err(parser.error(ParseErrorKind::ExpectedCommand)),
+                }
+            };
+
+            if previous.is_none() && !matches!(command, b'M' | b'm') {
+                return Err(ParseError {
+                    offset: start,
+                    kind: ParseErrorKind::ExpectedMoveTo,
+                });
+            }
+
+            // Closing a segment that was just closed does nothing.
+            let is_close = matches!(command, b'Z' | b'z');
+            if is_close && matches!(previous, Some(b'Z' | b'z')) {
+                parser.skip_separator();
+                continue;
+            }
+
+            parser
+                .command(command, &mut builder)?
+                .map_err(|error| ParseError {
+                    offset: start,
+                    kind: match error {
+                        Error::TooManyCurves => ParseErrorKind::TooManyCurves,
+                        // Every command after the first move has a current
+                        // point to start from.
+                        Error::PathNotStarted => unreachable!(),
+                    },
+                })?;
+
+            previous = Some(command);
+            parser.skip_separator();
+        }
+
+        Ok(builder
+            .build()
+            .expect("path data always begins with a move"))
+    }
+}
+
+/// Writes the path as SVG path data, using absolute move, cubic, and close
+/// commands. Numbers are written with enough precision to be parsed back to
+/// exactly the same path.
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let mut offset = 0;
+        for (i, segment) in self.segments.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            let x = &self.x[offset..offset + segment.length as usize];
+            let y = &self.y[offset..offset + segment.length as usize];
+            offset += segment.length as usize;
+
+            write!(f, "M{} {}", x[0], y[0])?;
+            for (x, y) in x[1..].chunks_exact(3).zip(y[1..].chunks_exact(3)) {
+                write!(f, " C{} {} {} {} {} {}", x[0], y[0], x[1], y[1], x[2], y[2])?;
+            }
+
+            if segment.closed {
+                write!(f, " Z")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl Parser<'_> {
+    /// Reads the arguments of a single command and adds it to the path. The
+    /// outer result holds syntax errors, and the inner one errors from the
+    /// builder.
+    fn command(
+        &mut self,
+        command: u8,
+        builder: &mut Builder,
+    ) -> Result<Result<(), Error>, ParseError> {
+        let relative = command.is_ascii_lowercase();
+        let cursor = builder.cursor().unwrap_or(Point::new(0.0, 0.0));
+        let origin = if relative {
+            cursor.vec()
+        } else {
+            Vec2::new(0.0, 0.0)
+        };
+
+        Ok(match command.to_ascii_uppercase() {
+            b'M' => {
+                // A relative move at the start of the path is relative to the
+                // origin.
+                let point = self.point()? + origin;
+                builder.move_to(point);
+                Ok(())
+            }
+            b'L' => {
+                let point = self.point()? + origin;
+                builder.line_to(point)
+            }
+            b'H' => {
+                let x = self.number()? + origin.x();
+                builder.horizontal_line_to(x)
+            }
+            b'V' => {
+                let y = self.number()? + origin.y();
+                builder.vertical_line_to(y)
+            }
+            b'C' => {
+                let p1 = self.point()? + origin;
+                let p2 = self.point()? + origin;
+                let p3 = self.point()? + origin;
+                builder.add_cubic(p1, p2, p3)
+            }
+            b'S' => {
+                let p2 = self.point()? + origin;
+                let p3 = self.point()? + origin;
+                builder.smooth_cubic_to(p2, p3)
+            }
+            b'Q' => {
+                let p1 = self.point()? + origin;
+                let p2 = self.point()? + origin;
+                builder.quad_to(p1, p2)
+            }
+            b'T' => {
+                let p2 = self.point()? + origin;
+                builder.smooth_quad_to(p2)
+            }
+            b'A' => {
+                let rx = self.number()?;
+                let ry = self.number()?;
+                let x_rotation = self.number()?;
+                let large_arc = self.flag()?;
+                let sweep = self.flag()?;
+                let to = self.point()? + origin;
+                builder.arc_to(
+                    Vec2::new(rx, ry),
+                    x_rotation.to_radians(),
+                    large_arc,
+                    sweep,
+                    to,
+                )
+            }
+            b'Z' => builder.close(),
+            _ => unreachable!("unknown commands are rejected before their arguments"),
+        })
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.offset).copied()
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            offset: self.offset,
+            kind,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r' | b'\x0C')) {
+            self.offset += 1;
+        }
+    }
+
+    /// Skips whitespace, and up to one comma within it.
+    fn skip_separator(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(b',') {
+            self.offset += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn point(&mut self) -> Result<Point, ParseError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(Point::new(x, y))
+    }
+
+    /// Reads a number, followed by an optional separator.
+    fn number(&mut self) -> Result<f32, ParseError> {
+        let start = self.offset;
+        let digits = |parser: &mut Self| {
+            let start = parser.offset;
+            while parser.peek().is_some_and(|c| c.is_ascii_digit()) {
+                parser.offset += 1;
+            }
+            parser.offset - start
+        };
+
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.offset += 1;
+        }
+
+        let mut mantissa = digits(self);
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            mantissa += digits(self);
+        }
+
+        if mantissa == 0 {
+            self.offset = start;
+            return Err(self.error(ParseErrorKind::ExpectedNumber));
+        }
+
+        // An exponent is only part of the number if it has digits, so that
+        // "1em" is read as the number 1 followed by whatever "em" is.
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            let before_exponent = self.offset;
+            self.offset += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.offset += 1;
+            }
+            if digits(self) == 0 {
+                self.offset = before_exponent;
+            }
+        }
+
+        // The grammar above only admits ASCII, and the standard library parses
+        // every string it admits.
+        let text = std::str::from_utf8(&self.data[start..self.offset]).unwrap();
+        let value = text.parse().unwrap();
+
+        self.skip_separator();
+        Ok(value)
+    }
+
+    /// Reads an arc flag, followed by an optional separator. Flags are a single
+    /// character, so they don't need to be separated from what follows them.
+    fn flag(&mut self) -> Result<bool, ParseError> {
+        let value = match self.peek() {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return Err(self.error(ParseErrorKind::ExpectedFlag)),
+        };
+
+        self.offset += 1;
+        self.skip_separator();
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::cmp::ApproxEq, shapes::bezier::Bezier};
+
+    fn parse(data: &str) -> Path {
+        Path::from_svg_path_data(data).unwrap()
+    }
+
+    fn error(data: &str) -> (usize, ParseErrorKind) {
+        let error = Path::from_svg_path_data(data).err().unwrap();
+        (error.offset, error.kind)
+    }
+
+    fn assert_same(a: &Path, b: &Path) {
+        assert_eq!(a.segments.len(), b.segments.len());
+        for (a, b) in a.segments.iter().zip(&b.segments) {
+            assert_eq!((a.length, a.closed), (b.length, b.closed));
+        }
+        for (a, b) in a.x.iter().zip(&b.x).chain(a.y.iter().zip(&b.y)) {
+            assert!(a.approx_eq(b), "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let mut builder = Builder::default();
+        builder.move_to(Point::new(10.0, 10.0));
+        builder.line_to(Point::new(20.0, 10.0)).unwrap();
+        builder.line_to(Point::new(20.0, 20.0)).unwrap();
+        builder.horizontal_line_to(30.0).unwrap();
+        builder.vertical_line_to(30.0).unwrap();
+        builder
+            .add_cubic(
+                Point::new(30.0, 40.0),
+                Point::new(20.0, 40.0),
+                Point::new(20.0, 30.0),
+            )
+            .unwrap();
+        builder
+            .smooth_cubic_to(Point::new(10.0, 20.0), Point::new(10.0, 30.0))
+            .unwrap();
+        builder
+            .quad_to(Point::new(5.0, 25.0), Point::new(10.0, 20.0))
+            .unwrap();
+        builder.smooth_quad_to(Point::new(10.0, 15.0)).unwrap();
+        builder
+            .arc_to(
+                Vec2::new(5.0, 2.5),
+                30f32.to_radians(),
+                true,
+                false,
+                Point::new(10.0, 10.0),
+            )
+            .unwrap();
+        builder.close().unwrap();
+        builder.move_to(Point::new(50.0, 50.0));
+        builder.line_to(Point::new(60.0, 60.0)).unwrap();
+        let expected = builder.build().unwrap();
+
+        assert_same(
+            &parse("M10 10 L20 10 20 20 H30 V30 C30 40 20 40 20 30 S10 20 10 30 Q5 25 10 20 T10 15 A5 2.5 30 1 0 10 10 Z M50 50 60 60"),
+            &expected,
+        );
+        assert_same(
+            &parse("m10,10 l10,0,0,10 h10 v10 c0,10 -10,10 -10,0 s-10,-10 -10,0 q-5,-5 0,-10 t0,-5 a5,2.5,30,1,0,0,-5 z m40,40 10,10"),
+            &expected,
+        );
+    }
+
+    #[test]
+    fn compact_syntax() {
+        // Numbers run together wherever a sign or a second decimal point makes
+        // it unambiguous, and arc flags need no separators.
+        assert_same(
+            &parse("M.5.5-1e1-1.5E+0l1-1a1 1 0 001 1"),
+            &parse("M 0.5 0.5 L -10 -1.5 L -9 -2.5 A 1 1 0 0 0 -8 -1.5"),
+        );
+
+        let path = parse("  \n M0,0 L 1 , 1  \t");
+        assert_eq!(path.x.len(), 4);
+
+        // "e" only begins an exponent if digits follow.
+        assert_eq!(error("M1 1e 2"), (4, ParseErrorKind::ExpectedCommand));
+
+        let path = parse("M0 0 L1 1 zZ z");
+        assert_eq!(path.segments.len(), 1);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("L0 0"), (0, ParseErrorKind::ExpectedMoveTo));
+        assert_eq!(error("  0 0"), (2, ParseErrorKind::ExpectedCommand));
+        assert_eq!(error("M0 0 L10"), (8, ParseErrorKind::ExpectedNumber));
+        assert_eq!(error("M0 0 L10 x"), (9, ParseErrorKind::ExpectedNumber));
+        assert_eq!(error("M0 0 X10 10"), (5, ParseErrorKind::ExpectedCommand));
+        assert_eq!(
+            error("M0 0 L1 1 Z 2 2"),
+            (12, ParseErrorKind::ExpectedCommand)
+        );
+        assert_eq!(
+            error("M0 0 A1 1 0 2 0 1 1"),
+            (12, ParseErrorKind::ExpectedFlag)
+        );
+        assert_eq!(error("M0 0 L.e1 1"), (6, ParseErrorKind::ExpectedNumber));
+        assert_eq!(error("M0 0 L 1,,1"), (9, ParseErrorKind::ExpectedNumber));
+
+        // Multi-byte characters are reported by their byte offset.
+        assert_eq!(error("M0 0 é"), (5, ParseErrorKind::ExpectedCommand));
+
+        assert_eq!(parse("").segments.len(), 0);
+    }
+
+    #[test]
+    fn display() {
+        let path = parse("M0.1 0.2 q 1 2 3 4 a 5 6 7 0 1 8 9 z m 1e-7 -3 h 1e7");
+        let text = path.to_string();
+        assert!(text.starts_with("M0.1 0.2 C"));
+
+        // Writing and reading the path gives back exactly the same points.
+        let reparsed = parse(&text);
+        assert_eq!(reparsed.x, path.x);
+        assert_eq!(reparsed.y, path.y);
+        assert_same(&reparsed, &path);
+        assert_eq!(reparsed.to_string(), text);
+
+        let curve = reparsed.iter().flatten().next().unwrap();
+        assert!(curve.p3().approx_eq(&Point::new(3.1, 4.2)));
+    }
+}
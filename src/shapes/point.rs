@@ -1,16 +1,17 @@
 use std::{
     hash::Hash,
-    ops::{Add, Sub},
+    ops::{Add, Mul, Sub},
 };
 
 use crate::math::{
+    affine::Affine,
     cmp::{ApproxEq, F32_APPROX_EQUAL_THRESHOLD},
     ops::Interpolate,
     vector2::Vec2,
 };
 
 /// A point in 2D space.
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -51,6 +52,13 @@ impl Add<Point> for Vec2 {
     }
 }
 
+impl Mul<Point> for Affine {
+    type Output = Point;
+    fn mul(self, rhs: Point) -> Self::Output {
+        (self * rhs.vec()).into()
+    }
+}
+
 impl From<Vec2> for Point {
     fn from(v: Vec2) -> Self {
         Self::new(v.x(), v.y())
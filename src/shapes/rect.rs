@@ -4,6 +4,7 @@ use std::{
 };
 
 use crate::math::{
+    affine::Affine,
     cmp::{max, min},
     simd::Float4,
     vector2::Vec2,
@@ -97,6 +98,32 @@ impl Rect {
             && self.top <= point.y
             && point.y <= self.bottom
     }
+
+    /// The distance from `point` to the nearest point in the rectangle, or 0
+    /// if it lies inside of it.
+    #[must_use]
+    pub fn distance_to(&self, point: Point) -> f32 {
+        let dx = max!(self.left - point.x, 0.0, point.x - self.right);
+        let dy = max!(self.top - point.y, 0.0, point.y - self.bottom);
+        Vec2::new(dx, dy).length()
+    }
+
+    /// Finds the smallest rectangle that contains this one after it has been
+    /// transformed. This is larger than the rectangle itself if the transform
+    /// rotates or skews it.
+    #[must_use]
+    pub fn transformed(&self, transform: &Affine) -> Self {
+        let mut x = [self.left, self.right, self.right, self.left];
+        let mut y = [self.top, self.top, self.bottom, self.bottom];
+        transform.apply(&mut x, &mut y);
+
+        Self::new(
+            min!(x[0], x[1], x[2], x[3]),
+            max!(x[0], x[1], x[2], x[3]),
+            min!(y[0], y[1], y[2], y[3]),
+            max!(y[0], y[1], y[2], y[3]),
+        )
+    }
 }
 
 pub trait BoundingBox {
@@ -110,7 +137,6 @@ impl BoundingBox for Rect {
 }
 
 impl Default for Rect {
-    #[must_use]
     fn default() -> Self {
         Self::new(0.0, 0.0, 0.0, 0.0)
     }
@@ -180,6 +206,14 @@ impl Debug for Rect {
 mod tests {
     use super::*;
 
+    #[test]
+    fn distance_to() {
+        let rect = Rect::new(1.0, 3.0, 1.0, 2.0);
+        assert_eq!(rect.distance_to(Point::new(2.0, 1.5)), 0.0);
+        assert_eq!(rect.distance_to(Point::new(5.0, 1.5)), 2.0);
+        assert_eq!(rect.distance_to(Point::new(4.0, 3.0)), 2.0f32.sqrt());
+    }
+
     #[test]
     fn position() {
         let r = Rect::new(1.0, 2.0, 3.0, 4.0);
@@ -240,4 +274,18 @@ mod tests {
             assert!(vertical.intersects_with(&horizontal));
         }
     }
+
+    #[test]
+    fn transformed() {
+        use std::f32::consts::FRAC_PI_4;
+
+        let r = Rect::new(-1.0, 1.0, -1.0, 1.0);
+        let rotated = r.transformed(&Affine::rotate(FRAC_PI_4));
+        let half_diagonal = 2f32.sqrt();
+        assert!((rotated.left + half_diagonal).abs() < 1e-5);
+        assert!((rotated.bottom - half_diagonal).abs() < 1e-5);
+
+        let moved = r.transformed(&Affine::translate(Vec2::new(2.0, 3.0)));
+        assert_eq!(moved, Rect::new(1.0, 3.0, 2.0, 4.0));
+    }
 }
@@ -0,0 +1,1045 @@
+//! Converts stroked paths into outlines that can be filled like any other path.
+//!
+//! Every curve in a subpath is offset to either side by half the stroke width,
+//! with joins inserted between consecutive curves and caps at the ends of open
+//! subpaths. The resulting outline covers the stroked area when filled with
+//! [`FillRule::NonZero`](crate::paint::FillRule::NonZero).
+//!
+//! Dashed strokes are produced by first cutting the path into one subpath per
+//! dash with [`dash`], then stroking each of those as usual.
+
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    hash::Hash,
+};
+
+use crate::math::{cmp::ApproxEq, vector2::Vec2};
+
+use super::{
+    bezier::{Bezier, Cubic},
+    path::{Builder, Path, Segment},
+    point::Point,
+};
+
+/// Curves whose control points all lie within this distance of each other
+/// have no meaningful direction, and are dropped before stroking.
+const DEGENERATE_DISTANCE: f32 = 1e-4;
+
+/// The maximum number of times a curve will be halved when searching for an
+/// offset curve that lies within tolerance. This bounds the work done for
+/// cusps, where no such curve exists.
+const MAX_OFFSET_DEPTH: u32 = 8;
+
+/// The largest parameter at which a curve is split when dashing. Splitting at
+/// exactly 1 would leave nothing to rescale the remaining splits against.
+const MAX_DASH_T: f32 = 1.0 - f32::EPSILON;
+
+/// The shape drawn at the ends of open subpaths.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum LineCap {
+    /// The stroke ends flush with the end of the subpath.
+    #[default]
+    Butt,
+    /// The stroke ends in a semicircle centered on the end of the subpath.
+    Round,
+    /// The stroke extends past the end of the subpath by half its width.
+    Square,
+}
+
+/// The shape drawn on the outside of corners between curves.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq)]
+pub enum LineJoin {
+    /// The edges of the stroke are extended until they meet, unless this would
+    /// exceed the miter limit, in which case a bevel is used instead.
+    #[default]
+    Miter,
+    /// The corner is rounded off with a circular arc.
+    Round,
+    /// The corner is cut off with a straight line.
+    Bevel,
+}
+
+/// Describes the geometry of a stroke. The defaults match those used by SVG.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrokeStyle {
+    /// The width of the stroke, centered on the path.
+    pub width: f32,
+    pub cap: LineCap,
+    pub join: LineJoin,
+    /// The maximum ratio of the length of a miter to the stroke width before
+    /// [`LineJoin::Miter`] falls back to a bevel.
+    pub miter_limit: f32,
+    /// Alternating lengths of dashes and gaps along the stroke. The stroke is
+    /// solid if this is empty. See [`dash`] for details.
+    pub dash_array: Vec<f32>,
+    /// The distance into the dash pattern at which each subpath begins.
+    pub dash_offset: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            cap: LineCap::default(),
+            join: LineJoin::default(),
+            miter_limit: 4.0,
+            dash_array: Vec::new(),
+            dash_offset: 0.0,
+        }
+    }
+}
+
+impl Hash for StrokeStyle {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.width.to_bits().hash(state);
+        self.cap.hash(state);
+        self.join.hash(state);
+        self.miter_limit.to_bits().hash(state);
+        for length in &self.dash_array {
+            length.to_bits().hash(state);
+        }
+        self.dash_offset.to_bits().hash(state);
+    }
+}
+
+/// Computes the outline of `path` when stroked with `style`. Offset curves are
+/// approximated such that they lie within `tolerance` of the true offset.
+///
+/// Closed subpaths are joined back onto themselves instead of being capped.
+#[must_use]
+pub fn stroke(path: &Path, style: &StrokeStyle, tolerance: f32) -> Path {
+    if style.dash_array.is_empty() {
+        outline(path, style, tolerance)
+    } else {
+        let dashed = dash(path, &style.dash_array, style.dash_offset);
+        outline(&dashed, style, tolerance)
+    }
+}
+
+/// Cuts `path` into one subpath for every dash in a pattern, in the manner of
+/// SVG's `stroke-dasharray` and `stroke-dashoffset`.
+///
+/// The lengths in `dashes` alternate between dashes and the gaps between them,
+/// and are repeated twice if there is an odd number of them. The pattern
+/// restarts at every subpath, beginning `offset` units into it. If a closed
+/// subpath both begins and ends within a dash, the two are joined together.
+///
+/// The path is returned unchanged if the pattern is empty, has a negative
+/// length, or has a total length of zero.
+#[must_use]
+pub fn dash(path: &Path, dashes: &[f32], offset: f32) -> Path {
+    let pattern = if dashes.len().is_multiple_of(2) {
+        dashes.to_vec()
+    } else {
+        dashes.repeat(2)
+    };
+
+    let period = pattern.iter().sum::<f32>();
+    if pattern.is_empty() || period <= 0.0 || pattern.iter().any(|d| d.is_nan() || *d < 0.0) {
+        return path.clone();
+    }
+
+    let mut dasher = Dasher {
+        pattern: &pattern,
+        index: 0,
+        remaining: 0.0,
+        current: None,
+        dashes: Vec::new(),
+        splits: Vec::new(),
+        buffer_x: Vec::new(),
+        buffer_y: Vec::new(),
+    };
+
+    let mut output = Path {
+        segments: Vec::new(),
+        x: Vec::new(),
+        y: Vec::new(),
+    };
+
+    for (segment, info) in path.iter().zip(&path.segments) {
+        let curves = segment.collect::<Vec<_>>();
+        let first = match curves.first() {
+            Some(first) => first,
+            None => continue,
+        };
+
+        dasher.begin(first.p0(), offset.rem_euclid(period));
+        let begins_in_dash = dasher.current.is_some();
+
+        for curve in &curves {
+            dasher.add_curve(&curve.as_owned());
+        }
+
+        if let Some((mut x, mut y)) = dasher.current.take() {
+            // A dash that starts exactly where the subpath ends has no length,
+            // and isn't part of the pattern.
+            if x.len() > 1 {
+                if info.closed && begins_in_dash && !dasher.dashes.is_empty() {
+                    let (first_x, first_y) = &dasher.dashes[0];
+                    x.extend(&first_x[1..]);
+                    y.extend(&first_y[1..]);
+                    dasher.dashes[0] = (x, y);
+                } else {
+                    dasher.dashes.push((x, y));
+                }
+            }
+        }
+
+        for (x, y) in dasher.dashes.drain(..) {
+            output.segments.push(Segment {
+                length: x.len() as u32,
+                closed: false,
+            });
+            output.x.extend(x);
+            output.y.extend(y);
+        }
+    }
+
+    output
+}
+
+/// Tracks progress through a dash pattern while walking the curves of a
+/// subpath.
+struct Dasher<'a> {
+    pattern: &'a [f32],
+    /// The interval of the pattern that the walk is currently in. Even indices
+    /// are dashes, odd indices are gaps.
+    index: usize,
+    /// The length left in the current interval.
+    remaining: f32,
+    /// The points of the dash being built, if the walk is within a dash.
+    current: Option<(Vec<f32>, Vec<f32>)>,
+    /// Dashes that have been completed in the current subpath.
+    dashes: Vec<(Vec<f32>, Vec<f32>)>,
+    splits: Vec<f32>,
+    buffer_x: Vec<f32>,
+    buffer_y: Vec<f32>,
+}
+
+impl<'a> Dasher<'a> {
+    /// Restarts the pattern at `start`, `phase` units into it.
+    fn begin(&mut self, start: Point, mut phase: f32) {
+        self.index = 0;
+        for _ in 0..self.pattern.len() {
+            if phase < self.pattern[self.index] || phase == 0.0 {
+                break;
+            }
+            phase -= self.pattern[self.index];
+            self.index = (self.index + 1) % self.pattern.len();
+        }
+
+        self.remaining = (self.pattern[self.index] - phase).max(0.0);
+        self.current = self
+            .index
+            .is_multiple_of(2)
+            .then(|| (vec![start.x], vec![start.y]));
+    }
+
+    /// Splits `curve` wherever it crosses from one interval of the pattern to
+    /// the next, adding the pieces that lie within dashes to the output.
+    fn add_curve(&mut self, curve: &Cubic) {
+        let length = curve.arc_length();
+
+        self.splits.clear();
+        let mut position = self.remaining;
+        let mut index = self.index;
+        let mut previous = 0.0;
+        while position < length {
+            let t = curve.t_at_arc_length(position).clamp(previous, MAX_DASH_T);
+            self.splits.push(t);
+            previous = t;
+
+            index = (index + 1) % self.pattern.len();
+            position += self.pattern[index];
+        }
+        self.remaining = position - length;
+
+        self.buffer_x.clear();
+        self.buffer_y.clear();
+        if self.splits.is_empty() {
+            self.buffer_x.extend(&curve.x);
+            self.buffer_y.extend(&curve.y);
+        } else {
+            curve.splitn(self.splits.iter(), &mut self.buffer_x, &mut self.buffer_y);
+        }
+
+        for i in 0..=self.splits.len() {
+            let range = i * 3..i * 3 + 4;
+            if i > 0 {
+                self.next_interval(Point::new(
+                    self.buffer_x[range.start],
+                    self.buffer_y[range.start],
+                ));
+            }
+
+            if let Some((x, y)) = &mut self.current {
+                x.extend(&self.buffer_x[range.start + 1..range.end]);
+                y.extend(&self.buffer_y[range.start + 1..range.end]);
+            }
+        }
+    }
+
+    /// Moves on to the next interval of the pattern at `point`, ending or
+    /// beginning a dash there.
+    fn next_interval(&mut self, point: Point) {
+        if let Some((mut x, mut y)) = self.current.take() {
+            // A zero-length dash is still drawn, so that it receives caps.
+            if x.len() == 1 {
+                x.extend([point.x; 3]);
+                y.extend([point.y; 3]);
+            }
+            self.dashes.push((x, y));
+        }
+
+        self.index = (self.index + 1) % self.pattern.len();
+        if self.index.is_multiple_of(2) {
+            self.current = Some((vec![point.x], vec![point.y]));
+        }
+    }
+}
+
+fn outline(path: &Path, style: &StrokeStyle, tolerance: f32) -> Path {
+    let mut stroker = Stroker {
+        builder: Builder::default(),
+        style,
+        radius: style.width * 0.5,
+        tolerance,
+    };
+
+    if stroker.radius > 0.0 {
+        let mut curves = Vec::new();
+        for (segment, info) in path.iter().zip(&path.segments) {
+            curves.clear();
+
+            let mut start = None;
+            for curve in segment {
+                start.get_or_insert_with(|| curve.p0());
+                let curve = curve.as_owned();
+                if !is_degenerate(&curve) {
+                    curves.push(curve);
+                }
+            }
+
+            // A subpath consisting of a single point is not stroked.
+            if let Some(start) = start {
+                stroker.contour(&curves, start, info.closed);
+            }
+        }
+    }
+
+    stroker
+        .builder
+        .build()
+        .expect("stroke outlines are always closed")
+}
+
+struct Stroker<'a> {
+    builder: Builder,
+    style: &'a StrokeStyle,
+    radius: f32,
+    tolerance: f32,
+}
+
+impl<'a> Stroker<'a> {
+    /// Strokes a single subpath. Every contour emitted here keeps the stroked
+    /// area on the same side, so overlapping strokes reinforce rather than
+    /// cancel each other under the non-zero fill rule.
+    fn contour(&mut self, curves: &[Cubic], start: Point, closed: bool) {
+        let (first, last) = match (curves.first(), curves.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                self.dot(start);
+                return;
+            }
+        };
+
+        let reversed = curves.iter().rev().map(reverse).collect::<Vec<_>>();
+
+        if closed {
+            self.builder
+                .move_to(first.p0() + normal(start_tangent(first)) * self.radius);
+            self.offset_contour(curves, true);
+            self.close();
+
+            self.builder
+                .move_to(reversed[0].p0() + normal(start_tangent(&reversed[0])) * self.radius);
+            self.offset_contour(&reversed, true);
+            self.close();
+        } else {
+            self.builder
+                .move_to(first.p0() + normal(start_tangent(first)) * self.radius);
+            self.offset_contour(curves, false);
+            self.cap(last.p3(), end_tangent(last));
+            self.offset_contour(&reversed, false);
+            self.cap(first.p0(), -start_tangent(first));
+            self.close();
+        }
+    }
+
+    /// Emits the offset of every curve on its left side, joining consecutive
+    /// curves together. If `closed` is set, the last curve is also joined to
+    /// the first.
+    fn offset_contour(&mut self, curves: &[Cubic], closed: bool) {
+        for (i, curve) in curves.iter().enumerate() {
+            self.offset_curve(curve, 0);
+
+            let next = if i + 1 < curves.len() {
+                &curves[i + 1]
+            } else if closed {
+                &curves[0]
+            } else {
+                continue;
+            };
+
+            self.join(curve.p3(), end_tangent(curve), start_tangent(next));
+        }
+    }
+
+    /// Approximates the offset of `curve` with a single cubic whose end points
+    /// are displaced along the normals of the curve, and whose handles are
+    /// scaled by the change in curvature at either end. The curve is split in
+    /// half until the approximation is within tolerance.
+    fn offset_curve(&mut self, curve: &Cubic, depth: u32) {
+        let r = self.radius;
+        let (p0, p1, p2, p3) = (curve.p0(), curve.p1(), curve.p2(), curve.p3());
+
+        let q0 = p0 + normal(start_tangent(curve)) * r;
+        let q3 = p3 + normal(end_tangent(curve)) * r;
+        let s0 = handle_scale(p1 - p0, p2 - p1, r);
+        let s3 = handle_scale(p3 - p2, p1 - p2, r);
+        let offset = Cubic::new(q0, q0 + (p1 - p0) * s0, q3 + (p2 - p3) * s3, q3);
+
+        if depth < MAX_OFFSET_DEPTH && !self.is_within_tolerance(curve, &offset) {
+            let (left, right) = curve.split(0.5);
+            self.offset_curve(&left, depth + 1);
+            self.offset_curve(&right, depth + 1);
+        } else {
+            self.curve_to(offset.p1(), offset.p2(), offset.p3());
+        }
+    }
+
+    fn is_within_tolerance(&self, curve: &Cubic, offset: &Cubic) -> bool {
+        [0.25, 0.5, 0.75].iter().all(|&t| {
+            let tangent = curve.derivative(t);
+            if tangent.length2() <= DEGENERATE_DISTANCE * DEGENERATE_DISTANCE {
+                return true;
+            }
+
+            let expected = curve.at(t) + normal(tangent.normalize()) * self.radius;
+            (offset.at(t) - expected).length() <= self.tolerance
+        })
+    }
+
+    /// Connects the offset of the curve ending at `point` with tangent
+    /// `incoming` to the offset of the curve leaving it with tangent
+    /// `outgoing`.
+    fn join(&mut self, point: Point, incoming: Vec2, outgoing: Vec2) {
+        let r = self.radius;
+        let n_in = normal(incoming);
+        let n_out = normal(outgoing);
+        let target = point + n_out * r;
+
+        let turn = cross(incoming, outgoing);
+        let cos_theta = incoming.dot(outgoing);
+
+        if turn.abs() <= DEGENERATE_DISTANCE && cos_theta > 0.0 {
+            // The curves meet smoothly.
+            self.line_to(target);
+        } else if turn > 0.0 {
+            // The path turns towards this side, so the offsets overlap. Pivoting
+            // through the corner keeps the outline connected without needing to
+            // find where they intersect.
+            self.line_to(point);
+            self.line_to(target);
+        } else {
+            match self.style.join {
+                LineJoin::Miter => {
+                    // The ratio of the miter length to the stroke width is
+                    // 1 / sin(phi / 2), where phi is the angle between the
+                    // curves. This is the same as 1 / cos(theta / 2), where
+                    // theta is the angle between the tangents.
+                    let cos_half_theta = ((1.0 + cos_theta) * 0.5).max(0.0).sqrt();
+                    if cos_half_theta * self.style.miter_limit >= 1.0 {
+                        let miter = (n_in + n_out).normalize() * (r / cos_half_theta);
+                        self.line_to(point + miter);
+                    }
+                    self.line_to(target);
+                }
+                LineJoin::Round => {
+                    let mut sweep = cross(n_in, n_out).atan2(n_in.dot(n_out));
+                    if sweep > 0.0 {
+                        // The curves double back on themselves. Go around the
+                        // outside of the corner.
+                        sweep -= TAU;
+                    }
+                    self.arc(point, n_in * r, sweep);
+                }
+                LineJoin::Bevel => self.line_to(target),
+            }
+        }
+    }
+
+    /// Caps the end of a subpath at `point`, where `tangent` is the direction
+    /// of travel. The outline is expected to be at the left offset of `point`
+    /// and continues from its right offset.
+    fn cap(&mut self, point: Point, tangent: Vec2) {
+        let n = normal(tangent) * self.radius;
+        let target = point + -n;
+
+        match self.style.cap {
+            LineCap::Butt => self.line_to(target),
+            LineCap::Round => self.arc(point, n, -PI),
+            LineCap::Square => {
+                let extension = tangent * self.radius;
+                self.line_to(point + (n + extension));
+                self.line_to(point + (extension - n));
+                self.line_to(target);
+            }
+        }
+    }
+
+    /// Strokes a subpath of zero length. Only round and square caps produce
+    /// any output, since there is no direction to orient them by.
+    fn dot(&mut self, center: Point) {
+        let r = self.radius;
+
+        match self.style.cap {
+            LineCap::Butt => return,
+            LineCap::Round => {
+                let from = Vec2::new(r, 0.0);
+                self.builder.move_to(center + from);
+                self.arc(center, from, -TAU);
+            }
+            LineCap::Square => {
+                self.builder.move_to(center + Vec2::new(r, r));
+                self.line_to(center + Vec2::new(r, -r));
+                self.line_to(center + Vec2::new(-r, -r));
+                self.line_to(center + Vec2::new(-r, r));
+            }
+        }
+
+        self.close();
+    }
+
+    /// Approximates a circular arc around `center` with cubics, starting at
+    /// `center + from` and sweeping through `sweep` radians. Each cubic covers
+    /// at most a quarter turn.
+    fn arc(&mut self, center: Point, from: Vec2, sweep: f32) {
+        let pieces = (sweep.abs() / FRAC_PI_2).ceil().max(1.0);
+        let step = sweep / pieces;
+        let k = 4.0 / 3.0 * (step / 4.0).tan();
+
+        let mut current = from;
+        for i in 1..=pieces as u32 {
+            let next = rotate(from, step * i as f32);
+            self.curve_to(
+                center + (current + normal(current) * k),
+                center + (next - normal(next) * k),
+                center + next,
+            );
+            current = next;
+        }
+    }
+
+    fn line_to(&mut self, point: Point) {
+        self.builder
+            .line_to(point)
+            .expect("stroke outlines always begin with move_to");
+    }
+
+    fn curve_to(&mut self, p1: Point, p2: Point, p3: Point) {
+        self.builder
+            .add_cubic(p1, p2, p3)
+            .expect("stroke outlines always begin with move_to");
+    }
+
+    fn close(&mut self) {
+        self.builder
+            .close()
+            .expect("stroke outlines always begin with move_to");
+    }
+}
+
+fn is_degenerate(curve: &Cubic) -> bool {
+    let p0 = curve.p0();
+    [curve.p1(), curve.p2(), curve.p3()]
+        .iter()
+        .all(|p| p.approx_eq_within(&p0, DEGENERATE_DISTANCE))
+}
+
+fn reverse(curve: &Cubic) -> Cubic {
+    Cubic::new(curve.p3(), curve.p2(), curve.p1(), curve.p0())
+}
+
+/// The direction of the curve at its first point. If the first control point
+/// coincides with the start of the curve, the next distinct point is used.
+fn start_tangent(curve: &Cubic) -> Vec2 {
+    let p0 = curve.p0();
+    [curve.p1(), curve.p2(), curve.p3()]
+        .iter()
+        .map(|p| *p - p0)
+        .find(|d| d.length() > DEGENERATE_DISTANCE)
+        .map_or(Vec2::new(1.0, 0.0), Vec2::normalize)
+}
+
+/// The direction of the curve at its last point. If the last control point
+/// coincides with the end of the curve, the previous distinct point is used.
+fn end_tangent(curve: &Cubic) -> Vec2 {
+    let p3 = curve.p3();
+    [curve.p2(), curve.p1(), curve.p0()]
+        .iter()
+        .map(|p| p3 - *p)
+        .find(|d| d.length() > DEGENERATE_DISTANCE)
+        .map_or(Vec2::new(1.0, 0.0), Vec2::normalize)
+}
+
+/// The factor by which the handle of a curve changes length when the curve is
+/// offset by `r`. `handle` points along the curve from an end point to its
+/// adjacent control point, or from that control point to the end point at the
+/// end of the curve, and `middle` points from the control point adjacent to the
+/// end point to the other one.
+///
+/// The curvature at an end point of a cubic is `2/3 * (h x m) / |h|^3`, and
+/// offsetting a curve of curvature `k` scales its speed by `1 - r * k`.
+fn handle_scale(handle: Vec2, middle: Vec2, r: f32) -> f32 {
+    let length = handle.length();
+    if length <= DEGENERATE_DISTANCE {
+        return 1.0;
+    }
+
+    let curvature = 2.0 / 3.0 * cross(handle, middle) / (length * length * length);
+    (1.0 - r * curvature).max(0.0)
+}
+
+/// The unit vector perpendicular to `tangent`, pointing to the left of it.
+fn normal(tangent: Vec2) -> Vec2 {
+    Vec2::new(-tangent.y(), tangent.x())
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x() * b.y() - a.y() * b.x()
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x() * cos - v.y() * sin, v.x() * sin + v.y() * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::path::Segment;
+
+    const TOLERANCE: f32 = 0.01;
+
+    /// Builds a single subpath through `points`, which is closed only if the
+    /// last point is the same as the first.
+    fn polyline(points: &[(f32, f32)]) -> Path {
+        let mut x = vec![points[0].0];
+        let mut y = vec![points[0].1];
+        for pair in points.windows(2) {
+            let (x0, y0) = pair[0];
+            let (x1, y1) = pair[1];
+            x.extend([x0 + (x1 - x0) / 3.0, x0 + (x1 - x0) * 2.0 / 3.0, x1]);
+            y.extend([y0 + (y1 - y0) / 3.0, y0 + (y1 - y0) * 2.0 / 3.0, y1]);
+        }
+
+        Path {
+            segments: vec![Segment {
+                length: x.len() as u32,
+                closed: points[0] == points[points.len() - 1],
+            }],
+            x,
+            y,
+        }
+    }
+
+    fn flatten(path: &Path) -> Vec<Vec<Point>> {
+        path.iter()
+            .map(|segment| {
+                segment
+                    .flat_map(|curve| (0..32).map(move |i| curve.at(i as f32 / 32.0)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn winding(path: &Path, point: Point) -> i32 {
+        let mut winding = 0;
+        for polygon in flatten(path) {
+            for (i, a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                let side = cross(b - *a, point - *a);
+                if a.y <= point.y && b.y > point.y && side > 0.0 {
+                    winding += 1;
+                } else if b.y <= point.y && a.y > point.y && side < 0.0 {
+                    winding -= 1;
+                }
+            }
+        }
+        winding
+    }
+
+    fn area(path: &Path) -> f32 {
+        let mut area = 0.0;
+        for polygon in flatten(path) {
+            for (i, a) in polygon.iter().enumerate() {
+                let b = polygon[(i + 1) % polygon.len()];
+                area += a.x * b.y - b.x * a.y;
+            }
+        }
+        area.abs() * 0.5
+    }
+
+    fn is_stroked(outline: &Path, x: f32, y: f32) -> bool {
+        winding(outline, Point::new(x, y)) != 0
+    }
+
+    /// The length and starting point of every subpath in a dashed path.
+    fn dashes(path: &Path) -> Vec<(f32, Point)> {
+        path.iter()
+            .map(|segment| {
+                let curves = segment.collect::<Vec<_>>();
+                let length = curves.iter().map(Bezier::arc_length).sum();
+                (length, curves[0].p0())
+            })
+            .collect()
+    }
+
+    fn assert_dashes(path: &Path, expected: &[(f32, (f32, f32))]) {
+        let actual = dashes(path);
+        assert_eq!(actual.len(), expected.len());
+        for ((length, start), (expected_length, (x, y))) in actual.iter().zip(expected) {
+            assert!(length.approx_eq_within(expected_length, 1e-3));
+            assert!(start.approx_eq_within(&Point::new(*x, *y), 1e-3));
+        }
+    }
+
+    /// A circle of radius 20 around (50, 50), approximated by four cubics.
+    fn circle() -> Path {
+        const K: f32 = 0.552_284_8 * 20.0;
+        Path {
+            segments: vec![Segment {
+                length: 13,
+                closed: true,
+            }],
+            x: vec![
+                70.0,
+                70.0,
+                50.0 + K,
+                50.0,
+                50.0 - K,
+                30.0,
+                30.0,
+                30.0,
+                50.0 - K,
+                50.0,
+                50.0 + K,
+                70.0,
+                70.0,
+            ],
+            y: vec![
+                50.0,
+                50.0 + K,
+                70.0,
+                70.0,
+                70.0,
+                50.0 + K,
+                50.0,
+                50.0 - K,
+                30.0,
+                30.0,
+                30.0,
+                50.0 - K,
+                50.0,
+            ],
+        }
+    }
+
+    fn style(width: f32, cap: LineCap, join: LineJoin) -> StrokeStyle {
+        StrokeStyle {
+            width,
+            cap,
+            join,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_caps() {
+        let line = polyline(&[(10.0, 10.0), (30.0, 10.0)]);
+
+        let butt = stroke(
+            &line,
+            &style(4.0, LineCap::Butt, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(area(&butt).approx_eq_within(&80.0, 1e-3));
+        assert!(!is_stroked(&butt, 9.5, 10.0));
+
+        let square = stroke(
+            &line,
+            &style(4.0, LineCap::Square, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(area(&square).approx_eq_within(&96.0, 1e-3));
+        assert!(is_stroked(&square, 8.5, 11.5));
+
+        let round = stroke(
+            &line,
+            &style(4.0, LineCap::Round, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(area(&round).approx_eq_within(&(80.0 + 4.0 * PI), 1e-2));
+        assert!(is_stroked(&round, 8.5, 10.0));
+        assert!(!is_stroked(&round, 8.5, 11.5));
+    }
+
+    #[test]
+    fn line_joins() {
+        let corner = polyline(&[(0.0, 0.0), (20.0, 0.0), (20.0, 20.0)]);
+        let miter = stroke(
+            &corner,
+            &style(4.0, LineCap::Butt, LineJoin::Miter),
+            TOLERANCE,
+        );
+        let round = stroke(
+            &corner,
+            &style(4.0, LineCap::Butt, LineJoin::Round),
+            TOLERANCE,
+        );
+        let bevel = stroke(
+            &corner,
+            &style(4.0, LineCap::Butt, LineJoin::Bevel),
+            TOLERANCE,
+        );
+
+        for outline in [&miter, &round, &bevel] {
+            assert!(is_stroked(outline, 10.0, 1.5));
+            assert!(is_stroked(outline, 19.0, 1.0));
+            assert!(is_stroked(outline, 21.5, 10.0));
+            assert!(!is_stroked(outline, 10.0, 2.5));
+            assert!(!is_stroked(outline, 17.0, 3.0));
+        }
+
+        assert!(is_stroked(&miter, 21.9, -1.9));
+        assert!(!is_stroked(&round, 21.9, -1.9));
+        assert!(!is_stroked(&bevel, 21.9, -1.9));
+
+        assert!(is_stroked(&round, 21.3, -1.3));
+        assert!(!is_stroked(&bevel, 21.3, -1.3));
+    }
+
+    #[test]
+    fn miter_limit() {
+        // The miter at this corner is about 10 times the stroke width.
+        let spike = polyline(&[(0.0, 0.0), (20.0, 0.0), (0.0, 4.0)]);
+
+        let limited = stroke(
+            &spike,
+            &style(4.0, LineCap::Butt, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(!is_stroked(&limited, 30.0, -1.0));
+        assert!(is_stroked(&limited, 20.0, -1.0));
+
+        let unlimited = StrokeStyle {
+            miter_limit: 20.0,
+            ..style(4.0, LineCap::Butt, LineJoin::Miter)
+        };
+        let unlimited = stroke(&spike, &unlimited, TOLERANCE);
+        assert!(is_stroked(&unlimited, 30.0, -1.0));
+        assert!(!is_stroked(&unlimited, 41.0, -2.0));
+    }
+
+    #[test]
+    fn closed_contour() {
+        let square = polyline(&[
+            (0.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 20.0),
+            (0.0, 20.0),
+            (0.0, 0.0),
+        ]);
+        let outline = stroke(
+            &square,
+            &style(4.0, LineCap::Round, LineJoin::Miter),
+            TOLERANCE,
+        );
+
+        // Closed subpaths are not capped, so every corner, including the one
+        // where the subpath begins and ends, is mitered.
+        for (x, y) in [(-1.9, -1.9), (21.9, -1.9), (21.9, 21.9), (-1.9, 21.9)] {
+            assert!(is_stroked(&outline, x, y));
+        }
+
+        assert!(is_stroked(&outline, 10.0, 1.9));
+        assert!(is_stroked(&outline, 10.0, -1.9));
+        assert!(!is_stroked(&outline, 10.0, -2.1));
+        assert!(!is_stroked(&outline, 10.0, 10.0));
+    }
+
+    #[test]
+    fn curves() {
+        let circle = circle();
+        let outline = stroke(&circle, &style(4.0, LineCap::Butt, LineJoin::Miter), 0.05);
+        for i in 0..64 {
+            let angle = i as f32 / 64.0 * TAU;
+            let at = |r: f32| {
+                let p = Point::new(50.0, 50.0) + rotate(Vec2::new(r, 0.0), angle);
+                is_stroked(&outline, p.x, p.y)
+            };
+
+            assert!(at(18.2) && at(20.0) && at(21.8));
+            assert!(!at(17.8) && !at(22.2));
+        }
+        assert!(!is_stroked(&outline, 50.0, 50.0));
+    }
+
+    #[test]
+    fn offset_arcs() {
+        // Offsetting a circular arc gives another circular arc, which the
+        // offset curve should match without being split more than a little.
+        let circle = circle();
+        for width in [1.0, 4.0, 10.0, 20.0] {
+            let outline = stroke(&circle, &style(width, LineCap::Butt, LineJoin::Miter), 0.05);
+
+            // Each side of the stroke has an offset curve and a join for each
+            // quarter of the circle, and may split each offset curve once.
+            let num_curves = outline.iter().map(|segment| segment.count()).sum::<usize>();
+            assert!(
+                num_curves <= 2 * (4 * 2 + 4),
+                "{num_curves} curves at width {width}"
+            );
+
+            for segment in outline.iter() {
+                for curve in segment {
+                    for i in 0..=8 {
+                        let distance = (curve.at(i as f32 / 8.0) - Point::new(50.0, 50.0)).length();
+                        let error = (distance - 20.0).abs() - width / 2.0;
+                        assert!(error.abs() <= 0.05, "{error} off at width {width}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dash_pattern() {
+        let line = polyline(&[(0.0, 0.0), (100.0, 0.0)]);
+
+        let expected = (0..7)
+            .map(|i| (10.0, (i as f32 * 15.0, 0.0)))
+            .collect::<Vec<_>>();
+        assert_dashes(&dash(&line, &[10.0, 5.0], 0.0), &expected);
+
+        // An odd number of lengths is repeated to make the pattern.
+        let expected = (0..5)
+            .map(|i| (10.0, (i as f32 * 20.0, 0.0)))
+            .collect::<Vec<_>>();
+        assert_dashes(&dash(&line, &[10.0], 0.0), &expected);
+
+        let mut expected = vec![(5.0, (0.0, 0.0))];
+        expected.extend((0..6).map(|i| (10.0, (10.0 + i as f32 * 15.0, 0.0))));
+        assert_dashes(&dash(&line, &[10.0, 5.0], 5.0), &expected);
+
+        let mut expected = (0..6)
+            .map(|i| (10.0, (5.0 + i as f32 * 15.0, 0.0)))
+            .collect::<Vec<_>>();
+        expected.push((5.0, (95.0, 0.0)));
+        assert_dashes(&dash(&line, &[10.0, 5.0], -5.0), &expected);
+
+        // Invalid patterns leave the path solid.
+        for pattern in [&[][..], &[0.0, 0.0], &[10.0, -5.0]] {
+            assert_dashes(&dash(&line, pattern, 0.0), &[(100.0, (0.0, 0.0))]);
+        }
+    }
+
+    #[test]
+    fn dash_closed_contour() {
+        let square = polyline(&[
+            (0.0, 0.0),
+            (20.0, 0.0),
+            (20.0, 20.0),
+            (0.0, 20.0),
+            (0.0, 0.0),
+        ]);
+
+        // The dash that runs over the start of the subpath is joined up.
+        let dashed = dash(&square, &[30.0, 10.0], 10.0);
+        assert_dashes(&dashed, &[(30.0, (0.0, 10.0)), (30.0, (20.0, 10.0))]);
+
+        let outline = stroke(
+            &square,
+            &StrokeStyle {
+                dash_array: vec![30.0, 10.0],
+                dash_offset: 10.0,
+                ..style(4.0, LineCap::Butt, LineJoin::Miter)
+            },
+            TOLERANCE,
+        );
+        assert!(is_stroked(&outline, -1.9, -1.9));
+        assert!(is_stroked(&outline, 21.9, 21.9));
+        assert!(!is_stroked(&outline, 21.9, -1.9));
+        assert!(!is_stroked(&outline, -1.9, 21.9));
+    }
+
+    #[test]
+    fn dash_curves() {
+        let circle = circle();
+        let total = circle.iter().flatten().map(|c| c.arc_length()).sum::<f32>();
+
+        let dashes = dashes(&dash(&circle, &[10.0, 5.0], 0.0));
+        assert_eq!(dashes.len(), 8);
+        assert!(dashes[0].0.approx_eq_within(&(total - 110.0), 1e-2));
+        for (length, _) in &dashes[1..] {
+            assert!(length.approx_eq_within(&10.0, 1e-2));
+        }
+    }
+
+    #[test]
+    fn dash_dots() {
+        let line = polyline(&[(0.0, 0.0), (100.0, 0.0)]);
+
+        let expected = (0..10)
+            .map(|i| (0.0, (i as f32 * 10.0, 0.0)))
+            .collect::<Vec<_>>();
+        assert_dashes(&dash(&line, &[0.0, 10.0], 0.0), &expected);
+
+        let dotted = StrokeStyle {
+            dash_array: vec![0.0, 10.0],
+            ..style(4.0, LineCap::Round, LineJoin::Miter)
+        };
+        let outline = stroke(&line, &dotted, TOLERANCE);
+        assert!(is_stroked(&outline, 10.0, 1.9));
+        assert!(is_stroked(&outline, 90.0, -1.9));
+        assert!(!is_stroked(&outline, 5.0, 0.0));
+    }
+
+    #[test]
+    fn zero_length_subpath() {
+        let point = polyline(&[(10.0, 10.0), (10.0, 10.0)]);
+
+        let butt = stroke(
+            &point,
+            &style(4.0, LineCap::Butt, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(butt.segments.is_empty());
+
+        let round = stroke(
+            &point,
+            &style(4.0, LineCap::Round, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(area(&round).approx_eq_within(&(4.0 * PI), 1e-2));
+
+        let square = stroke(
+            &point,
+            &style(4.0, LineCap::Square, LineJoin::Miter),
+            TOLERANCE,
+        );
+        assert!(area(&square).approx_eq_within(&16.0, 1e-3));
+        assert!(is_stroked(&square, 11.9, 8.1));
+    }
+}
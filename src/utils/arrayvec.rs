@@ -51,7 +51,7 @@ impl<T, const N: usize> ArrayVec<T, N> {
         if self.is_empty() {
             &[]
         } else {
-            unsafe { std::slice::from_raw_parts(self.as_ptr(), self.length as usize) }
+            unsafe { std::slice::from_raw_parts(self.as_ptr(), self.length) }
         }
     }
 
@@ -113,7 +113,6 @@ impl<T, const N: usize> ArrayVec<T, N> {
     }
 
     /// Creates a by-reference iterator over the elements in the vector.
-    #[must_use]
     #[allow(clippy::needless_lifetimes)] // Compiler gets into a cycle w/o parameters
     pub fn iter<'a>(&'a self) -> std::slice::Iter<'a, T> {
         self.as_slice().iter()
@@ -137,7 +136,6 @@ impl<T, const N: usize> ArrayVec<T, N> {
 }
 
 impl<T, const N: usize> Default for ArrayVec<T, N> {
-    #[must_use]
     fn default() -> Self {
         Self {
             array: MaybeUninit::uninit(),
@@ -196,7 +194,7 @@ impl<T, const N: usize> std::ops::Index<usize> for ArrayVec<T, N> {
 impl<T, const N: usize> std::ops::IndexMut<usize> for ArrayVec<T, N> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
         // let slice do the bounds checking for us
-        &mut self.as_mut_slice()[index as usize]
+        &mut self.as_mut_slice()[index]
     }
 }
 
@@ -323,7 +321,7 @@ mod tests {
 
         std::mem::drop(vec);
 
-        unsafe { assert_eq!(K, 3) };
+        assert_eq!(unsafe { K }, 3);
     }
 
     #[test]
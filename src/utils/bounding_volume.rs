@@ -2,74 +2,202 @@
 //!
 
 use crate::{
-    math::vector2::Vec2,
+    math::{
+        cmp::{max, min},
+        vector2::Vec2,
+    },
     shapes::{
         point::Point,
         rect::{BoundingBox, Rect},
     },
 };
 
-/// A simple bounding volume hierarchy implemented as a binary space partition.
-pub struct Bvh<'a, T>
+/// The number of bins that items are sorted into along each axis when
+/// searching for the best place to split a node.
+const NUM_BINS: usize = 16;
+
+/// A simple bounding volume hierarchy implemented as a binary space partition,
+/// built with the surface area heuristic.
+///
+/// The bvh owns its items, and reorders them so that the items in each leaf
+/// are stored next to each other.
+pub struct Bvh<T> {
+    items: Vec<T>,
+    nodes: Vec<Node>,
+    max_items_per_leaf: u32,
+    // Scratch space for refitting the bvh.
+    old_items: Vec<T>,
+    remap: Vec<u32>,
+}
+
+impl<T> Default for Bvh<T>
 where
     T: BoundingBox,
 {
-    items: &'a [T],
-    nodes: Vec<Node>,
-    indirect: Vec<u32>,
+    fn default() -> Self {
+        Self::with_leaf_size(1)
+    }
 }
 
-impl<'a, T> Bvh<'a, T>
+impl<T> Bvh<T>
 where
     T: BoundingBox,
 {
-    /// Constructs a new bounding volume hierarchy from the given list of
-    /// objects.
-    pub fn build(items: &'a [T]) -> Self {
-        Self::rebuild(
-            items,
-            Self {
-                items,
-                nodes: Vec::new(),
-                indirect: Vec::new(),
-            },
-        )
+    /// Creates an empty bounding volume hierarchy that stops subdividing
+    /// nodes once they hold no more than `max_items_per_leaf` objects.
+    pub fn with_leaf_size(max_items_per_leaf: u32) -> Self {
+        debug_assert!(max_items_per_leaf > 0);
+
+        Self {
+            items: Vec::new(),
+            nodes: vec![Node {
+                bbox: Rect::default(),
+                data: Data::Empty,
+            }],
+            max_items_per_leaf,
+            old_items: Vec::new(),
+            remap: Vec::new(),
+        }
     }
 
-    /// Constructs a new bounding volume hierarchy from the given list of
-    /// objects, recycling the memory from an existing bvh.
-    pub fn rebuild(items: &'a [T], old: Self) -> Self {
-        let mut this = Self {
-            items: old.items,
-            nodes: old.nodes,
-            indirect: old.indirect,
-        };
+    /// Replaces the contents of the bounding volume hierarchy with the given
+    /// list of objects, recycling its memory.
+    pub fn rebuild(&mut self, items: impl IntoIterator<Item = T>) {
+        self.items.clear();
+        self.items.extend(items);
+
+        self.nodes.clear();
+        self.nodes.reserve(self.items.len() * 2);
+
+        if self.items.is_empty() {
+            self.nodes.push(Node {
+                bbox: Rect::default(),
+                data: Data::Empty,
+            });
+            return;
+        }
+
+        self.nodes.push(Node {
+            bbox: bvh_impl::compute_bounds(&self.items),
+            data: Data::Leaf(Leaf {
+                first_item: 0,
+                count: self.items.len() as u32,
+            }),
+        });
 
-        this.nodes.clear();
-        this.nodes.reserve(this.items.len() * 2);
+        bvh_impl::subdivide(self, 0);
+    }
 
-        this.indirect.clear();
-        this.indirect.extend(0..this.items.len() as u32);
+    /// Replaces every object with the objects that `update` pushes in its
+    /// place, which may be the object itself, any number of new objects, or
+    /// none at all. Replacements are put in the same leaf as the object they
+    /// replace, leaves that grow too large are subdivided, and the bounds of
+    /// every node are refit to its contents. The rest of the tree is left as
+    /// it was, so this is much cheaper than rebuilding it when objects change
+    /// only a little.
+    pub fn refit<F>(&mut self, mut update: F)
+    where
+        F: FnMut(&T, &mut Vec<T>),
+    {
+        std::mem::swap(&mut self.items, &mut self.old_items);
+        self.items.clear();
+
+        // The new index of each old object, followed by the new number of
+        // objects.
+        self.remap.clear();
+        for item in &self.old_items {
+            self.remap.push(self.items.len() as u32);
+            update(item, &mut self.items);
+        }
+        self.remap.push(self.items.len() as u32);
+        self.old_items.clear();
+
+        for node in &mut self.nodes {
+            if let Data::Leaf(leaf) = &mut node.data {
+                let first_item = self.remap[leaf.first_item as usize];
+                let end = self.remap[(leaf.first_item + leaf.count) as usize];
+                *leaf = Leaf {
+                    first_item,
+                    count: end - first_item,
+                };
+            }
+        }
 
-        bvh_impl::build(&mut this);
+        for node_idx in 0..self.nodes.len() {
+            if matches!(self.nodes[node_idx].data, Data::Leaf(_)) {
+                bvh_impl::subdivide(self, node_idx);
+            }
+        }
+
+        // Children always come after their parents, so walking the nodes
+        // backwards refits every child before its parent.
+        for node_idx in (0..self.nodes.len()).rev() {
+            let (bbox, data) = match self.nodes[node_idx].data {
+                Data::Empty => continue,
+                Data::Leaf(leaf) if leaf.count == 0 => (Rect::default(), Data::Empty),
+                Data::Leaf(leaf) => (
+                    bvh_impl::compute_bounds(bvh_impl::leaf_items(self, leaf)),
+                    Data::Leaf(leaf),
+                ),
+                Data::Branch(branch) => {
+                    let left = &self.nodes[branch.left_child as usize];
+                    let right = &self.nodes[branch.left_child as usize + 1];
+                    match (&left.data, &right.data) {
+                        (Data::Empty, Data::Empty) => (Rect::default(), Data::Empty),
+                        (Data::Empty, _) => (right.bbox, Data::Branch(branch)),
+                        (_, Data::Empty) => (left.bbox, Data::Branch(branch)),
+                        _ => (left.bbox | right.bbox, Data::Branch(branch)),
+                    }
+                }
+            };
 
-        this
+            self.nodes[node_idx] = Node { bbox, data };
+        }
     }
 
     /// Retrieves the list of objects in the bvh.
     pub fn items(&self) -> &[T] {
-        self.items
+        &self.items
+    }
+
+    /// Calls `f` with every object whose bounds pass `test`. Nodes whose bounds
+    /// fail `test` are skipped along with everything beneath them.
+    pub fn query<'t, P, F>(&'t self, test: P, mut f: F)
+    where
+        P: Fn(&Rect) -> bool,
+        F: FnMut(&'t T),
+    {
+        bvh_impl::visit(self, 0, &test, &mut f);
     }
 
     /// Computes the list of objects that intersect the given rectangle.
     pub fn query_rect_intersection<'t>(&'t self, rect: Rect, out: &mut Vec<&'t T>) {
-        bvh_impl::intersect_rect(self, 0, rect, out)
+        self.query(
+            |bbox: &Rect| bbox.intersects_with(&rect),
+            |item: &'t T| out.push(item),
+        );
+    }
+
+    /// Computes the list of objects whose bounds intersect the ray cast from
+    /// `p` in the direction of `dir`. The ray includes its origin.
+    pub fn query_ray_intersection<'t>(&'t self, p: Point, dir: Vec2, out: &mut Vec<&'t T>) {
+        self.query(
+            |bbox: &Rect| bvh_impl::ray_hits(bbox, p, dir),
+            |item: &'t T| out.push(item),
+        );
     }
 
-    /// Computes the list of objects that intersect the given ray.
-    pub fn query_ray_intersection<'t>(&'t self, _p: Point, _dir: Vec2, _out: &mut Vec<&'t T>) {
-        // bvh_impl::intersect_ray(self, 0, p, dir, out)
-        todo!()
+    /// Finds the object nearest to `p`, along with its distance from `p` as
+    /// measured by `distance`. The distance to an object must never be less
+    /// than the distance to its bounding box, which is used to rule out
+    /// objects without measuring them.
+    pub fn query_nearest<F>(&self, p: Point, mut distance: F) -> Option<(&T, f32)>
+    where
+        F: FnMut(&T) -> f32,
+    {
+        let mut nearest = None;
+        bvh_impl::nearest(self, 0, p, &mut distance, &mut nearest);
+        nearest
     }
 }
 
@@ -88,175 +216,259 @@ enum Data {
 
 #[derive(Clone, Copy, Debug)]
 struct Leaf {
-    first_indirect: u32,
+    first_item: u32,
     count: u32,
 }
 
 #[derive(Clone, Copy, Debug)]
 struct Branch {
+    /// The index of the left child. The right child follows it.
     left_child: u32,
 }
 
 mod bvh_impl {
     use super::*;
 
-    pub(super) fn build<T>(bvh: &mut Bvh<T>)
+    pub(super) fn compute_bounds<T>(items: &[T]) -> Rect
     where
         T: BoundingBox,
     {
-        if bvh.items.is_empty() {
-            bvh.nodes.push(Node {
-                bbox: Rect::default(),
-                data: Data::Empty,
-            });
-            return;
+        let mut aabb = items[0].bounding_box();
+        for item in &items[1..] {
+            aabb |= item.bounding_box();
         }
+        aabb
+    }
 
-        let aabb = bvh.items.iter().fold(
-            Rect::new(f32::MAX, f32::MIN, f32::MAX, f32::MIN),
-            |aabb, item| aabb | item.bounding_box(),
-        );
+    pub(super) fn leaf_items<T>(bvh: &Bvh<T>, leaf: Leaf) -> &[T] {
+        &bvh.items[leaf.first_item as usize..(leaf.first_item + leaf.count) as usize]
+    }
 
-        bvh.nodes.push(Node {
-            bbox: aabb,
-            data: Data::Leaf(Leaf {
-                first_indirect: 0,
-                count: bvh.items.len() as u32,
-            }),
-        });
+    /// Calls `f` with every object whose bounds pass `test`, skipping any node
+    /// whose bounds don't.
+    pub(super) fn visit<'a, T, P, F>(bvh: &'a Bvh<T>, node_idx: usize, test: &P, f: &mut F)
+    where
+        T: BoundingBox,
+        P: Fn(&Rect) -> bool,
+        F: FnMut(&'a T),
+    {
+        let node = &bvh.nodes[node_idx];
+        match node.data {
+            Data::Empty => {}
+            Data::Leaf(leaf) => {
+                if test(&node.bbox) {
+                    for item in leaf_items(bvh, leaf) {
+                        if test(&item.bounding_box()) {
+                            f(item);
+                        }
+                    }
+                }
+            }
+            Data::Branch(branch) => {
+                if test(&node.bbox) {
+                    visit(bvh, branch.left_child as usize, test, f);
+                    visit(bvh, branch.left_child as usize + 1, test, f);
+                }
+            }
+        }
+    }
+
+    /// Tests a ray against a box by clipping it to the slab between each pair
+    /// of opposite sides in turn.
+    pub(super) fn ray_hits(bbox: &Rect, p: Point, dir: Vec2) -> bool {
+        let mut t_min = 0.0;
+        let mut t_max = f32::INFINITY;
+
+        for (origin, dir, low, high) in [
+            (p.x, dir.x(), bbox.left, bbox.right),
+            (p.y, dir.y(), bbox.top, bbox.bottom),
+        ] {
+            if dir == 0.0 {
+                // The ray runs parallel to the slab, so it is either always
+                // or never inside of it.
+                if origin < low || origin > high {
+                    return false;
+                }
+            } else {
+                let t0 = (low - origin) / dir;
+                let t1 = (high - origin) / dir;
+                t_min = max!(t_min, min!(t0, t1));
+                t_max = min!(t_max, max!(t0, t1));
+            }
+        }
 
-        subdivide(bvh, 0);
+        t_min <= t_max
     }
 
-    pub(super) fn intersect_rect<'a, T>(
+    pub(super) fn nearest<'a, T, F>(
         bvh: &'a Bvh<T>,
         node_idx: usize,
-        rect: Rect,
-        out: &mut Vec<&'a T>,
+        p: Point,
+        distance: &mut F,
+        nearest: &mut Option<(&'a T, f32)>,
     ) where
         T: BoundingBox,
+        F: FnMut(&T) -> f32,
     {
+        let is_closer = |d: f32, nearest: &Option<(&T, f32)>| nearest.is_none_or(|(_, n)| d < n);
+
         let node = &bvh.nodes[node_idx];
         match node.data {
             Data::Empty => {}
             Data::Leaf(leaf) => {
-                for item in leaf_items_indirect(bvh, node_idx) {
-                    if rect.intersects_with(&item.bounding_box()) {
-                        out.push(item);
+                for item in leaf_items(bvh, leaf) {
+                    if is_closer(item.bounding_box().distance_to(p), nearest) {
+                        let d = distance(item);
+                        if is_closer(d, nearest) {
+                            *nearest = Some((item, d));
+                        }
                     }
                 }
             }
             Data::Branch(branch) => {
-                let left = &bvh.nodes[branch.left_child as usize];
-                let right = &bvh.nodes[branch.left_child as usize + 1];
-
-                if left.bbox.intersects_with(&rect) {
-                    intersect_rect(bvh, branch.left_child as usize, rect, out);
-                }
-
-                if right.bbox.intersects_with(&rect) {
-                    intersect_rect(bvh, branch.left_child as usize + 1, rect, out);
+                let left = branch.left_child as usize;
+                let right = left + 1;
+                let left_distance = bvh.nodes[left].bbox.distance_to(p);
+                let right_distance = bvh.nodes[right].bbox.distance_to(p);
+
+                // Searching the nearer child first makes it more likely that
+                // the other can be skipped entirely.
+                let children = if left_distance <= right_distance {
+                    [(left, left_distance), (right, right_distance)]
+                } else {
+                    [(right, right_distance), (left, left_distance)]
+                };
+
+                for (child, child_distance) in children {
+                    if is_closer(child_distance, nearest) {
+                        self::nearest(bvh, child, p, distance, nearest);
+                    }
                 }
             }
         }
     }
 
+    #[derive(Clone, Copy)]
     enum SplitAxis {
         X,
         Y,
     }
 
+    impl SplitAxis {
+        fn of(self, point: Point) -> f32 {
+            match self {
+                SplitAxis::X => point.x,
+                SplitAxis::Y => point.y,
+            }
+        }
+    }
+
+    /// A plane dividing the objects in a node, found by binning their
+    /// centroids.
     struct Split {
         axis: SplitAxis,
-        position: f32,
-        cost: f32,
+        /// The lower end of the first bin along `axis`.
+        start: f32,
+        /// The number of bins per unit along `axis`.
+        scale: f32,
+        /// Objects in this bin or any before it go to the left child.
+        last_left_bin: usize,
     }
 
-    fn compute_bounds_indirect<T>(items: &[T], indirect: &[u32]) -> Rect
-    where
-        T: BoundingBox,
-    {
-        let mut aabb = items[indirect[0] as usize].bounding_box();
-        for indirect in &indirect[1..] {
-            aabb |= items[*indirect as usize].bounding_box();
+    impl Split {
+        fn is_left<T: BoundingBox>(&self, item: &T) -> bool {
+            let centroid = item.bounding_box().centroid();
+            bin_index(self.axis.of(centroid), self.start, self.scale) <= self.last_left_bin
         }
-        aabb
     }
 
-    fn leaf_items_indirect<'a, T>(bvh: &'a Bvh<T>, node_idx: usize) -> impl Iterator<Item = &'a T>
-    where
-        T: BoundingBox,
-    {
-        let node = &bvh.nodes[node_idx];
-        match &node.data {
-            Data::Leaf(leaf) => bvh.indirect
-                [leaf.first_indirect as usize..(leaf.first_indirect + leaf.count) as usize]
-                .iter()
-                .map(|i| &bvh.items[*i as usize]),
-            _ => panic!("expected leaf node"),
+    #[derive(Clone, Copy, Default)]
+    struct Bin {
+        bounds: Option<Rect>,
+        count: usize,
+    }
+
+    impl Bin {
+        fn add(&mut self, bounds: Rect, count: usize) {
+            self.bounds = Some(self.bounds.map_or(bounds, |b| b | bounds));
+            self.count += count;
+        }
+
+        /// The surface area heuristic. In two dimensions, the chance of a
+        /// query hitting a box is proportional to its perimeter rather than
+        /// its area, which also keeps boxes around horizontal or vertical
+        /// lines from appearing to cost nothing.
+        fn cost(&self) -> f32 {
+            self.bounds
+                .map_or(0.0, |b| self.count as f32 * (b.width() + b.height()))
         }
     }
 
-    pub fn subdivide<T>(bvh: &mut Bvh<T>, node_idx: usize)
+    fn bin_index(value: f32, start: f32, scale: f32) -> usize {
+        (((value - start) * scale) as usize).min(NUM_BINS - 1)
+    }
+
+    /// Splits a leaf in two, recursing until every leaf holds no more than
+    /// the bvh's maximum number of objects.
+    pub(super) fn subdivide<T>(bvh: &mut Bvh<T>, node_idx: usize)
     where
         T: BoundingBox,
     {
-        let node = &mut bvh.nodes[node_idx];
-        let leaf = match node.data {
+        let leaf = match bvh.nodes[node_idx].data {
             Data::Leaf(leaf) => leaf,
             _ => panic!("expected leaf node"),
         };
-        let node_cost = leaf.count as f32 * node.bbox.area();
 
-        let best_split = find_split_axis(bvh, node_idx);
-
-        if node_cost < best_split.cost {
+        if leaf.count <= bvh.max_items_per_leaf {
             return;
         }
 
-        let mut i = leaf.first_indirect as isize;
-        let mut j = i + leaf.count as isize - 1;
-
-        while i <= j {
-            let centroid = bvh.items[bvh.indirect[i as usize] as usize]
-                .bounding_box()
-                .centroid();
+        let items =
+            &mut bvh.items[leaf.first_item as usize..(leaf.first_item + leaf.count) as usize];
 
-            let value = match best_split.axis {
-                SplitAxis::X => centroid.x,
-                SplitAxis::Y => centroid.y,
-            };
+        let left_count = match find_split(items) {
+            Some(split) => partition(items, |item| split.is_left(item)),
+            None => 0,
+        };
 
-            if value < best_split.position {
-                i += 1;
+        // Objects whose centroids can't be told apart are split in half so
+        // that the tree stays balanced.
+        let left_count = if left_count == 0 || left_count == items.len() {
+            let centroids = centroid_bounds(items);
+            let axis = if centroids.width() >= centroids.height() {
+                SplitAxis::X
             } else {
-                bvh.indirect.swap(i as usize, j as usize);
-                j -= 1;
-            }
-        }
+                SplitAxis::Y
+            };
 
-        let left_count = i as usize - leaf.first_indirect as usize;
-        if left_count == 0 || left_count == leaf.count as usize {
-            return;
-        }
+            let middle = items.len() / 2;
+            items.select_nth_unstable_by(middle, |a, b| {
+                let a = axis.of(a.bounding_box().centroid());
+                let b = axis.of(b.bounding_box().centroid());
+                a.total_cmp(&b)
+            });
+            middle
+        } else {
+            left_count
+        };
 
-        let indirect = &mut bvh.indirect
-            [leaf.first_indirect as usize..(leaf.first_indirect + leaf.count) as usize];
+        let (left, right) = items.split_at(left_count);
+        let left_bbox = compute_bounds(left);
+        let right_bbox = compute_bounds(right);
 
         let left_child_idx = bvh.nodes.len();
         bvh.nodes.push(Node {
-            bbox: compute_bounds_indirect(bvh.items, &indirect[..left_count]),
+            bbox: left_bbox,
             data: Data::Leaf(Leaf {
-                first_indirect: leaf.first_indirect,
+                first_item: leaf.first_item,
                 count: left_count as u32,
             }),
         });
 
-        let right_child_idx = bvh.nodes.len();
         bvh.nodes.push(Node {
-            bbox: compute_bounds_indirect(bvh.items, &indirect[left_count..]),
+            bbox: right_bbox,
             data: Data::Leaf(Leaf {
-                first_indirect: i as u32,
+                first_item: leaf.first_item + left_count as u32,
                 count: leaf.count - left_count as u32,
             }),
         });
@@ -266,78 +478,101 @@ mod bvh_impl {
         });
 
         subdivide(bvh, left_child_idx);
-        subdivide(bvh, right_child_idx);
+        subdivide(bvh, left_child_idx + 1);
     }
 
-    fn find_split_axis<T>(bvh: &Bvh<T>, node_idx: usize) -> Split
+    /// Finds the split between bins with the lowest cost according to the
+    /// surface area heuristic, or `None` if the centroids of the objects all
+    /// coincide.
+    fn find_split<T>(items: &[T]) -> Option<Split>
     where
         T: BoundingBox,
     {
-        let mut best_pos = 0.0;
-        let mut best_axis = SplitAxis::X;
-        let mut best_cost = f32::MAX;
-
-        for item in leaf_items_indirect(bvh, node_idx) {
-            let candidate_pos = item.bounding_box().centroid();
-
-            let cost_x = evalate_sah(bvh, node_idx, SplitAxis::X, candidate_pos.x);
-            if cost_x < best_cost {
-                best_cost = cost_x;
-                best_axis = SplitAxis::X;
-                best_pos = candidate_pos.x;
+        let centroids = centroid_bounds(items);
+
+        let mut best: Option<(Split, f32)> = None;
+        for (axis, start, extent) in [
+            (SplitAxis::X, centroids.left, centroids.width()),
+            (SplitAxis::Y, centroids.top, centroids.height()),
+        ] {
+            if extent <= 0.0 {
+                continue;
             }
 
-            let cost_y = evalate_sah(bvh, node_idx, SplitAxis::Y, candidate_pos.y);
-            if cost_y < best_cost {
-                best_cost = cost_y;
-                best_axis = SplitAxis::Y;
-                best_pos = candidate_pos.y;
+            let scale = NUM_BINS as f32 / extent;
+            let mut bins = [Bin::default(); NUM_BINS];
+            for item in items {
+                let bounds = item.bounding_box();
+                bins[bin_index(axis.of(bounds.centroid()), start, scale)].add(bounds, 1);
             }
-        }
 
-        Split {
-            axis: best_axis,
-            position: best_pos,
-            cost: best_cost,
+            // The cost of everything right of each split, accumulated from
+            // the right so that both sides can be computed in a single sweep.
+            let mut right_costs = [0.0; NUM_BINS];
+            let mut right = Bin::default();
+            for i in (1..NUM_BINS).rev() {
+                if let Some(bounds) = bins[i].bounds {
+                    right.add(bounds, bins[i].count);
+                }
+                right_costs[i - 1] = right.cost();
+            }
+
+            let mut left = Bin::default();
+            for (i, bin) in bins[..NUM_BINS - 1].iter().enumerate() {
+                if let Some(bounds) = bin.bounds {
+                    left.add(bounds, bin.count);
+                }
+
+                if left.count == 0 || left.count == items.len() {
+                    continue;
+                }
+
+                let cost = left.cost() + right_costs[i];
+                if best.as_ref().is_none_or(|(_, best_cost)| cost < *best_cost) {
+                    let split = Split {
+                        axis,
+                        start,
+                        scale,
+                        last_left_bin: i,
+                    };
+                    best = Some((split, cost));
+                }
+            }
         }
+
+        best.map(|(split, _)| split)
     }
 
-    fn evalate_sah<T>(bvh: &Bvh<T>, node_idx: usize, axis: SplitAxis, pos: f32) -> f32
+    fn centroid_bounds<T>(items: &[T]) -> Rect
     where
         T: BoundingBox,
     {
-        let mut left = None;
-        let mut right = None;
-        let mut num_left = 0;
-        let mut num_right = 0;
-
-        for item in leaf_items_indirect(bvh, node_idx) {
-            let aabb = item.bounding_box();
-            let centroid = aabb.centroid();
-
-            let value = match axis {
-                SplitAxis::X => centroid.x,
-                SplitAxis::Y => centroid.y,
-            };
+        let first = items[0].bounding_box().centroid();
+        items[1..].iter().fold(
+            Rect::new(first.x, first.x, first.y, first.y),
+            |bounds, item| {
+                let c = item.bounding_box().centroid();
+                Rect::new(
+                    min!(bounds.left, c.x),
+                    max!(bounds.right, c.x),
+                    min!(bounds.top, c.y),
+                    max!(bounds.bottom, c.y),
+                )
+            },
+        )
+    }
 
-            if value < pos {
-                num_left += 1;
-                left = left.map_or(Some(aabb), |b: Rect| Some(b | aabb));
-            } else {
-                num_right += 1;
-                right = right.map_or(Some(aabb), |b: Rect| Some(b | aabb));
+    /// Moves the objects for which `is_left` returns true to the front of the
+    /// slice, returning how many there are.
+    fn partition<T>(items: &mut [T], is_left: impl Fn(&T) -> bool) -> usize {
+        let mut i = 0;
+        for j in 0..items.len() {
+            if is_left(&items[j]) {
+                items.swap(i, j);
+                i += 1;
             }
         }
-
-        let left_cost = num_left as f32 * left.map_or(0.0, |b| b.area());
-        let right_cost = num_right as f32 * right.map_or(0.0, |b| b.area());
-        let cost = left_cost + right_cost;
-
-        if cost > 0.0 {
-            cost
-        } else {
-            f32::MAX
-        }
+        i
     }
 }
 
@@ -352,7 +587,7 @@ mod tests {
 
     #[test]
     fn empty_bvh() {
-        let bvh = Bvh::<Rect>::build(&[]);
+        let bvh = Bvh::<Rect>::default();
         assert_eq!(bvh.items().len(), 0);
         let mut out = Vec::new();
         bvh.query_rect_intersection(Rect::new(1.0, 2.0, 1.0, 2.0), &mut out);
@@ -394,7 +629,8 @@ mod tests {
         assert!(rects[2].intersects_with(&rects[4]));
         assert!(rects[3].intersects_with(&rects[4]));
 
-        let bvh = Bvh::<Rect>::build(&rects);
+        let mut bvh = Bvh::default();
+        bvh.rebuild(rects);
         assert_eq!(bvh.nodes.len(), rects.len() * 2 - 1);
 
         {
@@ -433,4 +669,133 @@ mod tests {
             assert!(out.is_empty());
         }
     }
+
+    /// A grid of unit squares with their top-left corners at multiples of 3.
+    fn grid(size: usize) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        for i in 0..size {
+            for j in 0..size {
+                let (x, y) = (i as f32 * 3.0, j as f32 * 3.0);
+                rects.push(Rect::new(x, x + 1.0, y, y + 1.0));
+            }
+        }
+        rects
+    }
+
+    /// Checks that every leaf holds no more than `max_items` objects, which
+    /// all lie within the bounds of each of its ancestors. Returns the depth
+    /// of the tree.
+    fn check<T: BoundingBox>(bvh: &Bvh<T>, node_idx: usize, max_items: u32) -> usize {
+        let node = &bvh.nodes[node_idx];
+        match node.data {
+            Data::Empty => 0,
+            Data::Leaf(leaf) => {
+                assert!(leaf.count <= max_items);
+                for item in bvh_impl::leaf_items(bvh, leaf) {
+                    assert_eq!(item.bounding_box() | node.bbox, node.bbox);
+                }
+                0
+            }
+            Data::Branch(branch) => {
+                let left = branch.left_child as usize;
+                let mut depth = 0;
+                for child in [left, left + 1] {
+                    if !matches!(bvh.nodes[child].data, Data::Empty) {
+                        assert_eq!(bvh.nodes[child].bbox | node.bbox, node.bbox);
+                    }
+                    depth = depth.max(check(bvh, child, max_items) + 1);
+                }
+                depth
+            }
+        }
+    }
+
+    #[test]
+    fn balanced() {
+        let rects = grid(40);
+        let mut bvh = Bvh::with_leaf_size(4);
+        bvh.rebuild(rects.iter().copied());
+        assert_eq!(bvh.items().len(), rects.len());
+
+        // A balanced tree with 4 objects per leaf would be 9 levels deep.
+        assert!(check(&bvh, 0, 4) <= 12);
+    }
+
+    #[test]
+    fn queries() {
+        let rects = grid(10);
+        let mut bvh = Bvh::default();
+        bvh.rebuild(rects.iter().copied());
+
+        let mut out = Vec::new();
+        bvh.query_rect_intersection(Rect::new(2.5, 6.5, 2.5, 3.5), &mut out);
+        assert_eq!(out.len(), 2);
+
+        // A ray along the second row, starting inside of its third square.
+        out.clear();
+        bvh.query_ray_intersection(Point::new(6.5, 3.5), Vec2::new(1.0, 0.0), &mut out);
+        assert_eq!(out.len(), 8);
+        assert!(out.iter().all(|rect| rect.top == 3.0 && rect.left >= 6.0));
+
+        // A diagonal ray through the squares at (3, 3), (6, 6)...
+        out.clear();
+        bvh.query_ray_intersection(Point::new(2.0, 2.0), Vec2::new(1.0, 1.0), &mut out);
+        assert_eq!(out.len(), 9);
+
+        // Pointing away from the grid.
+        out.clear();
+        bvh.query_ray_intersection(Point::new(-1.0, -1.0), Vec2::new(-1.0, 0.5), &mut out);
+        assert!(out.is_empty());
+
+        let center = |rect: &Rect| rect.centroid();
+        let (nearest, distance) = bvh
+            .query_nearest(Point::new(10.0, 20.0), |rect| {
+                (center(rect) - Point::new(10.0, 20.0)).length()
+            })
+            .unwrap();
+        assert_eq!(*nearest, Rect::new(9.0, 10.0, 18.0, 19.0));
+        assert!((distance - (0.5f32 * 0.5 + 1.5 * 1.5).sqrt()).abs() < 1e-6);
+
+        assert!(Bvh::<Rect>::default()
+            .query_nearest(Point::new(0.0, 0.0), |_| 0.0)
+            .is_none());
+    }
+
+    #[test]
+    fn refit() {
+        let rects = grid(20);
+        let mut bvh = Bvh::with_leaf_size(4);
+        bvh.rebuild(rects.iter().copied());
+        let num_nodes = bvh.nodes.len();
+
+        // Cut every square in the first column into four, and remove every
+        // square in the last row.
+        bvh.refit(|rect, out| {
+            if rect.bottom > 57.0 {
+                return;
+            }
+
+            if rect.left == 0.0 {
+                for (dx, dy) in [(0.0, 0.0), (0.5, 0.0), (0.0, 0.5), (0.5, 0.5)] {
+                    let left = rect.left + dx;
+                    let top = rect.top + dy;
+                    out.push(Rect::new(left, left + 0.5, top, top + 0.5));
+                }
+            } else {
+                out.push(*rect);
+            }
+        });
+
+        assert_eq!(bvh.items().len(), 19 * 19 + 19 * 4);
+        assert!(bvh.nodes.len() > num_nodes);
+        check(&bvh, 0, 4);
+
+        let mut out = Vec::new();
+        bvh.query_rect_intersection(Rect::new(0.0, 0.25, 0.0, 100.0), &mut out);
+        assert_eq!(out.len(), 19 * 2);
+
+        out.clear();
+        bvh.query_rect_intersection(Rect::new(0.0, 100.0, 57.0, 100.0), &mut out);
+        assert!(out.is_empty());
+    }
 }